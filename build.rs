@@ -0,0 +1,8 @@
+#[cfg(feature = "node")]
+extern crate napi_build;
+
+fn main() {
+    // Link flags needed by Node.js addons.
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
@@ -0,0 +1,35 @@
+//! Decode arbitrary frames as commands, as responses to every command and
+//! as dump files, none of which may panic, and check that whatever decodes
+//! encodes back to the same frame.
+//!
+//! For responses the first byte selects the command, the rest is the frame.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate srix4k;
+
+use std::convert::TryFrom;
+
+use srix4k::codec::Response;
+use srix4k::dump::Srix4kDump;
+use srix4k::Command;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(command) = Command::try_from(data) {
+        assert_eq!(Vec::from(command), data);
+    }
+    if let Some((&selector, frame)) = data.split_first() {
+        let command = match selector % 3 {
+            0 => Command::ReadBlock(selector),
+            1 => Command::WriteBlock(selector, 0),
+            _ => Command::GetUid,
+        };
+        match Response::decode(&command, frame) {
+            Ok(Some(response)) => assert_eq!(Vec::from(response), frame),
+            Ok(None) => assert!(frame.is_empty()),
+            Err(_) => {}
+        }
+    }
+    let _ = Srix4kDump::parse(data);
+});
@@ -0,0 +1,7 @@
+// The .proto is compiled with protox, so building needs no protoc.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/srix4k.proto");
+    let descriptors = protox::compile(["proto/srix4k.proto"], ["proto"])?;
+    tonic_build::configure().compile_fds(descriptors)?;
+    Ok(())
+}
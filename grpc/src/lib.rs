@@ -0,0 +1,508 @@
+//! gRPC service of the operations on the tag in the field of a reader, see
+//! `proto/srix4k.proto`.
+//!
+//! A `Monitor` borrows the `nfc1::Context` it was opened from, so it cannot
+//! be held by the service, which tonic requires to be `'static`, and the
+//! commands of concurrent requests must not interleave on the reader. So
+//! the reader is opened and owned by the worker thread of the `Service`:
+//! the handlers send it the operations to run on the tag through a channel
+//! and await their result, one operation at a time, and the worker polls
+//! the field between operations for the `Events` streams.
+
+// The operations fail with the `Status` of the response, as tonic wants.
+#![allow(clippy::result_large_err)]
+
+use std::error::Error;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use srix4k::codec::Response;
+use srix4k::dump::Srix4kDump;
+use srix4k::mem::{self, BlockAddr, MemoryLayout};
+use srix4k::Command;
+use tokio::sync::{broadcast, oneshot};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Status};
+
+/// Messages and service generated from `proto/srix4k.proto`.
+pub mod proto {
+    tonic::include_proto!("srix4k");
+}
+
+use proto::tag_event::Kind;
+use proto::TagEvent;
+
+/// Time between two checks of the field by the worker, by default.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Events kept for an `Events` stream not keeping up, older ones are
+/// dropped.
+const EVENT_CAPACITY: usize = 16;
+
+/// Reader of the tags, owned by the worker thread of a `Service`.
+pub trait Reader {
+    /// Check whether a tag entered or left the field, selecting the tag
+    /// that entered.
+    fn poll(&mut self) -> Option<TagEvent>;
+    /// UID of the selected tag, `None` if there is no tag in the field.
+    fn uid(&self) -> Option<u64>;
+    /// Send `command` to the selected tag.
+    fn execute(
+        &mut self,
+        command: &Command,
+    ) -> Result<Option<Response>, Box<dyn Error>>;
+}
+
+#[cfg(feature = "nfc")]
+impl Reader for srix4k::monitor::Monitor<'_> {
+    fn poll(&mut self) -> Option<TagEvent> {
+        use srix4k::monitor::{Monitor, TagEvent};
+        Monitor::poll(self).map(|event| match event {
+            TagEvent::Arrival(uid) => tag_event(Kind::Arrival, uid),
+            TagEvent::Removal(uid) => tag_event(Kind::Removal, uid),
+        })
+    }
+    fn uid(&self) -> Option<u64> {
+        srix4k::monitor::Monitor::uid(self)
+    }
+    fn execute(
+        &mut self,
+        command: &Command,
+    ) -> Result<Option<Response>, Box<dyn Error>> {
+        use srix4k::replay::Transport;
+        let tag = self.tag().ok_or("no tag in the field")?;
+        Ok(tag.execute(command)?)
+    }
+}
+
+fn tag_event(kind: Kind, uid: u64) -> TagEvent {
+    TagEvent {
+        kind: kind as i32,
+        uid,
+    }
+}
+
+/// Operation run on the reader by the worker.
+type Job = Box<dyn FnOnce(&mut dyn Reader) + Send>;
+
+/// Worker thread of a `Service`, see `Service::spawn`.
+pub struct Worker {
+    jobs: mpsc::Receiver<Job>,
+    events: broadcast::Sender<TagEvent>,
+    poll_interval: Duration,
+}
+
+impl Worker {
+    /// Run the operations of the service on `reader`, polling the field
+    /// in between, until the service is dropped.
+    pub fn run(self, reader: &mut dyn Reader) {
+        self.poll(reader);
+        let mut last_poll = Instant::now();
+        loop {
+            let wait = self.poll_interval.saturating_sub(last_poll.elapsed());
+            match self.jobs.recv_timeout(wait) {
+                Ok(job) => {
+                    // A tag may have entered since the last poll.
+                    if reader.uid().is_none() {
+                        self.poll(reader);
+                        last_poll = Instant::now();
+                    }
+                    job(reader);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+            if last_poll.elapsed() >= self.poll_interval {
+                self.poll(reader);
+                last_poll = Instant::now();
+            }
+        }
+    }
+    fn poll(&self, reader: &mut dyn Reader) {
+        if let Some(event) = reader.poll() {
+            // Fails only if no stream is open.
+            let _ = self.events.send(event);
+        }
+    }
+}
+
+/// gRPC service of the reader owned by its worker thread.
+#[derive(Debug, Clone)]
+pub struct Service {
+    jobs: mpsc::Sender<Job>,
+    events: broadcast::Sender<TagEvent>,
+}
+
+impl Service {
+    /// Start the worker thread and call `open` on it, which opens the
+    /// reader and runs the worker with `Worker::run`. Once `open` returns,
+    /// e.g. because the reader cannot be opened, every request fails with
+    /// UNAVAILABLE.
+    pub fn spawn<F>(poll_interval: Duration, open: F) -> Service
+    where
+        F: FnOnce(Worker) + Send + 'static,
+    {
+        let (jobs, receiver) = mpsc::channel();
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let worker = Worker {
+            jobs: receiver,
+            events: events.clone(),
+            poll_interval,
+        };
+        thread::spawn(move || open(worker));
+        Service { jobs, events }
+    }
+
+    /// Run `operation` on the worker thread and return its result.
+    async fn run<T, F>(&self, operation: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut dyn Reader) -> Result<T, Status> + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        let job: Job = Box::new(move |reader| {
+            // Fails only if the request was cancelled.
+            let _ = reply.send(operation(reader));
+        });
+        let stopped = || Status::unavailable("the reader is closed");
+        self.jobs.send(job).map_err(|_| stopped())?;
+        result.await.map_err(|_| stopped())?
+    }
+}
+
+/// Stream of the `Events` request.
+type EventStream = Pin<Box<dyn Stream<Item = Result<TagEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl proto::tag_server::Tag for Service {
+    async fn get_uid(
+        &self,
+        _: Request<proto::GetUidRequest>,
+    ) -> Result<tonic::Response<proto::GetUidResponse>, Status> {
+        let uid = self.run(get_uid).await?;
+        Ok(tonic::Response::new(proto::GetUidResponse { uid }))
+    }
+
+    async fn read_block(
+        &self,
+        request: Request<proto::ReadBlockRequest>,
+    ) -> Result<tonic::Response<proto::Block>, Status> {
+        let address = block_addr(request.get_ref().address)?.get();
+        let data = self.run(move |reader| read_block(reader, address)).await?;
+        Ok(tonic::Response::new(proto::Block {
+            address: address.into(),
+            data,
+        }))
+    }
+
+    async fn write_block(
+        &self,
+        request: Request<proto::Block>,
+    ) -> Result<tonic::Response<proto::WriteBlockResponse>, Status> {
+        let proto::Block { address, data } = request.into_inner();
+        let address = block_addr(address)?;
+        if address == BlockAddr::SYSTEM {
+            return Err(Status::permission_denied(
+                "writing the system block cannot be undone",
+            ));
+        }
+        let command = Command::WriteBlock(address.get(), data);
+        self.run(move |reader| execute(reader, &command)).await?;
+        Ok(tonic::Response::new(proto::WriteBlockResponse {}))
+    }
+
+    async fn dump(
+        &self,
+        _: Request<proto::DumpRequest>,
+    ) -> Result<tonic::Response<proto::Dump>, Status> {
+        Ok(tonic::Response::new(self.run(dump).await?))
+    }
+
+    async fn restore(
+        &self,
+        request: Request<proto::RestoreRequest>,
+    ) -> Result<tonic::Response<proto::RestoreResponse>, Status> {
+        let request = request.into_inner();
+        let written = self.run(move |reader| restore(reader, request)).await?;
+        Ok(tonic::Response::new(proto::RestoreResponse { written }))
+    }
+
+    type EventsStream = EventStream;
+
+    async fn events(
+        &self,
+        _: Request<proto::EventsRequest>,
+    ) -> Result<tonic::Response<EventStream>, Status> {
+        let events = self.events.clone();
+        // Subscribing on the worker, which sends the events, the stream
+        // misses no event and does not repeat the arrival of the tag.
+        let (uid, receiver) = self
+            .run(move |reader| Ok((reader.uid(), events.subscribe())))
+            .await?;
+        let current = uid.map(|uid| Ok(tag_event(Kind::Arrival, uid)));
+        let stream = tokio_stream::iter(current).chain(
+            BroadcastStream::new(receiver)
+                .filter_map(|event| event.ok().map(Ok)),
+        );
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+}
+
+/// Address of a block of a request, from 0 to 127 or 255.
+fn block_addr(address: u32) -> Result<BlockAddr, Status> {
+    usize::try_from(address)
+        .ok()
+        .and_then(BlockAddr::new)
+        .ok_or_else(|| {
+            Status::invalid_argument(format!("no block at {}", address))
+        })
+}
+
+fn execute(
+    reader: &mut dyn Reader,
+    command: &Command,
+) -> Result<Option<Response>, Status> {
+    if reader.uid().is_none() {
+        return Err(Status::unavailable("no tag in the field"));
+    }
+    reader
+        .execute(command)
+        .map_err(|err| Status::unavailable(err.to_string()))
+}
+
+fn get_uid(reader: &mut dyn Reader) -> Result<u64, Status> {
+    match execute(reader, &Command::GetUid)? {
+        Some(Response::Uid(uid)) => Ok(uid),
+        _ => Err(Status::internal("GetUid not answered with a UID")),
+    }
+}
+
+fn read_block(reader: &mut dyn Reader, address: u8) -> Result<u32, Status> {
+    match execute(reader, &Command::ReadBlock(address))? {
+        Some(Response::Block(data)) => Ok(data),
+        _ => Err(Status::internal("ReadBlock not answered with a block")),
+    }
+}
+
+/// Memory map of the chip of `uid`, the SRIX4K one if it is unknown.
+fn layout(uid: u64) -> &'static dyn MemoryLayout {
+    mem::layout_of(uid).unwrap_or(&mem::Srix4kLayout)
+}
+
+fn dump(reader: &mut dyn Reader) -> Result<proto::Dump, Status> {
+    let uid = get_uid(reader)?;
+    let layout = layout(uid);
+    let eeprom = layout
+        .eeprom()
+        .map(|address| read_block(reader, address as u8))
+        .collect::<Result<_, _>>()?;
+    let system = read_block(reader, layout.system_addr() as u8)?;
+    Ok(proto::Dump {
+        uid: Some(uid),
+        eeprom,
+        system: Some(system),
+    })
+}
+
+/// Write the EEPROM blocks of the dump that differ from the tag, returning
+/// the number of blocks written.
+fn restore(
+    reader: &mut dyn Reader,
+    request: proto::RestoreRequest,
+) -> Result<u32, Status> {
+    let dump = request.dump.unwrap_or_default();
+    if dump.eeprom.len() > mem::BLOCK_COUNT {
+        return Err(Status::invalid_argument(format!(
+            "dump of {} blocks, at most {}",
+            dump.eeprom.len(),
+            mem::BLOCK_COUNT
+        )));
+    }
+    let uid = get_uid(reader)?;
+    let mut eeprom = [0; mem::BLOCK_COUNT];
+    eeprom[..dump.eeprom.len()].copy_from_slice(&dump.eeprom);
+    let file = Srix4kDump {
+        uid: dump.uid,
+        eeprom,
+        system: dump.system,
+    };
+    if let Some(mismatch) = file.check_uid(uid) {
+        if !request.allow_other_tag {
+            return Err(Status::failed_precondition(mismatch.to_string()));
+        }
+    }
+    let mut written = 0;
+    for (address, &data) in layout(uid).eeprom().zip(&dump.eeprom) {
+        let address = address as u8;
+        if read_block(reader, address)? == data {
+            continue;
+        }
+        execute(reader, &Command::WriteBlock(address, data))?;
+        if request.verify && read_block(reader, address)? != data {
+            return Err(Status::data_loss(format!(
+                "block {:#04X} reads back another value",
+                address
+            )));
+        }
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::tag_server::Tag;
+    use srix4k::emulator::{Answer, EmulatedTag, Field};
+    use srix4k::iso14443b2;
+    use srix4k::replay::Transport;
+    use std::sync::{Arc, Mutex};
+    use tonic::Code;
+
+    const UID: u64 = 0xD002_0C00_0000_0001;
+    const OTHER_UID: u64 = 0xD002_0C00_0000_0002;
+
+    /// Reader of emulated tags, selecting the first one to answer.
+    struct Emulated {
+        field: Arc<Mutex<Field>>,
+        uid: Option<u64>,
+    }
+
+    impl Reader for Emulated {
+        fn poll(&mut self) -> Option<TagEvent> {
+            let mut field = self.field.lock().unwrap();
+            if let Some(uid) = self.uid {
+                if field.tag(uid).is_some() {
+                    return None;
+                }
+                self.uid = None;
+                return Some(tag_event(Kind::Removal, uid));
+            }
+            let chip_id = match field.transceive(&iso14443b2::initiate()) {
+                Answer::Frame(frame) => frame[0],
+                _ => return None,
+            };
+            field.transceive(&iso14443b2::select(chip_id));
+            match field.execute(&Command::GetUid) {
+                Ok(Some(Response::Uid(uid))) => {
+                    self.uid = Some(uid);
+                    Some(tag_event(Kind::Arrival, uid))
+                }
+                _ => None,
+            }
+        }
+        fn uid(&self) -> Option<u64> {
+            self.uid
+        }
+        fn execute(
+            &mut self,
+            command: &Command,
+        ) -> Result<Option<Response>, Box<dyn Error>> {
+            Ok(self.field.lock().unwrap().execute(command)?)
+        }
+    }
+
+    fn service(uids: &[u64]) -> (Service, Arc<Mutex<Field>>) {
+        let mut field = Field::new(42);
+        for &uid in uids {
+            field.insert(EmulatedTag::new(uid));
+        }
+        let field = Arc::new(Mutex::new(field));
+        let reader_field = field.clone();
+        let service =
+            Service::spawn(Duration::from_millis(10), move |worker| {
+                worker.run(&mut Emulated {
+                    field: reader_field,
+                    uid: None,
+                })
+            });
+        (service, field)
+    }
+
+    async fn read(service: &Service, address: u32) -> Result<u32, Status> {
+        let request = Request::new(proto::ReadBlockRequest { address });
+        Ok(service.read_block(request).await?.into_inner().data)
+    }
+
+    async fn write(
+        service: &Service,
+        address: u32,
+        data: u32,
+    ) -> Result<(), Status> {
+        let request = Request::new(proto::Block { address, data });
+        service.write_block(request).await.map(drop)
+    }
+
+    #[tokio::test]
+    async fn write_then_read_and_dump() {
+        let (service, _) = service(&[UID]);
+        let request = Request::new(proto::GetUidRequest {});
+        assert_eq!(service.get_uid(request).await.unwrap().get_ref().uid, UID);
+        write(&service, 7, 0x1234_5678).await.unwrap();
+        assert_eq!(read(&service, 7).await.unwrap(), 0x1234_5678);
+        let request = Request::new(proto::DumpRequest {});
+        let dump = service.dump(request).await.unwrap().into_inner();
+        assert_eq!(dump.uid, Some(UID));
+        assert_eq!(dump.eeprom.len(), mem::BLOCK_COUNT);
+        assert_eq!(dump.eeprom[7], 0x1234_5678);
+        assert!(dump.system.is_some());
+    }
+
+    #[tokio::test]
+    async fn invalid_requests_are_refused() {
+        let (service, _) = service(&[UID]);
+        let err = read(&service, 128).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+        let err = write(&service, 255, 0).await.unwrap_err();
+        assert_eq!(err.code(), Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn empty_field_is_unavailable() {
+        let (service, _) = service(&[]);
+        let err = read(&service, 7).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn restore_writes_the_differing_blocks() {
+        let (service, _) = service(&[UID]);
+        let request = Request::new(proto::DumpRequest {});
+        let mut dump = service.dump(request).await.unwrap().into_inner();
+        dump.eeprom[7] ^= 1;
+        dump.eeprom[8] ^= 1;
+        let restore = |dump: &proto::Dump, allow_other_tag| {
+            service.restore(Request::new(proto::RestoreRequest {
+                dump: Some(dump.clone()),
+                verify: true,
+                allow_other_tag,
+            }))
+        };
+        let response = restore(&dump, false).await.unwrap();
+        assert_eq!(response.get_ref().written, 2);
+        assert_eq!(read(&service, 8).await.unwrap(), dump.eeprom[8]);
+
+        dump.uid = Some(OTHER_UID);
+        let err = restore(&dump, false).await.unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+        let response = restore(&dump, true).await.unwrap();
+        assert_eq!(response.get_ref().written, 0);
+    }
+
+    #[tokio::test]
+    async fn events_start_with_the_tag_in_the_field() {
+        let (service, field) = service(&[UID]);
+        let request = Request::new(proto::EventsRequest {});
+        let mut events = service.events(request).await.unwrap().into_inner();
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event, tag_event(Kind::Arrival, UID));
+        field.lock().unwrap().remove(UID);
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event, tag_event(Kind::Removal, UID));
+        field.lock().unwrap().insert(EmulatedTag::new(OTHER_UID));
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event, tag_event(Kind::Arrival, OTHER_UID));
+    }
+}
@@ -0,0 +1,44 @@
+//! `srix4k-grpc [<listen address>] [<connstring>]`
+//!
+//! Serve the tag in the field of the libnfc device `connstring`, or the
+//! first one found, over gRPC on `listen address`, `127.0.0.1:50051` by
+//! default.
+
+use std::env;
+use std::error::Error;
+
+use srix4k::monitor::Monitor;
+use srix4k_grpc::proto::tag_server::TagServer;
+use srix4k_grpc::{Service, POLL_INTERVAL};
+
+/// Address the server listens on when none is given.
+const DEFAULT_LISTEN: &str = "127.0.0.1:50051";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let listen = args.next();
+    let listen = listen.as_deref().unwrap_or(DEFAULT_LISTEN).parse()?;
+    let connstring = args.next();
+    let service = Service::spawn(POLL_INTERVAL, move |worker| {
+        let open = || -> nfc1::Result<()> {
+            let mut context = nfc1::Context::new()?;
+            let device = match &connstring {
+                Some(connstring) => context.open_with_connstring(connstring)?,
+                None => context.open()?,
+            };
+            let mut monitor = Monitor::new(device)?;
+            worker.run(&mut monitor);
+            Ok(())
+        };
+        if let Err(err) = open() {
+            eprintln!("Cannot open the reader: {}", err);
+        }
+    });
+    println!("Listening on {}", listen);
+    tonic::transport::Server::builder()
+        .add_service(TagServer::new(service))
+        .serve(listen)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+#ifndef SRIX4K_H
+#define SRIX4K_H
+
+/* Generated with cbindgen, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Size of the buffer needed by `srix4k_dump`.
+ */
+#define SRIX4K_FULL_DUMP_SIZE 524
+
+/**
+ * Connection to a tag, owning the libnfc context.
+ */
+typedef struct Srix4kHandle Srix4kHandle;
+
+/**
+ * Open the device and connect to the SRIX4K in its field.
+ *
+ * `connstring` selects the device, or the default one if null.
+ * Returns null on failure.
+ *
+ * # Safety
+ *
+ * `connstring` must be null or a valid null-terminated string.
+ */
+Srix4kHandle *srix4k_connect(const char *connstring);
+
+/**
+ * Disconnect from the tag and close the device.
+ *
+ * # Safety
+ *
+ * `handle` must be null or returned by `srix4k_connect`,
+ * and not used afterwards.
+ */
+void srix4k_close(Srix4kHandle *handle);
+
+/**
+ * Read a block, from 0 to 127, or 255 for system.
+ *
+ * # Safety
+ *
+ * `handle` must be returned by `srix4k_connect`
+ * and `block_data` must be valid for writes.
+ */
+int srix4k_read_block(Srix4kHandle *handle, uint8_t address, uint32_t *block_data);
+
+/**
+ * Write a block, from 0 to 127. Writing the system block cannot be undone
+ * and fails.
+ *
+ * # Safety
+ *
+ * `handle` must be returned by `srix4k_connect`.
+ */
+int srix4k_write_block(Srix4kHandle *handle, uint8_t address, uint32_t block_data);
+
+/**
+ * Read the UID.
+ *
+ * # Safety
+ *
+ * `handle` must be returned by `srix4k_connect`
+ * and `uid` must be valid for writes.
+ */
+int srix4k_get_uid(Srix4kHandle *handle, uint64_t *uid);
+
+/**
+ * Write a dump of the tag to `buffer`, which is `len` bytes long, and
+ * return the number of bytes written: `SRIX4K_FULL_DUMP_SIZE` for a full
+ * dump, or 512 for an EEPROM dump of a tag whose quirks skip `GetUID`.
+ *
+ * # Safety
+ *
+ * `handle` must be returned by `srix4k_connect`
+ * and `buffer` must be valid for `len` bytes of writes.
+ */
+int srix4k_dump(Srix4kHandle *handle, uint8_t *buffer, size_t len);
+
+/**
+ * Write the EEPROM blocks of the dump in `buffer`, `len` bytes long,
+ * that differ from the tag, reading each one back. The system block is
+ * never written. Fails if the dump has the UID of another tag.
+ *
+ * # Safety
+ *
+ * `handle` must be returned by `srix4k_connect`
+ * and `buffer` must be valid for `len` bytes of reads.
+ */
+int srix4k_restore(Srix4kHandle *handle, const uint8_t *buffer, size_t len);
+
+/**
+ * Description of the last error that occurred on this thread,
+ * or null if none did.
+ *
+ * The string is valid until the next failing call on the same thread.
+ */
+const char *srix4k_last_error(void);
+
+#endif /* SRIX4K_H */
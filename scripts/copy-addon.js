@@ -0,0 +1,9 @@
+// Copy the library built by `cargo rustc --crate-type cdylib` to the
+// Node.js addon, srix4k.node.
+const fs = require('fs');
+
+const file = {
+  darwin: 'libsrix4k.dylib',
+  win32: 'srix4k.dll',
+}[process.platform] || 'libsrix4k.so';
+fs.copyFileSync(`target/release/${file}`, 'srix4k.node');
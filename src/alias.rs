@@ -0,0 +1,123 @@
+//! Friendly names of tags, so users managing many of them see
+//! `gym-pass-blue` instead of a raw UID.
+//!
+//! Aliases are kept in one file, one tag per line, UID first:
+//!
+//! ```text
+//! D0023C0012345678 gym-pass-blue
+//! ```
+//!
+//! The CLI uses the file at `default_path`, and `srix4k alias` edits it.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use Uid;
+
+/// Environment variable overriding the path of the aliases file.
+pub const PATH_VAR: &str = "SRIX4K_ALIASES";
+
+/// Names of tags, by UID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Aliases(BTreeMap<u64, String>);
+
+/// Aliases file of the user: `$SRIX4K_ALIASES`, else `srix4k/aliases` in
+/// `$XDG_CONFIG_HOME` or `$HOME/.config`. `None` if none is set.
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(PATH_VAR) {
+        return Some(PathBuf::from(path));
+    }
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config) => PathBuf::from(config),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("srix4k").join("aliases"))
+}
+
+impl Aliases {
+    /// Load the aliases file at `path`, no aliases if it does not exist.
+    ///
+    /// Fails with `InvalidData` if a line is not a UID and a name.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Aliases> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Aliases::default())
+            }
+            Err(err) => return Err(err),
+        };
+        let mut aliases = Aliases::default();
+        for line in source.lines().filter(|line| !line.trim().is_empty()) {
+            let mut fields = line.trim().splitn(2, char::is_whitespace);
+            let uid = fields
+                .next()
+                .and_then(|uid| u64::from_str_radix(uid, 16).ok());
+            let name = fields.next().map(str::trim);
+            match (uid, name) {
+                (Some(uid), Some(name)) if !name.is_empty() => {
+                    aliases.0.insert(uid, name.to_string());
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid alias `{}`", line),
+                    ))
+                }
+            }
+        }
+        Ok(aliases)
+    }
+    /// Save the aliases to `path`, creating its directory if needed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = String::new();
+        for (uid, name) in self.iter() {
+            contents.push_str(&format!("{} {}\n", uid, name));
+        }
+        fs::write(path, contents)
+    }
+    /// Name of the tag with `uid`.
+    pub fn get(&self, uid: u64) -> Option<&str> {
+        self.0.get(&uid).map(String::as_str)
+    }
+    /// Name the tag with `uid`, returning its previous name.
+    ///
+    /// Names are one line, surrounding whitespace is trimmed. An empty
+    /// name removes the alias.
+    pub fn set(&mut self, uid: u64, name: &str) -> Option<String> {
+        match name.lines().next().map(str::trim) {
+            Some(name) if !name.is_empty() => {
+                self.0.insert(uid, name.to_string())
+            }
+            _ => self.remove(uid),
+        }
+    }
+    /// Forget the name of the tag with `uid`.
+    pub fn remove(&mut self, uid: u64) -> Option<String> {
+        self.0.remove(&uid)
+    }
+    /// UID of the tag named `name`.
+    pub fn find(&self, name: &str) -> Option<u64> {
+        self.0
+            .iter()
+            .find(|&(_, alias)| alias == name)
+            .map(|(&uid, _)| uid)
+    }
+    /// Iterate over `(uid, name)`, in UID order.
+    pub fn iter(&self) -> impl Iterator<Item = (Uid, &str)> {
+        self.0.iter().map(|(&uid, name)| (Uid(uid), name.as_str()))
+    }
+    /// Name of the tag with `uid`, or its UID in hexadecimal.
+    pub fn display(&self, uid: u64) -> String {
+        match self.get(uid) {
+            Some(name) => name.to_string(),
+            None => Uid(uid).to_string(),
+        }
+    }
+}
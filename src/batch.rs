@@ -0,0 +1,270 @@
+//! Provisioning of a stack of tags presented one after another.
+//!
+//! A `Batch` waits for each tag to enter the field, applies the same
+//! operations to it and records the result under its UID. A tag that is
+//! still in the field, or presented again, is not processed twice.
+//!
+//! Stations with several readers run a batch on each of them at once with
+//! `run_parallel`, with the `rayon` feature.
+
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use dump::{RestoreOptions, Srix4kDump};
+use error::{Srix4kError, Srix4kResult};
+use nfc1::Result;
+use {quirks, Srix4k, WAIT_POLL_INTERVAL};
+
+/// Operation applied to every tag of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Read the EEPROM and the system block into `TagResult::dump`.
+    Dump,
+    /// `Restore(dump, options)`
+    /// Write the blocks of `dump` that differ, see `Srix4k::restore`.
+    /// Provisioning every tag from one dump needs `Strictness::Warn`.
+    Restore(Srix4kDump, RestoreOptions),
+    /// `Write(blocks)`
+    /// Write the `(block_address, block_data)` pairs, in order.
+    Write(Vec<(u8, u32)>),
+    /// Read back the blocks written by the previous `Write` operations
+    /// and record those that differ in `TagResult::mismatches`.
+    Verify,
+}
+
+/// Outcome of the operations on one tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagResult {
+    /// UID of the tag.
+    pub uid: u64,
+    /// Memory of the tag, if dumped.
+    pub dump: Option<Srix4kDump>,
+    /// Addresses of the blocks whose data read back differs from the
+    /// written one.
+    pub mismatches: Vec<u8>,
+    /// Error that stopped the operations, `None` if they all completed.
+    pub error: Option<Srix4kError>,
+}
+
+impl TagResult {
+    /// Whether every operation completed and every write was verified.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.mismatches.is_empty()
+    }
+}
+
+/// Results of a batch, in the order the tags were presented.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchReport {
+    /// One result per tag.
+    pub results: Vec<TagResult>,
+}
+
+impl BatchReport {
+    /// Number of tags all the operations succeeded on.
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|result| result.is_ok()).count()
+    }
+    /// Number of tags an operation failed on.
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+    /// Result of the tag with `uid`, if it was processed.
+    pub fn get(&self, uid: u64) -> Option<&TagResult> {
+        self.results.iter().find(|result| result.uid == uid)
+    }
+    /// Add the results of `other`, e.g. of another reader.
+    pub fn merge(&mut self, other: BatchReport) {
+        self.results.extend(other.results);
+    }
+}
+
+impl fmt::Display for BatchReport {
+    /// One line per tag, `uid: outcome`, then the totals.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in &self.results {
+            write!(f, "{:016X}: ", result.uid)?;
+            match result.error {
+                Some(err) => writeln!(f, "failed, {}", err)?,
+                None if !result.mismatches.is_empty() => writeln!(
+                    f,
+                    "failed, blocks {:02X?} differ",
+                    result.mismatches
+                )?,
+                None => writeln!(f, "ok")?,
+            }
+        }
+        writeln!(
+            f,
+            "{} tags, {} succeeded, {} failed",
+            self.results.len(),
+            self.succeeded(),
+            self.failed()
+        )
+    }
+}
+
+/// Reader applying the same operations to each tag presented to it.
+pub struct Batch<'a> {
+    /// Reader, which may or may not have a tag selected.
+    tag: Srix4k<'a>,
+    /// Operations applied to every tag, in order.
+    operations: Vec<Operation>,
+}
+
+impl Batch<'_> {
+    /// Apply `operations` to the tags presented to the device.
+    pub fn new(
+        mut device: nfc1::Device<'_>,
+        operations: Vec<Operation>,
+    ) -> Result<Batch<'_>> {
+        // Selection must fail fast when the field is empty,
+        // otherwise waiting for the next tag would block.
+        device.set_property_bool(nfc1::Property::InfiniteSelect, false)?;
+        Ok(Batch {
+            tag: Srix4k::new(device),
+            operations,
+        })
+    }
+    /// Process tags until `count` of them are done or no new tag enters
+    /// the field within `timeout`. `None` sets no limit.
+    ///
+    /// Failures of the operations are recorded in the report, only
+    /// errors of the reader while waiting for tags are returned.
+    pub fn run(
+        &mut self,
+        count: Option<usize>,
+        timeout: Option<Duration>,
+    ) -> Result<BatchReport> {
+        let mut report = BatchReport::default();
+        while count.is_none_or(|count| report.results.len() < count) {
+            let uid = match self.next_tag(&report, timeout) {
+                Ok(uid) => uid,
+                Err(nfc1::Error::Timeout) => break,
+                Err(err) => return Err(err),
+            };
+            info!("Processing tag {:016X}", uid);
+            let result = self.process(uid);
+            if let Some(err) = result.error {
+                warn!("Tag {:016X} failed: {}", uid, err);
+            }
+            report.results.push(result);
+        }
+        info!(
+            "Batch done, {} succeeded, {} failed",
+            report.succeeded(),
+            report.failed()
+        );
+        Ok(report)
+    }
+    /// Wait for a tag not in `report` to enter the field and select it,
+    /// failing with `Timeout` if none did within `timeout`.
+    fn next_tag(
+        &mut self,
+        report: &BatchReport,
+        timeout: Option<Duration>,
+    ) -> Result<u64> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if self.tag.select().is_ok() {
+                if let Ok(uid) = self.tag.send_get_uid() {
+                    if report.get(uid).is_none() {
+                        return Ok(uid);
+                    }
+                    debug!("Tag {:016X} already processed", uid);
+                    let remaining = deadline.map(|deadline| {
+                        deadline.saturating_duration_since(Instant::now())
+                    });
+                    self.tag.wait_for_removal(remaining)?;
+                    continue;
+                }
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(nfc1::Error::Timeout);
+            }
+            thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+    /// Apply the operations to the selected tag with `uid`.
+    fn process(&mut self, uid: u64) -> TagResult {
+        self.tag.set_quirks(quirks::lookup(Some(uid)));
+        let mut result = TagResult {
+            uid,
+            dump: None,
+            mismatches: Vec::new(),
+            error: None,
+        };
+        if let Err(err) = self.apply(&mut result) {
+            result.error = Some(err);
+        }
+        result
+    }
+    /// Apply the operations, stopping at the first error.
+    fn apply(&mut self, result: &mut TagResult) -> Srix4kResult<()> {
+        let mut written = Vec::new();
+        for operation in &self.operations {
+            match operation {
+                Operation::Dump => result.dump = Some(self.tag.dump()?),
+                Operation::Restore(dump, options) => {
+                    self.tag.restore(dump, *options)?;
+                }
+                Operation::Write(blocks) => {
+                    for &(block_address, block_data) in blocks {
+                        self.tag.send_write_block(block_address, block_data)?;
+                        written.push((block_address, block_data));
+                    }
+                }
+                Operation::Verify => {
+                    for &(block_address, block_data) in &written {
+                        if self.tag.read_block(block_address)? != block_data {
+                            result.mismatches.push(block_address);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run a batch of `operations` on each device of `connstrings`, all at
+/// once on a rayon pool of one thread per reader, each with its own
+/// libnfc context. Returns the report of each reader, in order, see
+/// `Batch::run`.
+///
+/// A tag is only processed once per reader: move tags between readers
+/// during the batch and the merged report can have them twice.
+#[cfg(feature = "rayon")]
+pub fn run_parallel<S: AsRef<str> + Sync>(
+    connstrings: &[S],
+    operations: &[Operation],
+    count: Option<usize>,
+    timeout: Option<Duration>,
+) -> Vec<Result<BatchReport>> {
+    let run = |connstring: &S| {
+        let connstring = connstring.as_ref();
+        let mut context = nfc1::Context::new()?;
+        let device = context.open_with_connstring(connstring)?;
+        info!("Running the batch on {}", connstring);
+        Batch::new(device, operations.to_vec())?.run(count, timeout)
+    };
+    // Batches mostly wait for tags: the global pool, sized to the CPUs,
+    // would leave readers beyond the number of CPUs idle.
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(connstrings.len())
+        .build()
+    {
+        Ok(pool) => pool.install(|| connstrings.par_iter().map(run).collect()),
+        Err(err) => {
+            warn!("Cannot start the reader threads: {}", err);
+            connstrings
+                .iter()
+                .map(|_| Err(nfc1::Error::OperationAborted))
+                .collect()
+        }
+    }
+}
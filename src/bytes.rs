@@ -0,0 +1,124 @@
+//! Byte-addressed view of the EEPROM, for data that does not fit the
+//! block structure, such as labels.
+//!
+//! Byte `offset` is byte `offset % 4` of block `offset / 4`, least
+//! significant byte first: offsets are those of the dump files. Writes
+//! that do not cover whole blocks keep the other bytes of the blocks.
+
+use image::TagMemory;
+use mem;
+
+/// Byte access to any `TagMemory`, a tag or a dump file.
+pub trait ByteAccess: TagMemory {
+    /// Fill `buffer` with the bytes from `offset`.
+    fn read_bytes(
+        &mut self,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            let (block, index) = mem::block_of_byte(offset + i);
+            *byte = self.eeprom_get(block)?.to_le_bytes()[index];
+        }
+        Ok(())
+    }
+    /// Write `bytes` from `offset`, in the cache until `sync`.
+    fn write_bytes(
+        &mut self,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<(), Self::Error> {
+        for (i, &byte) in bytes.iter().enumerate() {
+            let (block, index) = mem::block_of_byte(offset + i);
+            let block_data = self.eeprom_get_mut(block)?;
+            let mut block_bytes = block_data.to_le_bytes();
+            block_bytes[index] = byte;
+            *block_data = u32::from_le_bytes(block_bytes);
+        }
+        Ok(())
+    }
+    /// Read the string stored at `offset` by `write_str`, up to its NUL
+    /// terminator or `max_len` bytes.
+    ///
+    /// Invalid UTF-8, e.g. a character cut by `max_len`, is replaced by
+    /// `U+FFFD`.
+    fn read_str(
+        &mut self,
+        offset: usize,
+        max_len: usize,
+    ) -> Result<String, Self::Error> {
+        let mut bytes = Vec::new();
+        let mut byte = [0];
+        while bytes.len() < max_len {
+            self.read_bytes(offset + bytes.len(), &mut byte)?;
+            if byte[0] == 0 {
+                break;
+            }
+            bytes.push(byte[0]);
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+    /// Store `string` at `offset` as UTF-8 followed by a NUL terminator,
+    /// `string.len() + 1` bytes.
+    ///
+    /// Fails like `eeprom_get` if the string does not fit before the end
+    /// of the EEPROM, before writing anything.
+    fn write_str(
+        &mut self,
+        offset: usize,
+        string: &str,
+    ) -> Result<(), Self::Error> {
+        let (last_block, _) = mem::block_of_byte(offset + string.len());
+        self.eeprom_get(last_block)?;
+        self.write_bytes(offset, string.as_bytes())?;
+        self.write_bytes(offset + string.len(), &[0])
+    }
+}
+
+impl<T: TagMemory + ?Sized> ByteAccess for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// EEPROM of two blocks, failing past them.
+    struct Memory([u32; 2]);
+
+    impl TagMemory for Memory {
+        type Error = ();
+
+        fn eeprom_get(&mut self, i: usize) -> Result<u32, ()> {
+            self.0.get(i).copied().ok_or(())
+        }
+        fn eeprom_get_mut(&mut self, i: usize) -> Result<&mut u32, ()> {
+            self.0.get_mut(i).ok_or(())
+        }
+        fn system_get(&mut self) -> Result<u32, ()> {
+            Err(())
+        }
+        fn system_get_mut(&mut self) -> Result<&mut u32, ()> {
+            Err(())
+        }
+        fn uid_get(&mut self) -> Result<u64, ()> {
+            Err(())
+        }
+        fn sync(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_str_round_trip() {
+        let mut memory = Memory([0xFFFF_FFFF; 2]);
+        memory.write_str(1, "abc").unwrap();
+        assert_eq!(memory.0, [0x6362_61FF, 0xFFFF_FF00]);
+        assert_eq!(memory.read_str(1, 7).unwrap(), "abc");
+    }
+
+    #[test]
+    fn write_str_too_long_writes_nothing() {
+        let mut memory = Memory([0xFFFF_FFFF; 2]);
+        assert_eq!(memory.write_str(1, "abcdefg"), Err(()));
+        assert_eq!(memory.0, [0xFFFF_FFFF; 2]);
+    }
+}
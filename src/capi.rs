@@ -0,0 +1,270 @@
+//! C API, enabled by the `capi` feature.
+//!
+//! The header is generated with [cbindgen](https://github.com/mozilla/cbindgen)
+//! into `include/srix4k.h`:
+//!
+//! ```sh
+//! cbindgen --config cbindgen.toml --output include/srix4k.h
+//! ```
+//!
+//! Functions returning `int` return `0`, or a size where stated, on
+//! success and `-1` on failure, in which case `srix4k_last_error`
+//! describes the error.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+
+use dump::{RestoreOptions, Srix4kDump, FULL_DUMP_SIZE};
+use mem;
+use Srix4k;
+
+/// Size of the buffer needed by `srix4k_dump`.
+pub const SRIX4K_FULL_DUMP_SIZE: usize = 524;
+const _: () = assert!(SRIX4K_FULL_DUMP_SIZE == FULL_DUMP_SIZE);
+
+thread_local! {
+    /// Description of the last error of the thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Connection to a tag, owning the libnfc context.
+pub struct Srix4kHandle {
+    /// Connected tag, dropped before the context.
+    tag: Option<Srix4k<'static>>,
+    /// Context the device of the tag was opened from.
+    context: *mut nfc1::Context<'static>,
+}
+
+impl Drop for Srix4kHandle {
+    fn drop(&mut self) {
+        self.tag = None;
+        // SAFETY: the context was leaked by `srix4k_connect`
+        // and nothing borrows it anymore.
+        unsafe { drop(Box::from_raw(self.context)) };
+    }
+}
+
+/// Store the error for `srix4k_last_error` and return `-1`.
+fn fail<E: ToString>(err: E) -> c_int {
+    let message = CString::new(err.to_string().replace('\0', ""))
+        .expect("null bytes were removed");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    -1
+}
+
+/// Run the operation on the tag of the handle.
+unsafe fn with_tag<F>(handle: *mut Srix4kHandle, operation: F) -> c_int
+where
+    F: FnOnce(&mut Srix4k<'static>) -> Result<(), String>,
+{
+    let tag = match handle.as_mut().and_then(|handle| handle.tag.as_mut()) {
+        Some(tag) => tag,
+        None => return fail("handle is null"),
+    };
+    match operation(tag) {
+        Ok(()) => 0,
+        Err(err) => fail(err),
+    }
+}
+
+/// Open the device and connect to the SRIX4K in its field.
+///
+/// `connstring` selects the device, or the default one if null.
+/// Returns null on failure.
+///
+/// # Safety
+///
+/// `connstring` must be null or a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn srix4k_connect(
+    connstring: *const c_char,
+) -> *mut Srix4kHandle {
+    let connstring = if connstring.is_null() {
+        None
+    } else {
+        match CStr::from_ptr(connstring).to_str() {
+            Ok(connstring) => Some(connstring),
+            Err(err) => {
+                fail(err);
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let context = match nfc1::Context::new() {
+        Ok(context) => Box::into_raw(Box::new(context)),
+        Err(err) => {
+            fail(err);
+            return ptr::null_mut();
+        }
+    };
+    // From now on the handle frees the context.
+    let mut handle = Box::new(Srix4kHandle { tag: None, context });
+
+    let device = match connstring {
+        Some(connstring) => (*context).open_with_connstring(connstring),
+        None => (*context).open(),
+    };
+    match device.and_then(Srix4k::connect_from) {
+        Ok(tag) => {
+            handle.tag = Some(tag);
+            Box::into_raw(handle)
+        }
+        Err(err) => {
+            fail(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Disconnect from the tag and close the device.
+///
+/// # Safety
+///
+/// `handle` must be null or returned by `srix4k_connect`,
+/// and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn srix4k_close(handle: *mut Srix4kHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Read a block, from 0 to 127, or 255 for system.
+///
+/// # Safety
+///
+/// `handle` must be returned by `srix4k_connect`
+/// and `block_data` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn srix4k_read_block(
+    handle: *mut Srix4kHandle,
+    address: u8,
+    block_data: *mut u32,
+) -> c_int {
+    if block_data.is_null() {
+        return fail("block_data is null");
+    }
+    with_tag(handle, |tag| {
+        *block_data = tag
+            .send_read_block(address)
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    })
+}
+
+/// Write a block, from 0 to 127. Writing the system block cannot be undone
+/// and fails.
+///
+/// # Safety
+///
+/// `handle` must be returned by `srix4k_connect`.
+#[no_mangle]
+pub unsafe extern "C" fn srix4k_write_block(
+    handle: *mut Srix4kHandle,
+    address: u8,
+    block_data: u32,
+) -> c_int {
+    with_tag(handle, |tag| {
+        if address as usize == mem::SYSTEM_ADDR {
+            return Err("writing the system block cannot be undone".into());
+        }
+        tag.send_write_block(address, block_data)
+            .map_err(|err| err.to_string())
+    })
+}
+
+/// Read the UID.
+///
+/// # Safety
+///
+/// `handle` must be returned by `srix4k_connect`
+/// and `uid` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn srix4k_get_uid(
+    handle: *mut Srix4kHandle,
+    uid: *mut u64,
+) -> c_int {
+    if uid.is_null() {
+        return fail("uid is null");
+    }
+    with_tag(handle, |tag| {
+        *uid = tag.send_get_uid().map_err(|err| err.to_string())?;
+        Ok(())
+    })
+}
+
+/// Write a dump of the tag to `buffer`, which is `len` bytes long, and
+/// return the number of bytes written: `SRIX4K_FULL_DUMP_SIZE` for a full
+/// dump, or 512 for an EEPROM dump of a tag whose quirks skip `GetUID`.
+///
+/// # Safety
+///
+/// `handle` must be returned by `srix4k_connect`
+/// and `buffer` must be valid for `len` bytes of writes.
+#[no_mangle]
+pub unsafe extern "C" fn srix4k_dump(
+    handle: *mut Srix4kHandle,
+    buffer: *mut u8,
+    len: usize,
+) -> c_int {
+    if buffer.is_null() || len < FULL_DUMP_SIZE {
+        return fail("buffer is smaller than a full dump");
+    }
+    let mut written = 0;
+    let result = with_tag(handle, |tag| {
+        let dump = tag.dump().map_err(|err| err.to_string())?;
+
+        let bytes = dump.serialize();
+        slice::from_raw_parts_mut(buffer, bytes.len()).copy_from_slice(&bytes);
+        written = bytes.len();
+        Ok(())
+    });
+    match result {
+        0 => written as c_int,
+        err => err,
+    }
+}
+
+/// Write the EEPROM blocks of the dump in `buffer`, `len` bytes long,
+/// that differ from the tag, reading each one back. The system block is
+/// never written. Fails if the dump has the UID of another tag.
+///
+/// # Safety
+///
+/// `handle` must be returned by `srix4k_connect`
+/// and `buffer` must be valid for `len` bytes of reads.
+#[no_mangle]
+pub unsafe extern "C" fn srix4k_restore(
+    handle: *mut Srix4kHandle,
+    buffer: *const u8,
+    len: usize,
+) -> c_int {
+    if buffer.is_null() {
+        return fail("buffer is null");
+    }
+    let dump = match Srix4kDump::parse(slice::from_raw_parts(buffer, len)) {
+        Ok(dump) => dump,
+        Err(err) => return fail(err),
+    };
+    with_tag(handle, |tag| {
+        tag.restore(&dump, RestoreOptions::default())
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    })
+}
+
+/// Description of the last error that occurred on this thread,
+/// or null if none did.
+///
+/// The string is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn srix4k_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match *last.borrow() {
+        Some(ref message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
@@ -0,0 +1,358 @@
+//! Encoding and decoding of the frames exchanged with the tag.
+//!
+//! Every byte coming from the reader is untrusted: a clone or a noisy
+//! channel can answer frames of any length and content. Responses are only
+//! decoded here, checking the length of the frame against the command, and
+//! no input can make decoding panic.
+//!
+//! Encoding and decoding are symmetrical, for commands
+//! (`Vec::from(command)`, `Command::try_from(frame)`) and responses
+//! (`Vec::from(response)`, `Response::decode(command, frame)`), so recorded
+//! sessions can be replayed.
+//!
+//! With the `defmt` feature, the frames encoded and decoded here are
+//! logged with `defmt`, so firmware gets RTT logs of the exchanges without
+//! the `log` crate.
+
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::ops::Deref;
+
+use iso14443b2;
+use mem;
+use Command;
+
+/// `defmt` log statement at `$level`. Without the `defmt` feature, only
+/// borrows its arguments, like the no-op `log` macros.
+macro_rules! defmt_log {
+    ($level:ident, $format:literal $(, $arg:expr)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::$level!($format $(, $arg)*);
+        #[cfg(not(feature = "defmt"))]
+        let _ = ($(&$arg,)*);
+    };
+}
+
+/// Responses of SRIX4K tag to the commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Response {
+    /// `Block(block_data)`
+    /// Answer to `ReadBlock`. Block Data(LSB)
+    Block(u32),
+    /// `Uid(uid)`
+    /// Answer to `GetUid`.
+    Uid(u64),
+}
+
+/// Length of the longest command frame, `WriteBlock`.
+pub const MAX_COMMAND_SIZE: usize = 6;
+
+/// Frame of a command, encoded on the stack so that sending it does not
+/// allocate. Dereferences to the bytes of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandFrame {
+    /// Frame, followed by unused bytes.
+    bytes: [u8; MAX_COMMAND_SIZE],
+    /// Length of the frame.
+    len: usize,
+}
+
+impl Deref for CommandFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Errors that can occur while decoding a frame answered by the tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// `InvalidLength(expected, actual)`
+    /// The frame does not have the length of the response to the command.
+    InvalidLength(usize, usize),
+    /// `UnexpectedResponse(length)`
+    /// The command has no response but the tag answered a frame.
+    UnexpectedResponse(usize),
+    /// `UnknownCommand(code)`
+    /// The frame is empty, code `None`, or its command code is not one of
+    /// `Command`.
+    UnknownCommand(Option<u8>),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidLength(expected, actual) => write!(
+                f,
+                "invalid response length {}, expected {} bytes",
+                actual, expected
+            ),
+            DecodeError::UnexpectedResponse(length) => write!(
+                f,
+                "unexpected response of {} bytes to a command without one",
+                length
+            ),
+            DecodeError::UnknownCommand(Some(code)) => {
+                write!(f, "unknown command code {:#04X}", code)
+            }
+            DecodeError::UnknownCommand(None) => write!(f, "empty command"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[cfg(feature = "nfc")]
+impl From<DecodeError> for nfc1::Error {
+    /// Malformed responses are reported as transmission errors.
+    fn from(_: DecodeError) -> Self {
+        nfc1::Error::RfTransmissionError
+    }
+}
+
+/// Decode the block data answered to `ReadBlock`.
+pub fn decode_block(frame: &[u8]) -> Result<u32, DecodeError> {
+    frame
+        .try_into()
+        .map(u32::from_le_bytes)
+        .map_err(|_| DecodeError::InvalidLength(mem::BLOCK_SIZE, frame.len()))
+}
+
+/// Decode the UID answered to `GetUid`.
+pub fn decode_uid(frame: &[u8]) -> Result<u64, DecodeError> {
+    frame.try_into().map(u64::from_le_bytes).map_err(|_| {
+        DecodeError::InvalidLength(mem::UID_SIZE.into(), frame.len())
+    })
+}
+
+impl Response {
+    /// Decode the frame answered to `command`, `None` if the command has
+    /// no response and the frame is empty.
+    pub fn decode(
+        command: &Command,
+        frame: &[u8],
+    ) -> Result<Option<Response>, DecodeError> {
+        let response = match command {
+            Command::ReadBlock(_) => decode_block(frame)
+                .map(|block_data| Some(Response::Block(block_data))),
+            Command::WriteBlock(_, _) if frame.is_empty() => Ok(None),
+            Command::WriteBlock(_, _) => {
+                Err(DecodeError::UnexpectedResponse(frame.len()))
+            }
+            Command::GetUid => {
+                decode_uid(frame).map(|uid| Some(Response::Uid(uid)))
+            }
+        };
+        match response {
+            Ok(ref response) => {
+                defmt_log!(trace, "{} answered {}", command, response);
+            }
+            Err(ref err) => {
+                defmt_log!(
+                    warn,
+                    "Bad answer {=[u8]:02X} to {}: {}",
+                    frame,
+                    command,
+                    err
+                );
+            }
+        }
+        response
+    }
+}
+
+impl From<Response> for Vec<u8> {
+    /// Convert response variant to the frame the tag answers.
+    fn from(value: Response) -> Self {
+        match value {
+            Response::Block(block_data) => block_data.to_le_bytes().to_vec(),
+            Response::Uid(uid) => uid.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+impl Command {
+    /// Encode the frame sent to the tag, like `Vec::from(command)` without
+    /// allocating.
+    pub fn frame(&self) -> CommandFrame {
+        let mut bytes = [0; MAX_COMMAND_SIZE];
+        let len = match *self {
+            Command::ReadBlock(address) => {
+                bytes[..2].copy_from_slice(&[iso14443b2::READ_BLOCK, address]);
+                2
+            }
+            Command::WriteBlock(address, block_data) => {
+                bytes[..2].copy_from_slice(&[iso14443b2::WRITE_BLOCK, address]);
+                bytes[2..].copy_from_slice(&block_data.to_le_bytes());
+                6
+            }
+            Command::GetUid => {
+                bytes[0] = iso14443b2::GET_UID;
+                1
+            }
+        };
+        defmt_log!(trace, "Sending {}: {=[u8]:02X}", self, &bytes[..len]);
+        CommandFrame { bytes, len }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Command {
+    type Error = DecodeError;
+
+    /// Decode the frame of a command, as sent to the tag.
+    fn try_from(frame: &'a [u8]) -> Result<Self, Self::Error> {
+        let length = match frame.first() {
+            Some(&iso14443b2::READ_BLOCK) => 2,
+            Some(&iso14443b2::WRITE_BLOCK) => 6,
+            Some(&iso14443b2::GET_UID) => 1,
+            code => {
+                defmt_log!(warn, "Unknown command frame {=[u8]:02X}", frame);
+                return Err(DecodeError::UnknownCommand(code.cloned()));
+            }
+        };
+        if frame.len() != length {
+            defmt_log!(warn, "Bad command frame {=[u8]:02X}", frame);
+            return Err(DecodeError::InvalidLength(length, frame.len()));
+        }
+
+        // The length matches the command code, indexing cannot panic.
+        Ok(match frame[0] {
+            iso14443b2::READ_BLOCK => Command::ReadBlock(frame[1]),
+            iso14443b2::WRITE_BLOCK => {
+                Command::WriteBlock(frame[1], decode_block(&frame[2..])?)
+            }
+            _ => Command::GetUid,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `defmt` logger of the tests, discarding the logs, which firmware
+    /// provides otherwise.
+    #[cfg(feature = "defmt")]
+    #[defmt::global_logger]
+    struct Logger;
+
+    #[cfg(feature = "defmt")]
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {}
+        unsafe fn flush() {}
+        unsafe fn release() {}
+        unsafe fn write(_bytes: &[u8]) {}
+    }
+
+    #[cfg(feature = "defmt")]
+    defmt::timestamp!("");
+
+    const COMMANDS: [Command; 5] = [
+        Command::ReadBlock(0x00),
+        Command::ReadBlock(0xFF),
+        Command::WriteBlock(0x07, 0x1234_5678),
+        Command::WriteBlock(0xFF, 0xFFFF_FFFE),
+        Command::GetUid,
+    ];
+
+    #[test]
+    fn command_round_trip() {
+        for &command in &COMMANDS {
+            let frame = command.frame();
+            assert_eq!(*frame, *Vec::from(command), "{:?}", command);
+            assert_eq!(Command::try_from(&*frame), Ok(command));
+        }
+    }
+
+    #[test]
+    fn command_frames() {
+        assert_eq!(*Command::ReadBlock(0x07).frame(), [0x08, 0x07]);
+        assert_eq!(
+            *Command::WriteBlock(0x07, 0x1234_5678).frame(),
+            [0x09, 0x07, 0x78, 0x56, 0x34, 0x12]
+        );
+        assert_eq!(*Command::GetUid.frame(), [0x0B]);
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let exchanges = [
+            (Command::ReadBlock(0x07), Response::Block(0x0707_0707)),
+            (Command::GetUid, Response::Uid(0xD002_0C00_1234_5678)),
+        ];
+        for &(command, response) in &exchanges {
+            let frame = Vec::from(response);
+            assert_eq!(Response::decode(&command, &frame), Ok(Some(response)));
+        }
+        assert_eq!(
+            Response::decode(&Command::WriteBlock(0x07, 0), &[]),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn malformed_commands() {
+        assert_eq!(
+            Command::try_from(&[][..]),
+            Err(DecodeError::UnknownCommand(None))
+        );
+        assert_eq!(
+            Command::try_from(&[0x0A, 0x07][..]),
+            Err(DecodeError::UnknownCommand(Some(0x0A)))
+        );
+        assert_eq!(
+            Command::try_from(&[0x08][..]),
+            Err(DecodeError::InvalidLength(2, 1))
+        );
+        assert_eq!(
+            Command::try_from(&[0x08, 0x07, 0x00][..]),
+            Err(DecodeError::InvalidLength(2, 3))
+        );
+        assert_eq!(
+            Command::try_from(&[0x09, 0x07, 0x78, 0x56, 0x34][..]),
+            Err(DecodeError::InvalidLength(6, 5))
+        );
+        assert_eq!(
+            Command::try_from(&[0x0B, 0x00][..]),
+            Err(DecodeError::InvalidLength(1, 2))
+        );
+    }
+
+    #[test]
+    fn malformed_responses() {
+        let read = Command::ReadBlock(0x07);
+        assert_eq!(
+            Response::decode(&read, &[]),
+            Err(DecodeError::InvalidLength(4, 0))
+        );
+        assert_eq!(
+            Response::decode(&read, &[0x07; 5]),
+            Err(DecodeError::InvalidLength(4, 5))
+        );
+        assert_eq!(
+            Response::decode(&Command::GetUid, &[0x78, 0x56, 0x34, 0x12]),
+            Err(DecodeError::InvalidLength(8, 4))
+        );
+        assert_eq!(
+            Response::decode(&Command::WriteBlock(0x07, 0), &[0x00]),
+            Err(DecodeError::UnexpectedResponse(1))
+        );
+    }
+
+    #[test]
+    fn decoding_never_panics() {
+        for len in 0..=12 {
+            for &byte in &[0x00, 0x08, 0x09, 0x0B, 0xFF] {
+                let frame = vec![byte; len];
+                let _ = Command::try_from(&frame[..]);
+                for command in &COMMANDS {
+                    let _ = Response::decode(command, &frame);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Typed configuration of the reader properties relevant to SRIX4K tags,
+//! so the device does not have to be set up before connecting.
+
+use std::time::Duration;
+
+use nfc1::{Property, Result};
+use Srix4k;
+
+/// Reader properties to set, `None` leaves a property as it is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceConfig {
+    /// Whether selecting a tag waits until one enters the field, instead
+    /// of failing right away if the field is empty.
+    pub infinite_select: Option<bool>,
+    /// Time the reader waits for the answer to a command,
+    /// `Duration::ZERO` to wait forever.
+    pub command_timeout: Option<Duration>,
+    /// Time the reader waits for the host communication,
+    /// `Duration::ZERO` to wait forever.
+    pub com_timeout: Option<Duration>,
+    /// Time the reader waits for the tag to answer its selection,
+    /// `Duration::ZERO` to wait forever.
+    pub atr_timeout: Option<Duration>,
+    /// Whether the RF field is on. Turning it off resets the tags in it.
+    pub activate_field: Option<bool>,
+    /// Whether the reader computes and checks the CRC of the frames.
+    pub handle_crc: Option<bool>,
+}
+
+impl DeviceConfig {
+    /// Set the properties of the configuration on `device`.
+    pub fn apply(&self, device: &mut nfc1::Device) -> Result<()> {
+        if let Some(enabled) = self.infinite_select {
+            device.set_property_bool(Property::InfiniteSelect, enabled)?;
+        }
+        if let Some(timeout) = self.command_timeout {
+            device
+                .set_property_int(Property::TimeoutCommand, millis(timeout))?;
+        }
+        if let Some(timeout) = self.com_timeout {
+            device.set_property_int(Property::TimeoutCom, millis(timeout))?;
+        }
+        if let Some(timeout) = self.atr_timeout {
+            device.set_property_int(Property::TimeoutAtr, millis(timeout))?;
+        }
+        if let Some(enabled) = self.activate_field {
+            device.set_property_bool(Property::ActivateField, enabled)?;
+        }
+        if let Some(enabled) = self.handle_crc {
+            device.set_property_bool(Property::HandleCrc, enabled)?;
+        }
+        Ok(())
+    }
+}
+
+/// Timeout in milliseconds, as libnfc expects it.
+fn millis(timeout: Duration) -> i32 {
+    timeout.as_millis().min(i32::MAX as u128) as i32
+}
+
+impl Srix4k<'_> {
+    /// Set reader properties while connected.
+    ///
+    /// `infinite_select` only affects the next selection; to configure the
+    /// first one connect with `connect_from_configured`.
+    pub fn configure(&mut self, config: &DeviceConfig) -> Result<()> {
+        config.apply(&mut self.device)
+    }
+}
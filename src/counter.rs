@@ -0,0 +1,200 @@
+//! Interpretation and verified decrements of the *Count down Counter*
+//! blocks.
+//!
+//! The 21 low bits of a counter are a binary counter, the 11 high bits are
+//! OTP reload bits: clearing one of them allows the low bits to be set
+//! again. Each block is transmitted least significant byte first.
+//!
+//! A decrement is a single write of the new value, with no protection
+//! against tearing: a write interrupted by the tag leaving the field can
+//! leave any mix of the old and new bits in the block. Such an
+//! intermediate value may be lower than the new one, and the chip refuses
+//! any write that would raise the counter, so it cannot be corrected.
+//! Splitting the decrement into ordered clears would not help: the second
+//! write would raise the counter.
+
+use std::fmt;
+
+use codec::Response;
+use replay::Transport;
+use Command;
+
+#[cfg(feature = "nfc")]
+use mem;
+#[cfg(feature = "nfc")]
+use nfc1::Result;
+#[cfg(feature = "nfc")]
+use Srix4k;
+
+/// Data of a counter block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Counter(u32);
+
+impl Counter {
+    /// Bits of the binary counter.
+    pub const COUNT_MASK: u32 = 0x001F_FFFF;
+    /// Bits of the reload bits.
+    pub const RELOAD_MASK: u32 = 0xFFE0_0000;
+
+    /// Counter from the bytes as transmitted by the tag.
+    pub fn from_bytes(bytes: [u8; 4]) -> Counter {
+        Counter(u32::from_le_bytes(bytes))
+    }
+    /// Bytes as transmitted by the tag.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+    /// Raw block data.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+    /// Value of the binary counter, the low 21 bits. Most applications
+    /// only use this part.
+    pub fn count(&self) -> u32 {
+        self.0 & Counter::COUNT_MASK
+    }
+    /// Reload bits still set, each one allowing the binary counter to be
+    /// set again.
+    pub fn reloads(&self) -> u32 {
+        (self.0 & Counter::RELOAD_MASK).count_ones()
+    }
+    /// Decrements left, using every reload to set the binary counter to
+    /// its maximum.
+    pub fn remaining(&self) -> u64 {
+        self.count() as u64
+            + self.reloads() as u64 * (Counter::COUNT_MASK as u64 + 1)
+    }
+}
+
+impl From<u32> for Counter {
+    fn from(value: u32) -> Self {
+        Counter(value)
+    }
+}
+
+impl From<Counter> for u32 {
+    fn from(value: Counter) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for Counter {
+    /// `count (reloads reloads)`, e.g. `1000 (11 reloads)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({} reloads)", self.count(), self.reloads())
+    }
+}
+
+/// Write `value` to the counter block `block_address` of `transport` and
+/// read it back, retrying the write once if it reads back another value.
+///
+/// Returns whether the counter holds `value`: `false` if the chip refused
+/// the write, e.g. because `value` is higher than the counter.
+pub fn write_verified<T: Transport>(
+    transport: &mut T,
+    block_address: u8,
+    value: u32,
+) -> std::result::Result<bool, T::Error> {
+    for _ in 0..2 {
+        transport.execute(&Command::WriteBlock(block_address, value))?;
+        let read = transport.execute(&Command::ReadBlock(block_address))?;
+        if read == Some(Response::Block(value)) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(feature = "nfc")]
+impl Srix4k<'_> {
+    /// Read the counter block, 5 or 6.
+    ///
+    /// Fails with `InvalidArgument` if the block is not a counter.
+    pub fn read_counter(&mut self, block_address: u8) -> Result<Counter> {
+        if !mem::COUNTDOWN.contains(&(block_address as usize)) {
+            return Err(nfc1::Error::InvalidArgument);
+        }
+        self.send_read_block(block_address).map(Counter)
+    }
+    /// Decrement the counter block, 5 or 6, to `value` in a single write,
+    /// re-reading it after the write, see `write_verified`.
+    ///
+    /// The write is not tear-safe: see the module documentation. A torn
+    /// write leaves an intermediate value, which the retry fixes if it is
+    /// higher than `value`.
+    ///
+    /// Fails with `InvalidArgument` if the block is not a counter or
+    /// `value` is higher than the counter, and with `RfTransmissionError`
+    /// if the write cannot be verified, e.g. after a torn write left a
+    /// value lower than `value`.
+    pub fn decrement_counter(
+        &mut self,
+        block_address: u8,
+        value: u32,
+    ) -> Result<()> {
+        if !mem::COUNTDOWN.contains(&(block_address as usize)) {
+            return Err(nfc1::Error::InvalidArgument);
+        }
+        let old = self.send_read_block(block_address)?;
+        if value > old {
+            return Err(nfc1::Error::InvalidArgument);
+        }
+        if value == old {
+            return Ok(());
+        }
+
+        debug!("Counter {:#04X}: writing {:#010X}", block_address, value);
+        if !write_verified(self, block_address, value)? {
+            warn!("Counter {:#04X}: write not verified", block_address);
+            return Err(nfc1::Error::RfTransmissionError);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::{Answer, EmulatedTag, Field};
+    use iso14443b2;
+
+    const UID: u64 = 0xD002_0C00_0000_0001;
+
+    /// Field with a selected tag whose first counter holds `counter`.
+    fn field(counter: u32) -> Field {
+        let mut tag = EmulatedTag::new(UID);
+        tag.eeprom[0x05] = counter;
+        let mut field = Field::new(42);
+        field.insert(tag);
+        let chip_id = match field.transceive(&iso14443b2::initiate()) {
+            Answer::Frame(frame) => frame[0],
+            answer => panic!("initiate answered {:?}", answer),
+        };
+        field.transceive(&iso14443b2::select(chip_id));
+        field
+    }
+
+    #[test]
+    fn borrowing_decrement_is_one_write() {
+        let mut field = field(6);
+        assert_eq!(write_verified(&mut field, 0x05, 5), Ok(true));
+        assert_eq!(field.tag(UID).unwrap().eeprom[0x05], 5);
+    }
+
+    #[test]
+    fn borrow_from_reload_bit() {
+        let old = Counter::RELOAD_MASK;
+        let new = (Counter::RELOAD_MASK << 1) | Counter::COUNT_MASK;
+        let mut field = field(old);
+        assert_eq!(write_verified(&mut field, 0x05, new), Ok(true));
+        assert_eq!(field.tag(UID).unwrap().eeprom[0x05], new);
+    }
+
+    #[test]
+    fn raising_is_refused() {
+        let mut field = field(5);
+        assert_eq!(write_verified(&mut field, 0x05, 6), Ok(false));
+        assert_eq!(field.tag(UID).unwrap().eeprom[0x05], 5);
+    }
+}
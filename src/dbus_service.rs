@@ -0,0 +1,168 @@
+//! Session D-Bus interface to a reader, so desktop applications can react
+//! to tags like they do to smartcards.
+//!
+//! The service owns the `org.srix4k` name and exposes the
+//! `org.srix4k.Reader1` interface on `/org/srix4k/Reader`:
+//! - `TagPresent() -> b`: whether a tag is in the field.
+//! - `GetUid() -> t`: UID of the tag in the field.
+//! - `ReadBlock(y address) -> u`: data of a block.
+//! - signal `TagArrived(t uid)`: a tag entered the field.
+//! - signal `TagRemoved(t uid)`: the tag left the field.
+
+use std::ffi::CString;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dbus::blocking::Connection;
+use dbus::channel::Sender;
+use dbus::strings::ErrorName;
+use dbus::{Message, MessageType};
+use monitor::{Monitor, TagEvent};
+
+/// Well-known bus name of the service.
+pub const BUS_NAME: &str = "org.srix4k";
+/// Object path of the reader.
+pub const PATH: &str = "/org/srix4k/Reader";
+/// Interface of the reader.
+pub const INTERFACE: &str = "org.srix4k.Reader1";
+
+/// Default time between two checks of the field.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Introspection data of the reader object.
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.srix4k.Reader1">
+    <method name="TagPresent"><arg type="b" direction="out"/></method>
+    <method name="GetUid"><arg type="t" direction="out"/></method>
+    <method name="ReadBlock">
+      <arg name="address" type="y" direction="in"/>
+      <arg type="u" direction="out"/>
+    </method>
+    <signal name="TagArrived"><arg name="uid" type="t"/></signal>
+    <signal name="TagRemoved"><arg name="uid" type="t"/></signal>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect"><arg type="s" direction="out"/></method>
+  </interface>
+</node>"#;
+
+/// D-Bus service owning a reader.
+pub struct Service<'a> {
+    /// Session bus connection.
+    connection: Connection,
+    /// Reader and the tag in its field.
+    monitor: Monitor<'a>,
+    /// Time between two checks of the field.
+    poll_interval: Duration,
+}
+
+impl Service<'_> {
+    /// Connect to the session bus and claim the service name.
+    pub fn new(
+        device: nfc1::Device<'_>,
+    ) -> Result<Service<'_>, Box<dyn std::error::Error>> {
+        let connection = Connection::new_session()?;
+        connection.request_name(BUS_NAME, false, true, true)?;
+        info!("Serving {} on the session bus", BUS_NAME);
+
+        Ok(Service {
+            connection,
+            monitor: Monitor::new(device)?,
+            poll_interval: POLL_INTERVAL,
+        })
+    }
+    /// Set the time between two checks of the field.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+    /// Serve method calls and emit signals forever.
+    pub fn run(&mut self) -> Result<(), dbus::Error> {
+        self.poll_tag();
+        let mut last_poll = Instant::now();
+        loop {
+            if last_poll.elapsed() >= self.poll_interval {
+                self.poll_tag();
+                last_poll = Instant::now();
+            }
+
+            let timeout = self.poll_interval.min(Duration::from_millis(50));
+            match self.connection.channel().blocking_pop_message(timeout)? {
+                Some(message) => self.handle(message),
+                None => thread::yield_now(),
+            }
+        }
+    }
+}
+
+impl Service<'_> {
+    /// Check whether a tag entered or left the field.
+    fn poll_tag(&mut self) {
+        if let Some(event) = self.monitor.poll() {
+            self.emit(event);
+        }
+    }
+    /// Emit the signal corresponding to the event.
+    fn emit(&self, event: TagEvent) {
+        let member = match event {
+            TagEvent::Arrival(_) => "TagArrived",
+            TagEvent::Removal(_) => "TagRemoved",
+        };
+        if let Ok(signal) = Message::new_signal(PATH, INTERFACE, member) {
+            let _ = self.connection.send(signal.append1(event.uid()));
+        }
+    }
+    /// Answer a method call.
+    fn handle(&mut self, call: Message) {
+        if call.msg_type() != MessageType::MethodCall {
+            return;
+        }
+        match call.path() {
+            Some(ref path) if &**path == PATH => {}
+            _ => return,
+        }
+        let member = match call.member() {
+            Some(member) => member.to_string(),
+            None => return,
+        };
+        debug!("D-Bus call {}", member);
+
+        let reply = match member.as_str() {
+            "Introspect" => call.method_return().append1(INTROSPECTION),
+            "TagPresent" => {
+                if self.monitor.uid().is_none() {
+                    self.poll_tag();
+                }
+                call.method_return().append1(self.monitor.uid().is_some())
+            }
+            "GetUid" => match self.monitor.tag() {
+                Some(tag) => match tag.send_get_uid() {
+                    Ok(uid) => call.method_return().append1(uid),
+                    Err(err) => error(&call, "Failed", &err.to_string()),
+                },
+                None => error(&call, "NoTag", "no tag in the field"),
+            },
+            "ReadBlock" => match (call.read1::<u8>(), self.monitor.tag()) {
+                (Ok(address), Some(tag)) => match tag.send_read_block(address)
+                {
+                    Ok(block_data) => call.method_return().append1(block_data),
+                    Err(err) => error(&call, "Failed", &err.to_string()),
+                },
+                (Err(_), _) => error(&call, "InvalidArgs", "expected a byte"),
+                (_, None) => error(&call, "NoTag", "no tag in the field"),
+            },
+            _ => error(&call, "UnknownMethod", &member),
+        };
+        let _ = self.connection.send(reply);
+    }
+}
+
+/// Error reply `org.srix4k.Error.<name>` to the method call.
+fn error(call: &Message, name: &str, message: &str) -> Message {
+    let name = ErrorName::new(format!("org.srix4k.Error.{}", name))
+        .expect("error names are valid");
+    let message = CString::new(message.replace('\0', ""))
+        .expect("null bytes were removed");
+    call.error(&name, &message)
+}
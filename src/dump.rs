@@ -0,0 +1,687 @@
+//! Offline copies of the tag memory and their differences.
+//!
+//! Dump files contain the EEPROM blocks, optionally followed by the system
+//! block and the UID. Every value is stored least significant byte first,
+//! in the same order the tag sends it:
+//!
+//! | Offset | Size | Content |
+//! | --- | --- | --- |
+//! | 0 | 512 | blocks 0 to 127 |
+//! | 512 | 4 | block 255 (optional) |
+//! | 516 | 8 | UID (optional) |
+//!
+//! With the `zstd` feature, `serialize_compressed` writes a zstd archive of
+//! the file, and `parse` and `signature::verify` read archives as the
+//! files they contain.
+
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::fmt;
+#[cfg(feature = "zstd")]
+use std::io;
+#[cfg(feature = "nfc")]
+use std::time::Instant;
+
+#[cfg(feature = "nfc")]
+use error::{Srix4kError, Srix4kResult};
+use mem;
+#[cfg(feature = "nfc")]
+use mem::{BlockAddr, Blocks};
+use range::BlockSet;
+use signature;
+#[cfg(feature = "nfc")]
+use system::IrreversibleWrite;
+use system::SystemBlock;
+#[cfg(feature = "nfc")]
+use {Srix4k, Strictness};
+
+/// Size of a dump file with only the EEPROM.
+pub const EEPROM_DUMP_SIZE: usize = mem::EEPROM_SIZE;
+/// Size of a dump file with EEPROM, system block and UID.
+pub const FULL_DUMP_SIZE: usize =
+    EEPROM_DUMP_SIZE + mem::BLOCK_SIZE + mem::UID_SIZE as usize;
+/// First bytes of a zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+/// Largest file a zstd archive is decompressed to, a signed full dump, so
+/// that an archive cannot inflate to any size.
+#[cfg(feature = "zstd")]
+const MAX_DECOMPRESSED_SIZE: usize = FULL_DUMP_SIZE + signature::TRAILER_SIZE;
+
+/// Copy of the tag memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Srix4kDump {
+    /// UID of the tag, if known.
+    pub uid: Option<u64>,
+    /// [0 to 127] EEPROM.
+    pub eeprom: [u32; mem::BLOCK_COUNT],
+    /// [255] System OTP bits, if known.
+    pub system: Option<u32>,
+}
+
+/// Errors that can occur while parsing a dump file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpError {
+    /// `InvalidSize(size)`
+    /// The file is neither an EEPROM nor a full dump.
+    InvalidSize(usize),
+    /// The file is a zstd archive, e.g. of a dump, to decompress first:
+    /// the crate is built without the `zstd` feature.
+    Compressed,
+    /// The file starts as a zstd archive, but is corrupt or decompresses
+    /// to more than a signed full dump.
+    InvalidArchive,
+    /// The file is a signed dump, to parse with `signature::verify`.
+    Signed,
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DumpError::InvalidSize(size) => write!(
+                f,
+                "invalid dump size {}, expected {} or {} bytes",
+                size, EEPROM_DUMP_SIZE, FULL_DUMP_SIZE
+            ),
+            DumpError::Compressed => {
+                write!(f, "zstd compressed file, decompress it first")
+            }
+            DumpError::InvalidArchive => {
+                write!(f, "invalid zstd archive of a dump")
+            }
+            DumpError::Signed => {
+                write!(f, "signed dump, verify its signature first")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+/// Reason a dump is not the one of the tag it is compared to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpMismatch {
+    /// `Chip { dump, tag }`
+    /// The dump comes from another kind of chip, named after its memory
+    /// map, e.g. `SRI512`.
+    Chip {
+        dump: &'static str,
+        tag: &'static str,
+    },
+    /// `Uid { dump, tag }`
+    /// The dump comes from another tag.
+    Uid { dump: u64, tag: u64 },
+}
+
+impl fmt::Display for DumpMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DumpMismatch::Chip { dump, tag } => {
+                write!(f, "dump of a {} chip, the tag is a {}", dump, tag)
+            }
+            DumpMismatch::Uid { dump, tag } => write!(
+                f,
+                "dump of UID {:016X}, the tag has UID {:016X}",
+                dump, tag
+            ),
+        }
+    }
+}
+
+/// Block whose data differs between two dumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockDiff {
+    /// From 0 to 127, or 255 for system.
+    pub address: u8,
+    /// Block data in the first dump.
+    pub old: u32,
+    /// Block data in the second dump.
+    pub new: u32,
+}
+
+/// Differences between two dumps.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DumpDiff {
+    /// `(old, new)` UIDs, if both are known and they differ.
+    pub uid: Option<(u64, u64)>,
+    /// Blocks that differ, in address order.
+    pub blocks: Vec<BlockDiff>,
+}
+
+/// Content of the file `bytes` if it is a zstd archive, the file itself
+/// otherwise.
+///
+/// A dump or signed dump starting with the magic number of zstd frames is
+/// taken as is if it does not decompress. Fails with `Compressed` for an
+/// archive without the `zstd` feature, and with `InvalidArchive` for an
+/// archive that is corrupt or larger than a signed full dump.
+pub fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, DumpError> {
+    if !bytes.starts_with(&ZSTD_MAGIC) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    #[cfg(feature = "zstd")]
+    match zstd::bulk::decompress(bytes, MAX_DECOMPRESSED_SIZE) {
+        Ok(content) => return Ok(Cow::Owned(content)),
+        Err(err) => debug!("Not a zstd archive of a dump: {}", err),
+    }
+    let size = bytes.len();
+    if size == EEPROM_DUMP_SIZE
+        || size == FULL_DUMP_SIZE
+        || signature::split(bytes).is_some()
+    {
+        Ok(Cow::Borrowed(bytes))
+    } else if cfg!(feature = "zstd") {
+        Err(DumpError::InvalidArchive)
+    } else {
+        Err(DumpError::Compressed)
+    }
+}
+
+/// zstd archive of the file `bytes`, at compression `level` from 1 to 22,
+/// or 0 for the default of zstd.
+#[cfg(feature = "zstd")]
+pub fn compress(bytes: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    zstd::bulk::compress(bytes, level)
+}
+
+impl Srix4kDump {
+    /// Parse an EEPROM or full dump file, or a zstd archive of one.
+    ///
+    /// Fails as `decompress` for archives, and with `Signed` for a dump
+    /// followed by the trailer of `signature`.
+    pub fn parse(bytes: &[u8]) -> Result<Srix4kDump, DumpError> {
+        let bytes = &*decompress(bytes)?;
+        if bytes.len() != EEPROM_DUMP_SIZE && bytes.len() != FULL_DUMP_SIZE {
+            if signature::split(bytes).is_some() {
+                return Err(DumpError::Signed);
+            }
+            return Err(DumpError::InvalidSize(bytes.len()));
+        }
+        Ok(Srix4kDump::from_blocks(bytes))
+    }
+    /// Dump of the blocks of an EEPROM or full dump file, least
+    /// significant byte first, `bytes` being of one of their sizes.
+    fn from_blocks(bytes: &[u8]) -> Srix4kDump {
+        let block = |i: usize| {
+            let start = mem::byte_offset(i);
+            u32::from_le_bytes(
+                bytes[start..start + mem::BLOCK_SIZE].try_into().unwrap(),
+            )
+        };
+        let mut dump = Srix4kDump {
+            uid: None,
+            eeprom: [0; mem::BLOCK_COUNT],
+            system: None,
+        };
+        for (i, block_data) in dump.eeprom.iter_mut().enumerate() {
+            *block_data = block(i);
+        }
+        if bytes.len() == FULL_DUMP_SIZE {
+            dump.system = Some(block(mem::BLOCK_COUNT));
+            dump.uid = Some(u64::from_le_bytes(
+                bytes[EEPROM_DUMP_SIZE + mem::BLOCK_SIZE..]
+                    .try_into()
+                    .unwrap(),
+            ));
+        }
+        dump
+    }
+    /// Memory map of the chip of the dump, from its UID, the one of the
+    /// SRIX4K if it has no UID or the chip is not known.
+    pub fn layout(&self) -> &'static dyn mem::MemoryLayout {
+        self.uid.and_then(mem::layout_of).unwrap_or(&mem::Srix4kLayout)
+    }
+    /// Whether the dump may come from the tag with `uid`: its UID, and the
+    /// chip telling its memory map, must be the same. `None` if the dump
+    /// has no UID to compare.
+    pub fn check_uid(&self, uid: u64) -> Option<DumpMismatch> {
+        let dump = self.uid?;
+        let chip = |uid| mem::layout_of(uid).map(|chip| chip.name());
+        match (chip(dump), chip(uid)) {
+            (Some(dump), Some(tag)) if dump != tag => {
+                Some(DumpMismatch::Chip { dump, tag })
+            }
+            _ if dump != uid => Some(DumpMismatch::Uid { dump, tag: uid }),
+            _ => None,
+        }
+    }
+    /// Serialize to a dump file.
+    ///
+    /// The dump is full only if both the system block and the UID are known,
+    /// otherwise only the EEPROM is written.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FULL_DUMP_SIZE);
+        for block_data in self.eeprom.iter() {
+            bytes.extend(block_data.to_le_bytes());
+        }
+        if let (Some(system), Some(uid)) = (self.system, self.uid) {
+            bytes.extend(system.to_le_bytes());
+            bytes.extend(uid.to_le_bytes());
+        }
+        bytes
+    }
+    /// zstd archive of the dump file, see `serialize` and `compress`.
+    #[cfg(feature = "zstd")]
+    pub fn serialize_compressed(&self, level: i32) -> io::Result<Vec<u8>> {
+        compress(&self.serialize(), level)
+    }
+    /// EEPROM blocks as an EEPROM dump file, least significant byte first.
+    pub fn to_bytes(&self) -> [u8; EEPROM_DUMP_SIZE] {
+        let mut bytes = [0; EEPROM_DUMP_SIZE];
+        for (chunk, block_data) in bytes
+            .chunks_exact_mut(mem::BLOCK_SIZE)
+            .zip(self.eeprom.iter())
+        {
+            chunk.copy_from_slice(&block_data.to_le_bytes());
+        }
+        bytes
+    }
+    /// Get specified block, from 0 to 127, or 255 for system.
+    pub fn block(&self, address: u8) -> Option<u32> {
+        match address as usize {
+            i if mem::EEPROM.contains(&i) => Some(self.eeprom[i]),
+            mem::SYSTEM_ADDR => self.system,
+            _ => None,
+        }
+    }
+    /// Typed view of the system block, if known.
+    pub fn system_block(&self) -> Option<SystemBlock> {
+        self.system.map(SystemBlock::from)
+    }
+    /// Compare with a newer dump.
+    pub fn diff(&self, new: &Srix4kDump) -> DumpDiff {
+        let mut diff = DumpDiff::default();
+        if let (Some(old_uid), Some(new_uid)) = (self.uid, new.uid) {
+            if old_uid != new_uid {
+                diff.uid = Some((old_uid, new_uid));
+            }
+        }
+        for (i, (old, new)) in
+            self.eeprom.iter().zip(new.eeprom.iter()).enumerate()
+        {
+            if old != new {
+                diff.blocks.push(BlockDiff {
+                    address: i as u8,
+                    old: *old,
+                    new: *new,
+                });
+            }
+        }
+        if let (Some(old), Some(new)) = (self.system, new.system) {
+            if old != new {
+                diff.blocks.push(BlockDiff {
+                    address: mem::SYSTEM_ADDR as u8,
+                    old,
+                    new,
+                });
+            }
+        }
+
+        diff
+    }
+}
+
+impl From<[u8; EEPROM_DUMP_SIZE]> for Srix4kDump {
+    /// Dump of the EEPROM blocks of an EEPROM dump file, least significant
+    /// byte first, without system block and UID.
+    fn from(bytes: [u8; EEPROM_DUMP_SIZE]) -> Self {
+        Srix4kDump::from_blocks(&bytes)
+    }
+}
+
+impl From<&Srix4kDump> for [u8; EEPROM_DUMP_SIZE] {
+    fn from(dump: &Srix4kDump) -> Self {
+        dump.to_bytes()
+    }
+}
+
+impl DumpDiff {
+    /// Whether the dumps are identical.
+    pub fn is_empty(&self) -> bool {
+        self.uid.is_none() && self.blocks.is_empty()
+    }
+    /// Differences of the blocks of `blocks` only, and of the UID.
+    pub fn filter(&self, blocks: &BlockSet) -> DumpDiff {
+        DumpDiff {
+            uid: self.uid,
+            blocks: self
+                .blocks
+                .iter()
+                .filter(|block| blocks.contains_address(block.address as usize))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for DumpDiff {
+    /// One line per difference, `address: old -> new`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((old, new)) = self.uid {
+            writeln!(f, "uid: {:016X} -> {:016X}", old, new)?;
+        }
+        for block in &self.blocks {
+            writeln!(
+                f,
+                "{:#04X}: {:#010X} -> {:#010X}",
+                block.address, block.old, block.new
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What `Srix4k::restore` writes, and how.
+#[cfg(feature = "nfc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreOptions {
+    /// Blocks to restore.
+    pub blocks: BlockSet,
+    /// Whether to restore the dump of another tag.
+    pub strictness: Strictness,
+    /// Read every written block back.
+    pub verify: bool,
+}
+
+#[cfg(feature = "nfc")]
+impl Default for RestoreOptions {
+    /// The whole EEPROM, verified, from a dump of the same tag only.
+    fn default() -> Self {
+        RestoreOptions {
+            blocks: BlockSet::eeprom(),
+            strictness: Strictness::Deny,
+            verify: true,
+        }
+    }
+}
+
+/// Errors of a dump or restore bounded by a deadline.
+#[cfg(feature = "nfc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeadlineError<P> {
+    /// `Tag(error)`
+    /// Communicating with the tag failed.
+    Tag(Srix4kError),
+    /// `Deadline(progress)`
+    /// The deadline passed, the operation stopped after `progress`.
+    Deadline(P),
+}
+
+#[cfg(feature = "nfc")]
+impl<P> fmt::Display for DeadlineError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeadlineError::Tag(err) => write!(f, "{}", err),
+            DeadlineError::Deadline(_) => {
+                write!(f, "deadline passed, operation incomplete")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl<P: fmt::Debug> std::error::Error for DeadlineError<P> {}
+
+#[cfg(feature = "nfc")]
+impl<P> From<Srix4kError> for DeadlineError<P> {
+    fn from(err: Srix4kError) -> Self {
+        DeadlineError::Tag(err)
+    }
+}
+
+/// Blocks read by `Srix4k::dump_before` before its deadline.
+#[cfg(feature = "nfc")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PartialDump {
+    /// Dump of the blocks read, the others are 0 or `None`.
+    pub dump: Srix4kDump,
+    /// Blocks read.
+    pub read: BlockSet,
+}
+
+/// Blocks of `Srix4k::restore_before` handled before its deadline.
+#[cfg(feature = "nfc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PartialRestore {
+    /// Blocks written.
+    pub written: usize,
+    /// Blocks left to restore.
+    pub remaining: BlockSet,
+}
+
+#[cfg(feature = "nfc")]
+impl PartialDump {
+    /// No block read yet.
+    fn new() -> PartialDump {
+        PartialDump {
+            dump: Srix4kDump {
+                uid: None,
+                eeprom: [0; mem::BLOCK_COUNT],
+                system: None,
+            },
+            read: BlockSet::EMPTY,
+        }
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl Srix4k<'_> {
+    /// Read the EEPROM, the system block and the UID, if the chip answers
+    /// `GetUID`, into a dump.
+    ///
+    /// The UID is read first for the memory map of the chip, see
+    /// `Srix4kDump::layout`: blocks past its EEPROM, e.g. of a SRIX512,
+    /// are left 0.
+    ///
+    /// Blocks are read with the retries of `read_block`, straight into the
+    /// dump: the only allocation per block is the answer of libnfc.
+    pub fn dump(&mut self) -> Srix4kResult<Srix4kDump> {
+        self.dump_with_progress(|_, _| {})
+    }
+    /// Like `dump`, calling `progress(read, total)` before the first block
+    /// and after each block read, e.g. to draw a progress bar. `total`
+    /// counts the system block.
+    pub fn dump_with_progress<F>(
+        &mut self,
+        mut progress: F,
+    ) -> Srix4kResult<Srix4kDump>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut partial = PartialDump::new();
+        self.dump_while(&mut partial, |read, total| {
+            progress(read, total);
+            true
+        })?;
+        Ok(partial.dump)
+    }
+    /// Like `dump`, stopping with the blocks read so far once `deadline`
+    /// passed, e.g. to bound the time a kiosk waits.
+    ///
+    /// The deadline is checked before each block: it can be exceeded by
+    /// the time of one read, retries included.
+    pub fn dump_before(
+        &mut self,
+        deadline: Instant,
+    ) -> Result<Srix4kDump, DeadlineError<Box<PartialDump>>> {
+        let mut partial = PartialDump::new();
+        if self.dump_while(&mut partial, |_, _| Instant::now() < deadline)? {
+            Ok(partial.dump)
+        } else {
+            warn!("Dump stopped at its deadline");
+            Err(DeadlineError::Deadline(Box::new(partial)))
+        }
+    }
+    /// Read the blocks into `partial` while `proceed(read, total)`, asked
+    /// before each block and once all are read, returns `true`. Returns
+    /// whether the dump is complete.
+    fn dump_while<F>(
+        &mut self,
+        partial: &mut PartialDump,
+        mut proceed: F,
+    ) -> Srix4kResult<bool>
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        // The UID tells the memory map of the chip.
+        if self.quirks().get_uid {
+            partial.dump.uid = Some(self.send_get_uid()?);
+        }
+        let layout = partial.dump.layout();
+        let total = layout.block_count() + 1;
+        for block_address in layout.eeprom().blocks() {
+            let i = block_address.index();
+            if !proceed(i, total) {
+                return Ok(false);
+            }
+            partial.dump.eeprom[i] = self.read_block(block_address.get())?;
+            partial.read.insert(block_address);
+        }
+        if !proceed(layout.block_count(), total) {
+            return Ok(false);
+        }
+        let system_addr = layout.system_addr() as u8;
+        partial.dump.system = Some(self.read_block(system_addr)?);
+        partial.read.insert(BlockAddr::SYSTEM);
+        proceed(total, total);
+        Ok(true)
+    }
+    /// Write the EEPROM blocks of `options.blocks` whose data in `dump`
+    /// differs from the tag, returning how many were written. Blocks past
+    /// the EEPROM of the chip of the tag, e.g. of a SRIX512, are ignored.
+    ///
+    /// A dump of another tag, see `Srix4kDump::check_uid`, is warned about
+    /// or refused according to `options.strictness`. The system block is
+    /// skipped, see `restore_system`. With `options.verify`, each written
+    /// block is read back, failing with `Transmission` if it differs.
+    pub fn restore(
+        &mut self,
+        dump: &Srix4kDump,
+        options: RestoreOptions,
+    ) -> Srix4kResult<usize> {
+        self.restore_while(dump, options, || true)
+            .map(|progress| progress.written)
+    }
+    /// Like `restore`, stopping once `deadline` passed with the number of
+    /// blocks written and those left, which a later `restore` of the
+    /// remaining blocks completes.
+    ///
+    /// The deadline is checked before each block: it can be exceeded by
+    /// the time of one block, read, written and verified.
+    pub fn restore_before(
+        &mut self,
+        dump: &Srix4kDump,
+        options: RestoreOptions,
+        deadline: Instant,
+    ) -> Result<usize, DeadlineError<PartialRestore>> {
+        let progress =
+            self.restore_while(dump, options, || Instant::now() < deadline)?;
+        if progress.remaining.is_empty() {
+            Ok(progress.written)
+        } else {
+            warn!("Restore stopped at its deadline");
+            Err(DeadlineError::Deadline(progress))
+        }
+    }
+    /// Restore the blocks while `proceed`, asked before each block,
+    /// returns `true`.
+    fn restore_while<F>(
+        &mut self,
+        dump: &Srix4kDump,
+        options: RestoreOptions,
+        mut proceed: F,
+    ) -> Srix4kResult<PartialRestore>
+    where
+        F: FnMut() -> bool,
+    {
+        self.check_dump(dump, options.strictness)?;
+        if options.blocks.contains(BlockAddr::SYSTEM) {
+            warn!("Skipping the system block, see restore_system");
+        }
+        // Blocks past the EEPROM of the chip, e.g. of a SRIX512, are not
+        // on the tag.
+        let blocks = options.blocks & BlockSet::from(self.layout()?.eeprom());
+        let mut progress = PartialRestore {
+            written: 0,
+            remaining: blocks,
+        };
+        for block_address in blocks {
+            if !proceed() {
+                return Ok(progress);
+            }
+            progress.remaining.remove(block_address);
+            let block_data = match dump.block(block_address.get()) {
+                Some(block_data) => block_data,
+                None => continue,
+            };
+            let address = block_address.get();
+            if self.read_block(address)? == block_data {
+                continue;
+            }
+            self.send_write_block(address, block_data)?;
+            if options.verify && self.read_block(address)? != block_data {
+                warn!("Block {:#04X}: write not verified", address);
+                return Err(Srix4kError::Transmission);
+            }
+            progress.written += 1;
+        }
+        Ok(progress)
+    }
+    /// Write the system block of `dump` if it differs from the tag,
+    /// returning whether it was written. A dump without system block
+    /// writes nothing.
+    ///
+    /// Bits the tag has unlocked stay locked if the dump has them locked:
+    /// the write cannot be undone. A dump of another tag is handled like
+    /// `restore` does.
+    pub fn restore_system(
+        &mut self,
+        dump: &Srix4kDump,
+        strictness: Strictness,
+        acknowledgment: IrreversibleWrite,
+    ) -> Srix4kResult<bool> {
+        let system = match dump.system {
+            Some(system) => system,
+            None => return Ok(false),
+        };
+        self.check_dump(dump, strictness)?;
+        let address = self.layout()?.system_addr() as u8;
+        if self.read_block(address)? == system {
+            return Ok(false);
+        }
+        self.send_write_system(system, acknowledgment)?;
+        Ok(true)
+    }
+    /// Memory map of the chip of the tag, from its UID, the one of the
+    /// SRIX4K if the UID cannot be read or the chip is not known, like
+    /// `Srix4kDump::layout`.
+    fn layout(&mut self) -> Srix4kResult<&'static dyn mem::MemoryLayout> {
+        let uid = match self.uid {
+            Some(uid) => Some(uid),
+            None if self.quirks().get_uid => Some(self.send_get_uid()?),
+            None => None,
+        };
+        Ok(uid.and_then(mem::layout_of).unwrap_or(&mem::Srix4kLayout))
+    }
+    /// Compare the UID of `dump` with the one of the tag, then warn or
+    /// fail with `DeviceNotSupported` according to `strictness`.
+    fn check_dump(
+        &mut self,
+        dump: &Srix4kDump,
+        strictness: Strictness,
+    ) -> Srix4kResult<()> {
+        if dump.uid.is_none() || !self.quirks().get_uid {
+            debug!("No UID to check the dump against");
+            return Ok(());
+        }
+        if let Some(mismatch) = dump.check_uid(self.send_get_uid()?) {
+            match strictness {
+                Strictness::Warn => warn!("Restoring anyway a {}", mismatch),
+                Strictness::Deny => {
+                    warn!("Refusing to restore a {}", mismatch);
+                    return Err(nfc1::Error::DeviceNotSupported.into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
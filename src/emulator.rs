@@ -0,0 +1,451 @@
+//! In-memory tags answering the frames of `iso14443b2`, to exercise the
+//! protocol, anticollision included, without physical tags.
+//!
+//! A `Field` holds any number of `EmulatedTag`s and answers each frame like
+//! the field of a reader would: every tag in a state accepting the frame
+//! answers, and different answers collide. Chip IDs are drawn from a
+//! seeded generator, so anticollision runs are deterministic. `Field` is a
+//! `replay::Transport` for the commands of the selected tag.
+//!
+//! Writes follow the rules of the regions: OTP bits can only be cleared,
+//! counters only decremented and locked blocks are not written.
+
+use std::fmt;
+
+use codec::{DecodeError, Response};
+use dump::Srix4kDump;
+use fill::splitmix64;
+use mem;
+use replay::Transport;
+use system::SystemBlock;
+use Command;
+
+/// State of a tag, as in the SRIX4K datasheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagState {
+    /// In the field, waiting for `Initiate`.
+    Ready,
+    /// Drew a chip ID, answers `Pcall16`, `Slot_marker` and `Select`.
+    Inventory,
+    /// Answers the memory commands.
+    Selected,
+    /// Another tag was selected, answers `Select` with its chip ID.
+    Deselected,
+    /// Silent until it leaves the field, after `Completion`.
+    Deactivated,
+}
+
+/// Tag of the SRIX4K family in a `Field`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmulatedTag {
+    /// UID, answered to `Get_UID`.
+    pub uid: u64,
+    /// [0 to 127] EEPROM.
+    pub eeprom: Vec<u32>,
+    /// [255] System OTP bits.
+    pub system: u32,
+    /// Chip ID drawn at the last `Initiate` or `Pcall16`.
+    chip_id: u8,
+    /// State in the field.
+    state: TagState,
+}
+
+impl EmulatedTag {
+    /// Blank tag with `uid`, every bit set: nothing locked, counters full.
+    pub fn new(uid: u64) -> EmulatedTag {
+        EmulatedTag {
+            uid,
+            eeprom: vec![0xFFFF_FFFF; mem::BLOCK_COUNT],
+            system: 0xFFFF_FFFF,
+            chip_id: 0,
+            state: TagState::Ready,
+        }
+    }
+    /// Tag with the memory of `dump`, UID 0 and every system bit set if
+    /// the dump does not have them.
+    pub fn from_dump(dump: &Srix4kDump) -> EmulatedTag {
+        EmulatedTag {
+            eeprom: dump.eeprom.to_vec(),
+            system: dump.system.unwrap_or(0xFFFF_FFFF),
+            ..EmulatedTag::new(dump.uid.unwrap_or(0))
+        }
+    }
+    /// Chip ID drawn at the last `Initiate` or `Pcall16`.
+    pub fn chip_id(&self) -> u8 {
+        self.chip_id
+    }
+    /// State in the field.
+    pub fn state(&self) -> TagState {
+        self.state
+    }
+    /// Answer to `frame`, `None` if the tag stays silent.
+    fn answer(&mut self, frame: &[u8], random: &mut u64) -> Option<Vec<u8>> {
+        use iso14443b2::*;
+
+        let state = self.state;
+        match *frame {
+            [INITIATE, 0x00]
+                if state == TagState::Ready || state == TagState::Inventory =>
+            {
+                self.chip_id = splitmix64(random) as u8;
+                self.state = TagState::Inventory;
+                Some(vec![self.chip_id])
+            }
+            [INITIATE, PCALL16] if state == TagState::Inventory => {
+                self.chip_id = splitmix64(random) as u8;
+                self.in_slot(0)
+            }
+            [marker] if marker & 0x0F == INITIATE && marker >> 4 != 0 => {
+                match state {
+                    TagState::Inventory => self.in_slot(marker >> 4),
+                    _ => None,
+                }
+            }
+            [SELECT, chip_id] => match state {
+                TagState::Inventory | TagState::Deselected
+                    if chip_id == self.chip_id =>
+                {
+                    self.state = TagState::Selected;
+                    Some(vec![chip_id])
+                }
+                TagState::Selected if chip_id != self.chip_id => {
+                    self.state = TagState::Deselected;
+                    None
+                }
+                _ => None,
+            },
+            _ if state != TagState::Selected => None,
+            [READ_BLOCK, address] => self
+                .block(address)
+                .map(|block_data| block_data.to_le_bytes().to_vec()),
+            [WRITE_BLOCK, address, b0, b1, b2, b3] => {
+                self.write(address, u32::from_le_bytes([b0, b1, b2, b3]));
+                None
+            }
+            [GET_UID] => Some(self.uid.to_le_bytes().to_vec()),
+            [RESET_TO_INVENTORY] => {
+                self.state = TagState::Inventory;
+                None
+            }
+            [COMPLETION] => {
+                self.state = TagState::Deactivated;
+                None
+            }
+            _ => None,
+        }
+    }
+    /// Chip ID if the tag answers in `slot` of the anticollision.
+    fn in_slot(&self, slot: u8) -> Option<Vec<u8>> {
+        if self.chip_id & 0x0F == slot {
+            Some(vec![self.chip_id])
+        } else {
+            None
+        }
+    }
+    /// Data of the block at `address`, `None` outside the memory map.
+    fn block(&self, address: u8) -> Option<u32> {
+        if address as usize == mem::SYSTEM_ADDR {
+            Some(self.system)
+        } else {
+            self.eeprom.get(address as usize).cloned()
+        }
+    }
+    /// Write the block at `address`, following the rules of its region.
+    fn write(&mut self, address: u8, block_data: u32) {
+        let i = address as usize;
+        let system = SystemBlock::from(self.system);
+        if i == mem::SYSTEM_ADDR {
+            self.system &= block_data;
+        } else if mem::OTP.contains(&i) {
+            self.eeprom[i] &= block_data;
+        } else if mem::COUNTDOWN.contains(&i) {
+            if block_data < self.eeprom[i] {
+                self.eeprom[i] = block_data;
+            }
+        } else if system.is_locked(address) == Some(true) {
+            trace!("Block {:#04X} is locked, not written", i);
+        } else if let Some(block) = self.eeprom.get_mut(i) {
+            *block = block_data;
+        }
+    }
+}
+
+/// What the reader receives after sending a frame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Answer {
+    /// No tag answered.
+    Silence,
+    /// `Frame(frame)`
+    /// The tags that answered all sent `frame`.
+    Frame(Vec<u8>),
+    /// Tags answered different frames.
+    Collision,
+}
+
+/// Errors of the commands sent to a `Field`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldError {
+    /// No tag answered, none is selected.
+    Silence,
+    /// Several tags answered.
+    Collision,
+    /// `Decode(error)`
+    /// The answer is not the response to the command.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldError::Silence => write!(f, "no tag answered"),
+            FieldError::Collision => write!(f, "more than one tag answered"),
+            FieldError::Decode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+/// Emulated tags in the field of a reader.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Field {
+    /// Tags, in the order they entered the field.
+    tags: Vec<EmulatedTag>,
+    /// State of the generator of the chip IDs.
+    random: u64,
+}
+
+impl Field {
+    /// Empty field, drawing the chip IDs from `seed`.
+    pub fn new(seed: u64) -> Field {
+        Field {
+            tags: Vec::new(),
+            random: seed,
+        }
+    }
+    /// Bring `tag` into the field, in the `Ready` state.
+    pub fn insert(&mut self, mut tag: EmulatedTag) {
+        tag.state = TagState::Ready;
+        self.tags.push(tag);
+    }
+    /// Take the tag with `uid` out of the field.
+    pub fn remove(&mut self, uid: u64) -> Option<EmulatedTag> {
+        let index = self.tags.iter().position(|tag| tag.uid == uid)?;
+        Some(self.tags.remove(index))
+    }
+    /// Tags in the field.
+    pub fn tags(&self) -> &[EmulatedTag] {
+        &self.tags
+    }
+    /// Tag with `uid`, if it is in the field.
+    pub fn tag(&self, uid: u64) -> Option<&EmulatedTag> {
+        self.tags.iter().find(|tag| tag.uid == uid)
+    }
+    /// Send `frame` to every tag and return what the reader receives.
+    pub fn transceive(&mut self, frame: &[u8]) -> Answer {
+        let random = &mut self.random;
+        let answers: Vec<_> = self
+            .tags
+            .iter_mut()
+            .filter_map(|tag| tag.answer(frame, random))
+            .collect();
+        match answers.split_first() {
+            None => Answer::Silence,
+            Some((first, rest)) if rest.iter().all(|other| other == first) => {
+                Answer::Frame(first.clone())
+            }
+            Some(_) => {
+                trace!("{} tags collided", answers.len());
+                Answer::Collision
+            }
+        }
+    }
+}
+
+impl Transport for Field {
+    type Error = FieldError;
+
+    fn execute(
+        &mut self,
+        command: &Command,
+    ) -> Result<Option<Response>, FieldError> {
+        let frame = match self.transceive(&command.frame()) {
+            Answer::Frame(frame) => frame,
+            Answer::Silence => match command {
+                Command::WriteBlock(_, _) => Vec::new(),
+                _ => return Err(FieldError::Silence),
+            },
+            Answer::Collision => return Err(FieldError::Collision),
+        };
+        Response::decode(command, &frame).map_err(FieldError::Decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iso14443b2;
+    use system::LockBits;
+
+    const UIDS: [u64; 3] = [
+        0xD002_0C00_0000_0001,
+        0xD002_0C00_0000_0002,
+        0xD002_0C00_0000_0003,
+    ];
+
+    fn field(uids: &[u64]) -> Field {
+        let mut field = Field::new(42);
+        for &uid in uids {
+            field.insert(EmulatedTag::new(uid));
+        }
+        field
+    }
+
+    /// Select the only tag of `field`, returning its chip ID.
+    fn select_single(field: &mut Field) -> u8 {
+        let chip_id = match field.transceive(&iso14443b2::initiate()) {
+            Answer::Frame(frame) => frame[0],
+            answer => panic!("initiate answered {:?}", answer),
+        };
+        assert_eq!(
+            field.transceive(&iso14443b2::select(chip_id)),
+            Answer::Frame(vec![chip_id])
+        );
+        chip_id
+    }
+
+    /// Chip IDs answered alone in the slots of one `Pcall16` round.
+    fn pcall16_round(field: &mut Field) -> Vec<u8> {
+        let mut found = Vec::new();
+        for slot in 0..16 {
+            let frame = if slot == 0 {
+                iso14443b2::pcall16()
+            } else {
+                iso14443b2::slot_marker(slot)
+            };
+            if let Answer::Frame(frame) = field.transceive(&frame) {
+                found.push(frame[0]);
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn chip_ids_are_deterministic() {
+        let draw = || {
+            let mut field = field(&UIDS);
+            field.transceive(&iso14443b2::initiate());
+            let chip_ids: Vec<u8> =
+                field.tags().iter().map(EmulatedTag::chip_id).collect();
+            chip_ids
+        };
+        assert_eq!(draw(), draw());
+    }
+
+    #[test]
+    fn single_tag_select_and_read() {
+        let mut field = field(&UIDS[..1]);
+        assert_eq!(field.execute(&Command::GetUid), Err(FieldError::Silence));
+        let chip_id = select_single(&mut field);
+        assert_eq!(field.tag(UIDS[0]).unwrap().chip_id(), chip_id);
+        assert_eq!(field.tag(UIDS[0]).unwrap().state(), TagState::Selected);
+        assert_eq!(
+            field.execute(&Command::GetUid),
+            Ok(Some(Response::Uid(UIDS[0])))
+        );
+        assert_eq!(
+            field.execute(&Command::ReadBlock(0x07)),
+            Ok(Some(Response::Block(0xFFFF_FFFF)))
+        );
+        assert_eq!(
+            field.execute(&Command::ReadBlock(0x80)),
+            Err(FieldError::Silence)
+        );
+    }
+
+    #[test]
+    fn anticollision_finds_every_tag() {
+        let mut field = field(&UIDS);
+        assert_eq!(
+            field.transceive(&iso14443b2::initiate()),
+            Answer::Collision
+        );
+        let mut uids = Vec::new();
+        for _ in 0..16 {
+            for chip_id in pcall16_round(&mut field) {
+                field.transceive(&iso14443b2::select(chip_id));
+                match field.execute(&Command::GetUid) {
+                    Ok(Some(Response::Uid(uid))) => uids.push(uid),
+                    answer => panic!("Get_UID answered {:?}", answer),
+                }
+                // Out of the next rounds.
+                field.transceive(&[iso14443b2::COMPLETION]);
+            }
+            if uids.len() == UIDS.len() {
+                break;
+            }
+        }
+        uids.sort_unstable();
+        assert_eq!(uids, UIDS);
+        assert!(field
+            .tags()
+            .iter()
+            .all(|tag| tag.state() == TagState::Deactivated));
+        assert_eq!(field.transceive(&iso14443b2::initiate()), Answer::Silence);
+    }
+
+    #[test]
+    fn select_deselects_the_other_tags() {
+        let mut field = field(&UIDS[..2]);
+        field.transceive(&iso14443b2::initiate());
+        let chip_ids = (0..16)
+            .map(|_| pcall16_round(&mut field))
+            .find(|chip_ids| chip_ids.len() == 2)
+            .expect("both tags answer alone in a round");
+        field.transceive(&iso14443b2::select(chip_ids[0]));
+        field.transceive(&iso14443b2::select(chip_ids[1]));
+        let states: Vec<TagState> =
+            field.tags().iter().map(EmulatedTag::state).collect();
+        assert!(states.contains(&TagState::Deselected));
+        assert!(states.contains(&TagState::Selected));
+        assert!(field.execute(&Command::GetUid).unwrap().is_some());
+    }
+
+    #[test]
+    fn writes_follow_the_regions() {
+        let mut field = field(&UIDS[..1]);
+        select_single(&mut field);
+        let mut write = |address: u8, block_data: u32| {
+            field
+                .execute(&Command::WriteBlock(address, block_data))
+                .unwrap();
+        };
+        // OTP bits are only cleared.
+        write(0x00, 0x0000_FFFF);
+        write(0x00, 0xFFFF_0000);
+        // Counters are only decremented.
+        write(0x05, 0xFFFF_FFF0);
+        write(0x05, 0xFFFF_FFFE);
+        // Locked blocks are not written.
+        let mut system = SystemBlock::from(0xFFFF_FFFF);
+        system.lock(LockBits::for_block(0x08).unwrap());
+        write(mem::SYSTEM_ADDR as u8, system.bits());
+        write(0x08, 0x1234_5678);
+        write(0x10, 0x1234_5678);
+        let tag = field.tag(UIDS[0]).unwrap();
+        assert_eq!(tag.eeprom[0x00], 0);
+        assert_eq!(tag.eeprom[0x05], 0xFFFF_FFF0);
+        assert_eq!(tag.system, system.bits());
+        assert_eq!(tag.eeprom[0x08], 0xFFFF_FFFF);
+        assert_eq!(tag.eeprom[0x10], 0x1234_5678);
+    }
+
+    #[test]
+    fn removed_tags_stop_answering() {
+        let mut field = field(&UIDS[..1]);
+        select_single(&mut field);
+        assert_eq!(field.remove(UIDS[0]).map(|tag| tag.uid), Some(UIDS[0]));
+        assert_eq!(field.remove(UIDS[0]), None);
+        assert_eq!(field.execute(&Command::GetUid), Err(FieldError::Silence));
+    }
+}
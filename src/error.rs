@@ -0,0 +1,270 @@
+//! Errors of the operations on tags, by kind of failure.
+//!
+//! libnfc reports timeouts of the tag, CRC errors and collisions alike as
+//! `RfTransmissionError`. The crate tells them apart itself where it can,
+//! so that each kind is retried the way it can succeed, see
+//! `Srix4k::read_block`.
+
+use std::fmt;
+use std::io;
+
+use codec::DecodeError;
+use Command;
+
+/// Errors that can occur while communicating with a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Srix4kError {
+    /// The tag did not answer in time, it may have left the field.
+    Timeout,
+    /// More than one tag is in the field.
+    Collision,
+    /// `MalformedResponse(error)`
+    /// The answer is not a response to the command, e.g. its length does
+    /// not match. libnfc already drops the frames with a bad CRC.
+    MalformedResponse(DecodeError),
+    /// The reader failed to exchange the frame with the tag.
+    Transmission,
+    /// `TagSwapped(expected, found)`
+    /// Selecting the tag again found another UID than the one of the tag
+    /// connected, which was swapped while out of the field.
+    TagSwapped(u64, u64),
+    /// `Nfc(error)`
+    /// Any other error of the reader.
+    Nfc(nfc1::Error),
+}
+
+/// Result of the operations on tags.
+pub type Srix4kResult<T> = std::result::Result<T, Srix4kError>;
+
+impl From<nfc1::Error> for Srix4kError {
+    fn from(value: nfc1::Error) -> Self {
+        match value {
+            nfc1::Error::Timeout => Srix4kError::Timeout,
+            nfc1::Error::RfTransmissionError => Srix4kError::Transmission,
+            err => Srix4kError::Nfc(err),
+        }
+    }
+}
+
+impl From<DecodeError> for Srix4kError {
+    fn from(value: DecodeError) -> Self {
+        Srix4kError::MalformedResponse(value)
+    }
+}
+
+impl From<Srix4kError> for nfc1::Error {
+    fn from(value: Srix4kError) -> Self {
+        match value {
+            Srix4kError::Timeout => nfc1::Error::Timeout,
+            Srix4kError::Collision
+            | Srix4kError::MalformedResponse(_)
+            | Srix4kError::Transmission => nfc1::Error::RfTransmissionError,
+            Srix4kError::TagSwapped(_, _) => nfc1::Error::TargetReleased,
+            Srix4kError::Nfc(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for Srix4kError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Srix4kError::Timeout => write!(f, "tag did not answer in time"),
+            Srix4kError::Collision => write!(f, "more than one tag in field"),
+            Srix4kError::MalformedResponse(err) => {
+                write!(f, "malformed answer: {}", err)
+            }
+            Srix4kError::Transmission => write!(f, "RF transmission error"),
+            Srix4kError::TagSwapped(expected, found) => write!(
+                f,
+                "tag swapped: expected UID {:016X}, found {:016X}",
+                expected, found
+            ),
+            Srix4kError::Nfc(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Srix4kError {}
+
+impl Srix4kError {
+    /// Kind of the `io::Error` the error converts to.
+    fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            Srix4kError::Timeout => io::ErrorKind::TimedOut,
+            Srix4kError::MalformedResponse(_) => io::ErrorKind::InvalidData,
+            Srix4kError::Collision | Srix4kError::Transmission => {
+                io::ErrorKind::Other
+            }
+            Srix4kError::TagSwapped(_, _) => io::ErrorKind::NotConnected,
+            Srix4kError::Nfc(err) => match err {
+                nfc1::Error::Timeout => io::ErrorKind::TimedOut,
+                nfc1::Error::InvalidArgument => io::ErrorKind::InvalidInput,
+                nfc1::Error::DeviceNotSupported
+                | nfc1::Error::NotImplemented => io::ErrorKind::Unsupported,
+                nfc1::Error::NoDeviceFound | nfc1::Error::NoSuchDeviceFound => {
+                    io::ErrorKind::NotFound
+                }
+                nfc1::Error::TargetReleased => io::ErrorKind::NotConnected,
+                nfc1::Error::OperationAborted => io::ErrorKind::Interrupted,
+                nfc1::Error::BufferOverflow => io::ErrorKind::InvalidData,
+                nfc1::Error::Malloc => io::ErrorKind::OutOfMemory,
+                _ => io::ErrorKind::Other,
+            },
+        }
+    }
+}
+
+impl From<Srix4kError> for io::Error {
+    /// Error of the kind closest to `value`, which `get_ref` returns, for
+    /// adapters to standard IO interfaces.
+    fn from(value: Srix4kError) -> Self {
+        io::Error::new(value.io_kind(), value)
+    }
+}
+
+/// Error of an operation of `Srix4kCached` on a block, with the command
+/// and block involved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedError {
+    /// Command that failed, `None` if the operation failed before sending
+    /// one, e.g. for a block outside the memory map.
+    pub command: Option<Command>,
+    /// Block accessed, `None` for the UID.
+    pub block: Option<usize>,
+    /// What went wrong.
+    pub error: Srix4kError,
+}
+
+/// Result of the operations of `Srix4kCached` on blocks.
+pub type CachedResult<T> = std::result::Result<T, CachedError>;
+
+impl CachedError {
+    /// Block `i` cannot be accessed, e.g. it is outside the memory map,
+    /// reported as `InvalidArgument`.
+    pub(crate) fn invalid_block(i: usize) -> CachedError {
+        CachedError {
+            command: None,
+            block: Some(i),
+            error: Srix4kError::Nfc(nfc1::Error::InvalidArgument),
+        }
+    }
+}
+
+/// Attach the command being sent, and its block, to an error.
+pub(crate) trait Context<T> {
+    fn context(self, command: Command) -> CachedResult<T>;
+}
+
+impl<T, E: Into<Srix4kError>> Context<T> for std::result::Result<T, E> {
+    fn context(self, command: Command) -> CachedResult<T> {
+        self.map_err(|err| CachedError {
+            command: Some(command),
+            block: match command {
+                Command::ReadBlock(address)
+                | Command::WriteBlock(address, _) => Some(address as usize),
+                Command::GetUid => None,
+            },
+            error: err.into(),
+        })
+    }
+}
+
+impl From<CachedError> for Srix4kError {
+    fn from(value: CachedError) -> Self {
+        value.error
+    }
+}
+
+impl From<CachedError> for nfc1::Error {
+    fn from(value: CachedError) -> Self {
+        value.error.into()
+    }
+}
+
+impl From<CachedError> for io::Error {
+    /// Error of the kind of `value.error`, keeping the command and block.
+    fn from(value: CachedError) -> Self {
+        io::Error::new(value.error.io_kind(), value)
+    }
+}
+
+impl fmt::Display for CachedError {
+    /// The operation, then the error, e.g.
+    /// `reading block 0x05: tag did not answer in time`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.command, self.block) {
+            (Some(Command::ReadBlock(address)), _) => {
+                write!(f, "reading block {:#04X}: ", address)?
+            }
+            (Some(Command::WriteBlock(address, block_data)), _) => write!(
+                f,
+                "writing {:#010X} to block {:#04X}: ",
+                block_data, address
+            )?,
+            (Some(Command::GetUid), _) => write!(f, "getting the UID: ")?,
+            (None, Some(i)) => write!(f, "block {}: ", i)?,
+            (None, None) => {}
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for CachedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Failures of a sync that went on after the first one, one per block in
+/// the order they were written, see `Srix4kCached::sync_continue_on_error`
+/// and `Srix4kCached::set_sync_order`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncErrors {
+    /// Failed blocks, with their cause. Never empty.
+    errors: Vec<CachedError>,
+}
+
+impl SyncErrors {
+    /// Failures of `errors`, `None` if there are none.
+    pub(crate) fn new(errors: Vec<CachedError>) -> Option<SyncErrors> {
+        if errors.is_empty() {
+            None
+        } else {
+            Some(SyncErrors { errors })
+        }
+    }
+    /// First failure.
+    pub fn first(&self) -> CachedError {
+        self.errors[0]
+    }
+    /// Failed blocks, with their cause, at least one.
+    pub fn errors(&self) -> &[CachedError] {
+        &self.errors
+    }
+}
+
+impl From<CachedError> for SyncErrors {
+    /// Failure stopping the sync before any block could be written.
+    fn from(value: CachedError) -> Self {
+        SyncErrors {
+            errors: vec![value],
+        }
+    }
+}
+
+impl fmt::Display for SyncErrors {
+    /// Number of failed blocks, then one failure per line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} blocks failed to sync", self.errors.len())?;
+        for err in &self.errors {
+            write!(f, "\n{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SyncErrors {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.errors[0])
+    }
+}
@@ -0,0 +1,128 @@
+//! Test data staged across blocks, for stress tests, benchmarks and the
+//! validation of clone tags.
+//!
+//! `Srix4kCached::fill_pattern` and `Srix4kCached::fill_random` stage the
+//! data of a `Pattern` in the cache, written by `sync`, following the
+//! rules of each region:
+//!
+//! | Region | Staged data |
+//! | --- | --- |
+//! | *EEPROM*, *Lockable EEPROM* | pattern, locked blocks skipped |
+//! | *Resettable OTP bits* | pattern bits cleared, skipped in safe mode |
+//! | *Count down Counter*, *System OTP bits* | skipped |
+//!
+//! Counters and the system block are never filled: their writes use up
+//! the counter or lock blocks for good.
+
+#[cfg(feature = "nfc")]
+use error::{CachedError, CachedResult};
+#[cfg(feature = "nfc")]
+use mem::Region;
+#[cfg(feature = "nfc")]
+use range::BlockSet;
+#[cfg(feature = "nfc")]
+use Srix4kCached;
+
+/// Data of each block of a fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pattern {
+    /// `Constant(block_data)`
+    /// The same data in every block.
+    Constant(u32),
+    /// The address of the block in each of its bytes, e.g. `0x07070707`,
+    /// to spot blocks written at the wrong address.
+    Address,
+    /// One bit set, moving up one bit per block, to spot stuck bits.
+    WalkingOne,
+    /// `0x55555555` and `0xAAAAAAAA` in turn, to spot coupled bits.
+    Checkerboard,
+    /// `Random(seed)`
+    /// Pseudo-random data, the same for the same seed, so a clone can be
+    /// checked against the fill of the original.
+    Random(u64),
+}
+
+impl Pattern {
+    /// Data of the blocks of `addresses`, in order.
+    pub fn block_data<I>(&self, addresses: I) -> Vec<u32>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        let mut state = match *self {
+            Pattern::Random(seed) => seed,
+            _ => 0,
+        };
+        addresses
+            .into_iter()
+            .map(|address| match *self {
+                Pattern::Constant(block_data) => block_data,
+                Pattern::Address => u32::from_le_bytes([address; 4]),
+                Pattern::WalkingOne => 1 << (address % 32),
+                Pattern::Checkerboard if address % 2 == 0 => 0x5555_5555,
+                Pattern::Checkerboard => 0xAAAA_AAAA,
+                Pattern::Random(_) => (splitmix64(&mut state) >> 32) as u32,
+            })
+            .collect()
+    }
+}
+
+/// Next output of the SplitMix64 generator.
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(feature = "nfc")]
+impl Srix4kCached<'_> {
+    /// Stage the data of `pattern` in the EEPROM blocks of `blocks`,
+    /// following the rules of their region, see the `fill` module.
+    /// Returns how many blocks were staged.
+    ///
+    /// Fails with `InvalidArgument` if a block is outside the memory map.
+    pub fn fill_pattern(
+        &mut self,
+        blocks: &BlockSet,
+        pattern: Pattern,
+    ) -> CachedResult<usize> {
+        let system_addr = self.layout().system_addr();
+        let addresses: Vec<u8> = blocks
+            .iter()
+            .map(|block_address| block_address.get())
+            .filter(|&address| address as usize != system_addr)
+            .collect();
+        let block_data = pattern.block_data(addresses.iter().cloned());
+        let mut staged = 0;
+        for (&address, &block_data) in addresses.iter().zip(&block_data) {
+            let i = address as usize;
+            let block_data = match self.layout().region(i) {
+                Some(Region::Generic) => block_data,
+                Some(Region::Lockable) => {
+                    if self.system_block()?.is_locked(address) == Some(true) {
+                        debug!("Block {:#04X} is locked, not filled", i);
+                        continue;
+                    }
+                    block_data
+                }
+                Some(Region::Otp) if self.safe_mode() => continue,
+                Some(Region::Otp) => self.eeprom_get(i)? & block_data,
+                Some(Region::Countdown) | Some(Region::System) => continue,
+                None => return Err(CachedError::invalid_block(i)),
+            };
+            self.eeprom_set(i, block_data)?;
+            staged += 1;
+        }
+        Ok(staged)
+    }
+    /// Stage pseudo-random data from `seed` in the EEPROM blocks of
+    /// `blocks`, see `fill_pattern`.
+    pub fn fill_random(
+        &mut self,
+        blocks: &BlockSet,
+        seed: u64,
+    ) -> CachedResult<usize> {
+        self.fill_pattern(blocks, Pattern::Random(seed))
+    }
+}
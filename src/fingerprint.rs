@@ -0,0 +1,142 @@
+//! Heuristics telling genuine ST SRIX4K chips from clones and emulators.
+//!
+//! None of the checks is conclusive on its own, the score only says how
+//! much the tag behaves like a genuine chip:
+//! - genuine UIDs start with `D0 02`, the ST manufacturer code;
+//! - genuine chips answer in a nearly constant number of cycles,
+//!   software emulators tend to jitter;
+//! - genuine chips ignore reserved block addresses and unknown commands.
+
+use std::fmt;
+
+use nfc1::{Result, Timeout};
+use Srix4k;
+use Uid;
+
+/// Number of timed `GetUID` commands the timing statistics are based on.
+const TIMING_SAMPLES: usize = 8;
+/// Relative standard deviation of the response time above which the tag
+/// is considered jittery.
+const MAX_TIMING_JITTER: f64 = 0.05;
+/// Frames a genuine chip does not answer: `ReadBlock` of the reserved
+/// address 128 and the unused command code `0x01`.
+const RESERVED_FRAMES: [&[u8]; 2] = [&[0x08, 0x80], &[0x01]];
+
+/// Response time statistics, in reader clock cycles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    /// Average response time.
+    pub mean: f64,
+    /// Standard deviation of the response time.
+    pub std_dev: f64,
+}
+
+/// Outcome of the fingerprinting of a tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    /// UID of the tag.
+    pub uid: u64,
+    /// Whether the UID has the `D0 02` prefix of ST chips.
+    pub st_uid: bool,
+    /// Response time of `GetUID`, `None` if the reader cannot measure it.
+    pub timing: Option<Timing>,
+    /// Reserved frames the tag answered, see `RESERVED_FRAMES`.
+    pub reserved_answers: usize,
+    /// Confidence, from 0 to 1, that the tag is a genuine SRIX4K.
+    pub score: f64,
+}
+
+impl Timing {
+    /// Standard deviation relative to the mean.
+    pub fn jitter(&self) -> f64 {
+        if self.mean > 0.0 {
+            self.std_dev / self.mean
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "uid: {:016X} (ST prefix: {})", self.uid, self.st_uid)?;
+        match self.timing {
+            Some(timing) => writeln!(
+                f,
+                "timing: {:.0} cycles, jitter {:.1}%",
+                timing.mean,
+                timing.jitter() * 100.0
+            )?,
+            None => writeln!(f, "timing: unavailable")?,
+        }
+        writeln!(
+            f,
+            "reserved frames answered: {}/{}",
+            self.reserved_answers,
+            RESERVED_FRAMES.len()
+        )?;
+        write!(f, "genuine score: {:.2}", self.score)
+    }
+}
+
+impl Srix4k<'_> {
+    /// Run every heuristic on the tag and score how genuine it looks.
+    pub fn fingerprint(&mut self) -> Result<Fingerprint> {
+        let uid = self.send_get_uid()?;
+        let st_uid = Uid(uid).is_st_manufactured();
+
+        let mut cycles = Vec::with_capacity(TIMING_SAMPLES);
+        for _ in 0..TIMING_SAMPLES {
+            match self.send_get_uid_timed() {
+                Ok((_, sample)) => cycles.push(sample as f64),
+                Err(nfc1::Error::DeviceNotSupported) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        let timing = if cycles.is_empty() {
+            None
+        } else {
+            let mean = cycles.iter().sum::<f64>() / cycles.len() as f64;
+            let variance =
+                cycles.iter().map(|c| (c - mean).powi(2)).sum::<f64>()
+                    / cycles.len() as f64;
+            Some(Timing {
+                mean,
+                std_dev: variance.sqrt(),
+            })
+        };
+
+        let mut reserved_answers = 0;
+        for frame in RESERVED_FRAMES.iter() {
+            let answer = self.device.initiator_transceive_bytes(
+                frame,
+                4,
+                Timeout::Default,
+            );
+            if answer.is_ok_and(|response| !response.is_empty()) {
+                reserved_answers += 1;
+            }
+        }
+        // A tag that answered garbage may need to be selected again.
+        if reserved_answers > 0 {
+            self.select()?;
+        }
+
+        let mut score = 1.0;
+        if !st_uid {
+            score *= 0.2;
+        }
+        if timing.is_some_and(|timing| timing.jitter() > MAX_TIMING_JITTER) {
+            score *= 0.6;
+        }
+        score *= 0.4f64.powi(reserved_answers as i32);
+
+        Ok(Fingerprint {
+            uid,
+            st_uid,
+            timing,
+            reserved_answers,
+            score,
+        })
+    }
+}
@@ -0,0 +1,186 @@
+//! Dump files edited through the same interface as a tag.
+//!
+//! `Srix4kImage` has the get, get mut and sync methods of `Srix4kCached`,
+//! backed by a dump file instead of a tag. Both implement `TagMemory`, so
+//! editing tools can be written once and run against either.
+//!
+//! With the `zstd` feature, zstd archives of dumps are edited too, and
+//! written back compressed.
+
+#[cfg(feature = "zstd")]
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use dump::{self, Srix4kDump};
+#[cfg(feature = "nfc")]
+use error::{CachedError, CachedResult};
+use mem;
+#[cfg(feature = "nfc")]
+use Srix4kCached;
+
+/// Memory of a tag, live or offline, edited in a cache and written back
+/// by `sync`.
+pub trait TagMemory {
+    /// Error of the accesses.
+    type Error;
+
+    /// Get specified block.
+    fn eeprom_get(&mut self, i: usize) -> Result<u32, Self::Error>;
+    /// Get specified block mut.
+    fn eeprom_get_mut(&mut self, i: usize) -> Result<&mut u32, Self::Error>;
+    /// Get the System OTP bits.
+    fn system_get(&mut self) -> Result<u32, Self::Error>;
+    /// Get the System OTP bits mut.
+    fn system_get_mut(&mut self) -> Result<&mut u32, Self::Error>;
+    /// Get the UID.
+    fn uid_get(&mut self) -> Result<u64, Self::Error>;
+    /// Write modified data back and sync the cache.
+    fn sync(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Dump file opened for editing.
+///
+/// To write the modified blocks to the file call the `sync` method.
+pub struct Srix4kImage {
+    /// File the dump was read from.
+    path: PathBuf,
+    /// Dump as last written to the file.
+    original: Srix4kDump,
+    /// Dump with the modifications.
+    dump: Srix4kDump,
+    /// Whether the file is a zstd archive of the dump.
+    #[cfg(feature = "zstd")]
+    compressed: bool,
+}
+
+impl Srix4kImage {
+    /// Read the EEPROM or full dump file at `path`, or its zstd archive.
+    ///
+    /// Fails with `InvalidData` if the file is not a dump.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Srix4kImage> {
+        let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+        let bytes = fs::read(path.as_ref())?;
+        let content = dump::decompress(&bytes).map_err(invalid)?;
+        let dump = Srix4kDump::parse(&content).map_err(invalid)?;
+        Ok(Srix4kImage {
+            path: path.as_ref().to_path_buf(),
+            original: dump.clone(),
+            dump,
+            #[cfg(feature = "zstd")]
+            compressed: matches!(content, Cow::Owned(_)),
+        })
+    }
+    /// File the dump is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Dump with the modifications.
+    pub fn dump(&self) -> &Srix4kDump {
+        &self.dump
+    }
+    /// Get specified block.
+    ///
+    /// Fails with `InvalidInput` if the block is outside the EEPROM.
+    pub fn eeprom_get(&mut self, i: usize) -> io::Result<u32> {
+        self.eeprom_get_mut(i).map(|block_data| *block_data)
+    }
+    /// Get specified block mut.
+    ///
+    /// Fails with `InvalidInput` if the block is outside the EEPROM.
+    pub fn eeprom_get_mut(&mut self, i: usize) -> io::Result<&mut u32> {
+        self.dump.eeprom.get_mut(i).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("block {} outside the {} blocks", i, mem::BLOCK_COUNT),
+            )
+        })
+    }
+    /// Get the System OTP bits.
+    ///
+    /// Fails with `NotFound` if the file only contains the EEPROM.
+    pub fn system_get(&mut self) -> io::Result<u32> {
+        self.system_get_mut().map(|system| *system)
+    }
+    /// Get the System OTP bits mut.
+    ///
+    /// Fails with `NotFound` if the file only contains the EEPROM.
+    pub fn system_get_mut(&mut self) -> io::Result<&mut u32> {
+        self.dump.system.as_mut().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "dump has no system block")
+        })
+    }
+    /// Get the UID.
+    ///
+    /// Fails with `NotFound` if the file only contains the EEPROM.
+    pub fn uid_get(&mut self) -> io::Result<u64> {
+        self.dump.uid.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "dump has no UID")
+        })
+    }
+    /// Write the dump to the file if it was modified.
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.dump != self.original {
+            debug!("Writing dump to {}", self.path.display());
+            fs::write(&self.path, self.file_bytes()?)?;
+            self.original = self.dump.clone();
+        }
+        Ok(())
+    }
+    /// Bytes of the file of the dump, compressed if it was.
+    fn file_bytes(&self) -> io::Result<Vec<u8>> {
+        #[cfg(feature = "zstd")]
+        if self.compressed {
+            return self.dump.serialize_compressed(0);
+        }
+        Ok(self.dump.serialize())
+    }
+}
+
+impl TagMemory for Srix4kImage {
+    type Error = io::Error;
+
+    fn eeprom_get(&mut self, i: usize) -> io::Result<u32> {
+        self.eeprom_get(i)
+    }
+    fn eeprom_get_mut(&mut self, i: usize) -> io::Result<&mut u32> {
+        self.eeprom_get_mut(i)
+    }
+    fn system_get(&mut self) -> io::Result<u32> {
+        self.system_get()
+    }
+    fn system_get_mut(&mut self) -> io::Result<&mut u32> {
+        self.system_get_mut()
+    }
+    fn uid_get(&mut self) -> io::Result<u64> {
+        self.uid_get()
+    }
+    fn sync(&mut self) -> io::Result<()> {
+        self.sync()
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl TagMemory for Srix4kCached<'_> {
+    type Error = CachedError;
+
+    fn eeprom_get(&mut self, i: usize) -> CachedResult<u32> {
+        self.eeprom_get(i)
+    }
+    fn eeprom_get_mut(&mut self, i: usize) -> CachedResult<&mut u32> {
+        self.eeprom_get_mut(i)
+    }
+    fn system_get(&mut self) -> CachedResult<u32> {
+        self.system_get()
+    }
+    fn system_get_mut(&mut self) -> CachedResult<&mut u32> {
+        self.system_get_mut()
+    }
+    fn uid_get(&mut self) -> CachedResult<u64> {
+        self.uid_get()
+    }
+    fn sync(&mut self) -> CachedResult<()> {
+        self.sync()
+    }
+}
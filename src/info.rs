@@ -0,0 +1,180 @@
+//! Decoded information about a tag: the fields of its UID, its chip, the
+//! locked blocks, the counters and the use of the OTP blocks.
+//!
+//! `TagInfo` is built from a dump or from the cache of a connected tag,
+//! and serialized with the `serde` feature, so services can return it as
+//! is. Its `Display` is the text printed for users.
+
+use std::fmt;
+
+use counter::Counter;
+use dump::Srix4kDump;
+#[cfg(feature = "nfc")]
+use error::CachedResult;
+use mem;
+use system::SystemBlock;
+#[cfg(feature = "nfc")]
+use Srix4kCached;
+use Uid;
+
+/// Decoded counter block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CounterInfo {
+    /// Address of the block.
+    pub address: u8,
+    /// Raw block data.
+    pub bits: u32,
+    /// Value of the binary counter, see `Counter::count`.
+    pub count: u32,
+    /// Reload bits still set, see `Counter::reloads`.
+    pub reloads: u32,
+    /// Decrements left, see `Counter::remaining`.
+    pub remaining: u64,
+}
+
+/// Decoded information about a tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TagInfo {
+    /// UID of the tag.
+    pub uid: u64,
+    /// Manufacturer code of the UID, `0x02` for STMicroelectronics.
+    pub manufacturer: u8,
+    /// Whether the UID is the one of a chip manufactured by ST.
+    pub st_manufactured: bool,
+    /// Product code of the UID.
+    pub chip_code: u8,
+    /// Name of the chip, `None` if the product code is not known.
+    pub chip: Option<&'static str>,
+    /// Serial number of the UID.
+    pub serial: u64,
+    /// Fixed chip ID of the system block, if known.
+    pub chip_id: Option<u8>,
+    /// Locked blocks of the *Lockable EEPROM*, `None` if the system block
+    /// is not known.
+    pub locked_blocks: Option<Vec<u8>>,
+    /// *Count down Counter* blocks.
+    pub counters: Vec<CounterInfo>,
+    /// *Resettable OTP bits* blocks with bits cleared.
+    pub otp_used: usize,
+    /// *Resettable OTP bits* blocks.
+    pub otp_blocks: usize,
+}
+
+impl TagInfo {
+    /// Information about the tag with `uid`, `system` block and `eeprom`
+    /// blocks, which must hold at least the OTP and counter blocks of the
+    /// memory map of the chip.
+    pub fn new(uid: u64, system: Option<u32>, eeprom: &[u32]) -> TagInfo {
+        let layout = mem::layout_of(uid).unwrap_or(&mem::Srix4kLayout);
+        let system = system.map(SystemBlock::from);
+        let counters = layout
+            .countdown()
+            .filter_map(|i| {
+                let counter = Counter::from(*eeprom.get(i)?);
+                Some(CounterInfo {
+                    address: i as u8,
+                    bits: counter.bits(),
+                    count: counter.count(),
+                    reloads: counter.reloads(),
+                    remaining: counter.remaining(),
+                })
+            })
+            .collect();
+        let otp_used = layout
+            .otp()
+            .filter_map(|i| eeprom.get(i))
+            .filter(|&&block_data| block_data != u32::MAX)
+            .count();
+        TagInfo {
+            uid,
+            manufacturer: Uid(uid).manufacturer(),
+            st_manufactured: Uid(uid).is_st_manufactured(),
+            chip_code: Uid(uid).chip_code(),
+            chip: mem::layout_of(uid).map(|layout| layout.name()),
+            serial: Uid(uid).serial(),
+            chip_id: system.map(|system| system.chip_id()),
+            locked_blocks: system.map(|system| {
+                layout
+                    .lockable()
+                    .map(|i| i as u8)
+                    .filter(|&i| system.is_locked(i) == Some(true))
+                    .collect()
+            }),
+            counters,
+            otp_used,
+            otp_blocks: layout.otp().len(),
+        }
+    }
+    /// Information about the tag of `dump`, `None` if it has no UID.
+    pub fn from_dump(dump: &Srix4kDump) -> Option<TagInfo> {
+        Some(TagInfo::new(dump.uid?, dump.system, &dump.eeprom))
+    }
+}
+
+impl fmt::Display for TagInfo {
+    /// One line per field, e.g. `Chip: SRIX4K (product code 0x03)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "UID: {}", Uid(self.uid))?;
+        writeln!(
+            f,
+            "Manufacturer: {:#04X}{}",
+            self.manufacturer,
+            if self.st_manufactured {
+                " (STMicroelectronics)"
+            } else {
+                ""
+            }
+        )?;
+        writeln!(
+            f,
+            "Chip: {} (product code {:#04X})",
+            self.chip.unwrap_or("unknown"),
+            self.chip_code
+        )?;
+        writeln!(f, "Serial: {:011X}", self.serial)?;
+        if let Some(chip_id) = self.chip_id {
+            writeln!(f, "Chip ID: {:#04X}", chip_id)?;
+        }
+        match &self.locked_blocks {
+            Some(locked) if locked.is_empty() => {
+                writeln!(f, "Locked blocks: none")?
+            }
+            Some(locked) => {
+                write!(f, "Locked blocks:")?;
+                for block_address in locked {
+                    write!(f, " {:#04X}", block_address)?;
+                }
+                writeln!(f)?;
+            }
+            None => {}
+        }
+        for counter in &self.counters {
+            writeln!(
+                f,
+                "Counter {:#04X}: {} ({} reloads, {} decrements left)",
+                counter.address,
+                counter.count,
+                counter.reloads,
+                counter.remaining
+            )?;
+        }
+        writeln!(f, "OTP blocks used: {}/{}", self.otp_used, self.otp_blocks)
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl Srix4kCached<'_> {
+    /// Information about the tag, reading the UID, the system block and
+    /// the OTP and counter blocks not cached.
+    pub fn info(&mut self) -> CachedResult<TagInfo> {
+        let uid = self.uid_get()?;
+        let system = self.system_get()?;
+        let end = self.layout.otp().end.max(self.layout.countdown().end);
+        let eeprom = (0..end)
+            .map(|i| self.eeprom_get(i))
+            .collect::<CachedResult<Vec<u32>>>()?;
+        Ok(TagInfo::new(uid, Some(system), &eeprom))
+    }
+}
@@ -0,0 +1,110 @@
+//! Counters of the frames exchanged with the tag, enabled by the
+//! `instrument` feature, for performance work and regression tracking.
+//!
+//! Unlike `metrics`, which counts operations of a reader station, these
+//! count what goes over the air: every frame `Srix4k` sends and receives,
+//! the retries of `Srix4k::read_block` and the writes to each region.
+
+use std::fmt;
+
+use mem::{self, MemoryLayout, Region};
+
+/// Frames, bytes, retries and writes since the tag was connected or the
+/// counters were reset, see `Srix4k::instrumentation`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Instrumentation {
+    /// Command frames sent to the tag.
+    pub frames_sent: u64,
+    /// Response frames received from the tag.
+    pub frames_received: u64,
+    /// Bytes of the command frames.
+    pub bytes_sent: u64,
+    /// Bytes of the response frames.
+    pub bytes_received: u64,
+    /// `ReadBlock` commands sent again after a failed attempt.
+    pub retries: u64,
+    /// `WriteBlock` commands sent, per region.
+    pub writes: RegionWrites,
+}
+
+/// `WriteBlock` commands sent to the blocks of each region.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RegionWrites {
+    /// *Resettable OTP bits*.
+    pub otp: u64,
+    /// *Count down Counter*.
+    pub countdown: u64,
+    /// *Lockable EEPROM*.
+    pub lockable: u64,
+    /// *EEPROM*.
+    pub generic: u64,
+    /// *System OTP bits*.
+    pub system: u64,
+}
+
+impl RegionWrites {
+    /// Writes to the blocks of `region`.
+    pub fn get(&self, region: Region) -> u64 {
+        match region {
+            Region::Otp => self.otp,
+            Region::Countdown => self.countdown,
+            Region::Lockable => self.lockable,
+            Region::Generic => self.generic,
+            Region::System => self.system,
+        }
+    }
+    /// Writes to every region.
+    pub fn total(&self) -> u64 {
+        self.otp + self.countdown + self.lockable + self.generic + self.system
+    }
+}
+
+impl Instrumentation {
+    /// Count a command frame sent.
+    pub(crate) fn sent(&mut self, frame: &[u8]) {
+        self.frames_sent += 1;
+        self.bytes_sent += frame.len() as u64;
+    }
+    /// Count a response frame received.
+    pub(crate) fn received(&mut self, frame: &[u8]) {
+        self.frames_received += 1;
+        self.bytes_received += frame.len() as u64;
+    }
+    /// Count a command sent again.
+    pub(crate) fn retry(&mut self) {
+        self.retries += 1;
+    }
+    /// Count a write to the block, in the region of the SRIX4K map.
+    pub(crate) fn written(&mut self, block_address: u8) {
+        let writes = &mut self.writes;
+        let count = match mem::Srix4kLayout.region(block_address as usize) {
+            Some(Region::Otp) => &mut writes.otp,
+            Some(Region::Countdown) => &mut writes.countdown,
+            Some(Region::Lockable) => &mut writes.lockable,
+            Some(Region::Generic) => &mut writes.generic,
+            Some(Region::System) => &mut writes.system,
+            None => return,
+        };
+        *count += 1;
+    }
+}
+
+impl fmt::Display for Instrumentation {
+    /// One line per counter, e.g. `frames sent: 12`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "frames sent: {}", self.frames_sent)?;
+        writeln!(f, "frames received: {}", self.frames_received)?;
+        writeln!(f, "bytes sent: {}", self.bytes_sent)?;
+        writeln!(f, "bytes received: {}", self.bytes_received)?;
+        writeln!(f, "retries: {}", self.retries)?;
+        writeln!(
+            f,
+            "writes: {} otp, {} countdown, {} lockable, {} generic, {} system",
+            self.writes.otp,
+            self.writes.countdown,
+            self.writes.lockable,
+            self.writes.generic,
+            self.writes.system
+        )
+    }
+}
@@ -0,0 +1,183 @@
+//! Inventory of a fleet of tags from their dumps, exported to CSV for
+//! spreadsheets.
+//!
+//! The tag database is a directory of dump files, see `default_dir`.
+//! `Inventory::scan` reads its full dump files, the only ones with a UID,
+//! and keeps one row per tag: when it was first and last seen, from the
+//! modification times of the files, and its counters in the latest dump.
+//! `srix4k db export --csv` prints the CSV:
+//!
+//! ```text
+//! uid,alias,chip,first_seen,last_seen,counter_5,counter_6
+//! D0020C0012345678,gym-pass-blue,SRIX4K,2023-11-14T22:13:20Z,2023-11-15T22:13:20Z,1000,0
+//! ```
+//!
+//! Times are UTC, counters the value of the binary counter, see
+//! `Counter::count`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use alias::Aliases;
+use counter::Counter;
+use dump::Srix4kDump;
+use mem;
+use Uid;
+
+/// Environment variable overriding the directory of the tag database.
+pub const DIR_VAR: &str = "SRIX4K_DB";
+
+/// Tag database of the user: `$SRIX4K_DB`, else `srix4k/db` in
+/// `$XDG_DATA_HOME` or `$HOME/.local/share`. `None` if none is set.
+pub fn default_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(DIR_VAR) {
+        return Some(PathBuf::from(dir));
+    }
+    let data = match env::var_os("XDG_DATA_HOME") {
+        Some(data) => PathBuf::from(data),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data.join("srix4k").join("db"))
+}
+
+/// Row of a tag in the inventory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TagRecord {
+    /// UID of the tag.
+    pub uid: u64,
+    /// Name of the chip, from the product code of the UID.
+    pub chip: Option<&'static str>,
+    /// When the oldest dump was taken.
+    pub first_seen: SystemTime,
+    /// When the latest dump was taken.
+    pub last_seen: SystemTime,
+    /// Dumps recorded.
+    pub dumps: usize,
+    /// *Count down Counter* blocks of the latest dump.
+    pub counters: [Counter; 2],
+}
+
+/// Tags known from their dumps, by UID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inventory(BTreeMap<u64, TagRecord>);
+
+impl Inventory {
+    /// Inventory of the full dump files in `dir`, dated by their
+    /// modification time. Other files are skipped.
+    pub fn scan<P: AsRef<Path>>(dir: P) -> io::Result<Inventory> {
+        let mut inventory = Inventory::default();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let dump = match Srix4kDump::parse(&fs::read(entry.path())?) {
+                Ok(dump) => dump,
+                Err(err) => {
+                    debug!("Skipping {}: {}", entry.path().display(), err);
+                    continue;
+                }
+            };
+            inventory.record(&dump, entry.metadata()?.modified()?);
+        }
+        Ok(inventory)
+    }
+    /// Record `dump`, taken at `seen`. Returns whether it has a UID, which
+    /// dumps without are not recorded.
+    pub fn record(&mut self, dump: &Srix4kDump, seen: SystemTime) -> bool {
+        let uid = match dump.uid {
+            Some(uid) => uid,
+            None => return false,
+        };
+        let counters = [
+            Counter::from(dump.eeprom[mem::COUNTDOWN.start]),
+            Counter::from(dump.eeprom[mem::COUNTDOWN.start + 1]),
+        ];
+        let record = self.0.entry(uid).or_insert(TagRecord {
+            uid,
+            chip: mem::layout_of(uid).map(|layout| layout.name()),
+            first_seen: seen,
+            last_seen: seen,
+            dumps: 0,
+            counters,
+        });
+        record.dumps += 1;
+        record.first_seen = record.first_seen.min(seen);
+        if seen >= record.last_seen {
+            record.last_seen = seen;
+            record.counters = counters;
+        }
+        true
+    }
+    /// Tags, in UID order.
+    pub fn tags(&self) -> impl Iterator<Item = &TagRecord> {
+        self.0.values()
+    }
+    /// CSV of the tags, with header, see the `inventory` module.
+    pub fn to_csv(&self, aliases: &Aliases) -> String {
+        let mut csv = String::from(
+            "uid,alias,chip,first_seen,last_seen,counter_5,counter_6\n",
+        );
+        for tag in self.tags() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                Uid(tag.uid),
+                csv_field(aliases.get(tag.uid).unwrap_or("")),
+                tag.chip.unwrap_or(""),
+                utc_time(tag.first_seen),
+                utc_time(tag.last_seen),
+                tag.counters[0].count(),
+                tag.counters[1].count()
+            ));
+        }
+        csv
+    }
+}
+
+/// `field` quoted if it holds a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// `time` in UTC, to the second, e.g. `2023-11-14T22:13:20Z`. Times
+/// before the Unix epoch are the epoch.
+fn utc_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+    // Civil date of the day, counted in eras of 400 years from 0000-03-01.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
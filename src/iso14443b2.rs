@@ -0,0 +1,168 @@
+//! ISO14443B-2 short range protocol of the ST SRI family and of the other
+//! ST contactless memories answering the same commands (SRIX4K, SRI512,
+//! SRI2K, SRT512, ST25TB).
+//!
+//! Frames are written least significant byte first and do not include
+//! the CRC, which readers compute themselves; `crc_b` is provided for
+//! those that do not. The tag answers `Initiate` and `Pcall16` with its
+//! chip ID, is selected with `Select(chip_id)` and deactivated with
+//! `Completion`:
+//!
+//! | Command | Frame | Answer |
+//! | --- | --- | --- |
+//! | `Initiate` | `06 00` | chip ID |
+//! | `Pcall16` | `06 04` | chip ID |
+//! | `Slot_marker(n)` | `n6` | chip ID |
+//! | `Select(chip_id)` | `0E id` | chip ID |
+//! | `Read_block(addr)` | `08 addr` | 4 bytes |
+//! | `Write_block(addr, data)` | `09 addr data` | none |
+//! | `Get_UID` | `0B` | 8 bytes |
+//! | `Reset_to_inventory` | `0C` | none |
+//! | `Completion` | `0F` | none |
+
+use std::time::Duration;
+
+/// Frequency of the reader carrier.
+pub const CARRIER_FREQUENCY_HZ: u32 = 13_560_000;
+/// Carrier cycles per elementary time unit at 106 kbit/s.
+pub const ETU_CYCLES: u32 = 128;
+/// Time the tag needs to program a block after `Write_block`, during
+/// which it does not answer.
+pub const WRITE_TIME: Duration = Duration::from_millis(5);
+
+/// `Initiate` and `Pcall16` code, followed by their parameter byte.
+pub const INITIATE: u8 = 0x06;
+/// Parameter byte of `Pcall16`.
+pub const PCALL16: u8 = 0x04;
+/// `Read_block` code.
+pub const READ_BLOCK: u8 = 0x08;
+/// `Write_block` code.
+pub const WRITE_BLOCK: u8 = 0x09;
+/// `Get_UID` code.
+pub const GET_UID: u8 = 0x0B;
+/// `Reset_to_inventory` code.
+pub const RESET_TO_INVENTORY: u8 = 0x0C;
+/// `Select` code.
+pub const SELECT: u8 = 0x0E;
+/// `Completion` code.
+pub const COMPLETION: u8 = 0x0F;
+
+/// `Initiate` frame, answered by every tag in the field.
+pub fn initiate() -> Vec<u8> {
+    vec![INITIATE, 0x00]
+}
+
+/// `Pcall16` frame, starting the anticollision over 16 slots.
+pub fn pcall16() -> Vec<u8> {
+    vec![INITIATE, PCALL16]
+}
+
+/// `Slot_marker` frame of slot 1 to 15.
+pub fn slot_marker(slot: u8) -> Vec<u8> {
+    vec![(slot & 0x0F) << 4 | INITIATE]
+}
+
+/// `Select` frame of the tag with `chip_id`.
+pub fn select(chip_id: u8) -> Vec<u8> {
+    vec![SELECT, chip_id]
+}
+
+/// `Read_block` frame.
+pub fn read_block(block_address: u8) -> Vec<u8> {
+    vec![READ_BLOCK, block_address]
+}
+
+/// `Write_block` frame.
+pub fn write_block(block_address: u8, block_data: u32) -> Vec<u8> {
+    let mut frame = vec![WRITE_BLOCK, block_address];
+    frame.extend(block_data.to_le_bytes());
+    frame
+}
+
+/// `Get_UID` frame.
+pub fn get_uid() -> Vec<u8> {
+    vec![GET_UID]
+}
+
+/// `Reset_to_inventory` frame.
+pub fn reset_to_inventory() -> Vec<u8> {
+    vec![RESET_TO_INVENTORY]
+}
+
+/// `Completion` frame, deactivating the tag until it leaves the field.
+pub fn completion() -> Vec<u8> {
+    vec![COMPLETION]
+}
+
+/// CRC_B of ISO/IEC 14443-3, sent least significant byte first after
+/// the frame.
+pub fn crc_b(frame: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in frame {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// `frame` followed by its CRC_B.
+pub fn append_crc(frame: &[u8]) -> Vec<u8> {
+    let mut framed = frame.to_vec();
+    framed.extend(crc_b(frame).to_le_bytes());
+    framed
+}
+
+/// Duration of `cycles` carrier cycles, e.g. a response time measured by
+/// libnfc.
+pub fn cycles_to_duration(cycles: u32) -> Duration {
+    Duration::from_nanos(
+        cycles as u64 * 1_000_000_000 / CARRIER_FREQUENCY_HZ as u64,
+    )
+}
+
+/// Duration of `etus` elementary time units at 106 kbit/s, e.g. a frame
+/// waiting time.
+pub fn etus_to_duration(etus: u32) -> Duration {
+    cycles_to_duration(etus.saturating_mul(ETU_CYCLES))
+}
+
+/// Select the tag in the field of `device`: poll ISO14443B targets, then
+/// select the ISO14443B-2 one.
+#[cfg(feature = "nfc")]
+pub fn select_target(device: &mut nfc1::Device) -> nfc1::Result<()> {
+    device.initiator_list_passive_targets(
+        &nfc1::Modulation {
+            modulation_type: nfc1::ModulationType::Iso14443b,
+            baud_rate: nfc1::BaudRate::Baud106,
+        },
+        1,
+    )?;
+    device.initiator_select_passive_target(&nfc1::Modulation {
+        modulation_type: nfc1::ModulationType::Iso14443b2sr,
+        baud_rate: nfc1::BaudRate::Baud106,
+    })?;
+    Ok(())
+}
+
+/// Number of ISO14443B targets in the field of `device`, up to 2.
+///
+/// Polling deselects the current target, select it again afterwards.
+#[cfg(feature = "nfc")]
+pub fn count_targets(device: &mut nfc1::Device) -> nfc1::Result<usize> {
+    let modulation = nfc1::Modulation {
+        modulation_type: nfc1::ModulationType::Iso14443b,
+        baud_rate: nfc1::BaudRate::Baud106,
+    };
+    let targets = device.initiator_list_passive_targets(&modulation, 2)?;
+    // The list is always full, padded with ISO14443A placeholders.
+    Ok(targets
+        .iter()
+        .filter(|target| target.modulation == modulation)
+        .count())
+}
@@ -0,0 +1,181 @@
+//! Journal of the writes of a sync, for crash consistency.
+//!
+//! Once enabled with `Srix4kCached::set_journal`, `sync` writes the
+//! planned writes to the journal file and flushes it to disk before
+//! writing to the tag, then appends each block once written. The file is
+//! removed when the sync returns, even if writes failed: those blocks stay
+//! modified in the cache. A journal left behind by a crash or a power
+//! loss lists the blocks whose write may have been torn, see `pending`,
+//! which `Srix4k::check_journal` reads back.
+//!
+//! One entry per line:
+//!
+//! ```text
+//! plan 0x10 0xFFFFFFFF 0xDEADBEEF
+//! done 0x10
+//! ```
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "nfc")]
+use nfc1::Result;
+#[cfg(feature = "nfc")]
+use Srix4k;
+
+/// Write planned by a sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JournalEntry {
+    /// From 0 to 127, or 255 for system.
+    pub address: u8,
+    /// Block data before the write.
+    pub old: u32,
+    /// Block data written.
+    pub new: u32,
+}
+
+/// Data of a journaled block read back after an interrupted sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockState {
+    /// The write did not happen.
+    Old,
+    /// The write completed.
+    New,
+    /// `Torn(block_data)`
+    /// Neither, the write was interrupted.
+    Torn(u32),
+}
+
+/// Journal of a sync in progress.
+#[derive(Debug)]
+pub struct Journal {
+    /// File of the journal.
+    path: PathBuf,
+    /// File, open for appending.
+    file: File,
+}
+
+impl Journal {
+    /// Create the journal at `path` with the planned writes, flushed to
+    /// disk.
+    ///
+    /// Fails with `AlreadyExists` if the journal of an interrupted sync is
+    /// there, so it is not lost: check it, then `discard` it.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        entries: &[JournalEntry],
+    ) -> io::Result<Journal> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path.as_ref())?;
+        let mut plan = String::new();
+        for entry in entries {
+            plan.push_str(&format!(
+                "plan {:#04X} {:#010X} {:#010X}\n",
+                entry.address, entry.old, entry.new
+            ));
+        }
+        file.write_all(plan.as_bytes())?;
+        file.sync_all()?;
+        Ok(Journal {
+            path: path.as_ref().to_path_buf(),
+            file,
+        })
+    }
+    /// Record that the block was written, flushed to disk.
+    pub fn complete(&mut self, address: u8) -> io::Result<()> {
+        writeln!(self.file, "done {:#04X}", address)?;
+        self.file.sync_data()
+    }
+    /// Remove the journal of a completed sync.
+    pub fn finish(self) -> io::Result<()> {
+        fs::remove_file(&self.path)
+    }
+}
+
+/// Planned writes of the journal at `path` that were not recorded as
+/// done, `None` if there is no journal, i.e. the last sync completed.
+///
+/// A last line without newline, torn by the crash, is ignored. Fails with
+/// `InvalidData` if another line is not an entry.
+pub fn pending<P: AsRef<Path>>(
+    path: P,
+) -> io::Result<Option<Vec<JournalEntry>>> {
+    let journal = match fs::read_to_string(path) {
+        Ok(journal) => journal,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(None)
+        }
+        Err(err) => return Err(err),
+    };
+    let complete = match journal.rfind('\n') {
+        Some(end) => &journal[..end],
+        None => "",
+    };
+    let mut entries = Vec::new();
+    for line in complete.lines() {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid journal entry `{}`", line),
+            )
+        };
+        let fields: Vec<_> = line.split(' ').collect();
+        match fields[..] {
+            ["plan", address, old, new] => entries.push(JournalEntry {
+                address: parse_hex(address).ok_or_else(invalid)? as u8,
+                old: parse_hex(old).ok_or_else(invalid)?,
+                new: parse_hex(new).ok_or_else(invalid)?,
+            }),
+            ["done", address] => {
+                let address = parse_hex(address).ok_or_else(invalid)?;
+                entries.retain(|entry| entry.address as u32 != address);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(Some(entries))
+}
+
+/// Remove the journal at `path`, once its pending writes are dealt with.
+/// Succeeds if there is none.
+pub fn discard<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Value of `0x`-prefixed hexadecimal `digits`, as written in the
+/// journal and the `persist` files.
+pub(crate) fn parse_hex(digits: &str) -> Option<u32> {
+    let digits = digits.strip_prefix("0x")?;
+    u32::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(feature = "nfc")]
+impl Srix4k<'_> {
+    /// Read back the blocks of the pending writes of a journal, to tell
+    /// which ones were torn.
+    pub fn check_journal(
+        &mut self,
+        entries: &[JournalEntry],
+    ) -> Result<Vec<(JournalEntry, BlockState)>> {
+        entries
+            .iter()
+            .map(|&entry| {
+                let block_data = self.read_block(entry.address)?;
+                let state = if block_data == entry.new {
+                    BlockState::New
+                } else if block_data == entry.old {
+                    BlockState::Old
+                } else {
+                    BlockState::Torn(block_data)
+                };
+                Ok((entry, state))
+            })
+            .collect()
+    }
+}
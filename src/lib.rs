@@ -1,10 +1,196 @@
+#[cfg(feature = "dbus")]
+extern crate dbus;
+#[cfg(feature = "defmt")]
+extern crate defmt;
+#[cfg(feature = "ed25519")]
+extern crate ed25519_dalek;
+#[cfg(feature = "log")]
+#[macro_use]
 extern crate log;
+#[cfg(feature = "node")]
+extern crate napi;
+#[cfg(feature = "node")]
+extern crate napi_derive;
+#[cfg(feature = "nfc")]
 extern crate nfc1;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "schema-yaml")]
+extern crate serde_yaml;
+#[cfg(feature = "schema-toml")]
+extern crate toml;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
-use std::convert::TryInto;
-use log::{debug, info, trace};
+/// No-op replacements of the `log` macros, still type checking their
+/// arguments, when the `log` feature is disabled.
+#[cfg(not(feature = "log"))]
+#[macro_use]
+mod log {
+    #![allow(unused_macros)]
+
+    macro_rules! trace {
+        ($($arg:tt)+) => {{ let _ = format_args!($($arg)+); }};
+    }
+    macro_rules! debug {
+        ($($arg:tt)+) => {{ let _ = format_args!($($arg)+); }};
+    }
+    macro_rules! info {
+        (target: $target:expr, $($arg:tt)+) => {{
+            let _ = ($target, format_args!($($arg)+));
+        }};
+        ($($arg:tt)+) => {{ let _ = format_args!($($arg)+); }};
+    }
+    macro_rules! warn {
+        ($($arg:tt)+) => {{ let _ = format_args!($($arg)+); }};
+    }
+    macro_rules! error {
+        ($($arg:tt)+) => {{ let _ = format_args!($($arg)+); }};
+    }
+}
+
+/// `trace!` for the commands sent in loops: asks the logger first whether
+/// it keeps trace records, so that loggers filtering them only after
+/// building the record, or formatting it, cost a single check.
+#[cfg(all(feature = "nfc", feature = "log"))]
+macro_rules! hot_trace {
+    ($($arg:tt)+) => {
+        if log_enabled!(::log::Level::Trace) {
+            trace!($($arg)+);
+        }
+    };
+}
+#[cfg(all(feature = "nfc", not(feature = "log")))]
+macro_rules! hot_trace {
+    ($($arg:tt)+) => { trace!($($arg)+) };
+}
+
+#[cfg(feature = "nfc")]
+use std::cmp::Ordering;
+#[cfg(feature = "nfc")]
+use std::thread;
+#[cfg(feature = "nfc")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "nfc")]
+use error::{
+    CachedError, CachedResult, Context, Srix4kError, Srix4kResult, SyncErrors,
+};
+#[cfg(feature = "nfc")]
+use journal::JournalEntry;
+#[cfg(feature = "nfc")]
 use nfc1::{Result, Timeout};
 
+/// Run `$body`, a block evaluating to a `Result`, in a `tracing` span with
+/// the fields, recording its duration and error. The error is
+/// `nfc1::Error` unless given before the block.
+/// `?` in the block returns from the block, not from the function.
+#[cfg(feature = "nfc")]
+macro_rules! traced {
+    ($name:expr, { $($field:ident = $value:expr),* }, $body:block) => {
+        traced!($name, { $($field = $value),* }, nfc1::Error, $body)
+    };
+    (
+        $name:expr,
+        { $($field:ident = $value:expr),* },
+        $error:ty,
+        $body:block
+    ) => {{
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::debug_span!(
+                $name,
+                $($field = $value,)*
+                duration_us = tracing::field::Empty,
+                error = tracing::field::Empty,
+            );
+            let _entered = span.enter();
+            let start = std::time::Instant::now();
+            let result =
+                (|| -> std::result::Result<_, $error> { $body })();
+            span.record("duration_us", start.elapsed().as_micros() as u64);
+            if let Err(ref err) = result {
+                span.record("error", tracing::field::display(err));
+            }
+            result
+        }
+        #[cfg(not(feature = "tracing"))]
+        (|| -> std::result::Result<_, $error> { $body })()
+    }};
+}
+
+pub mod alias;
+#[cfg(feature = "nfc")]
+pub mod batch;
+pub mod bytes;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod codec;
+#[cfg(feature = "nfc")]
+pub mod config;
+pub mod counter;
+#[cfg(all(feature = "dbus", feature = "nfc"))]
+pub mod dbus_service;
+pub mod dump;
+pub mod emulator;
+#[cfg(feature = "nfc")]
+pub mod error;
+pub mod fill;
+#[cfg(feature = "nfc")]
+pub mod fingerprint;
+pub mod image;
+pub mod info;
+#[cfg(feature = "instrument")]
+pub mod instrument;
+pub mod inventory;
+pub mod iso14443b2;
+pub mod journal;
+pub mod metrics;
+pub mod mirror;
+#[cfg(feature = "nfc")]
+pub mod monitor;
+#[cfg(feature = "nfc")]
+pub mod mqtt;
+#[cfg(feature = "node")]
+mod node;
+pub mod notes;
+pub mod oplog;
+pub mod otlp;
+pub mod persist;
+#[cfg(feature = "nfc")]
+pub mod probe;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "nfc")]
+pub mod quirks;
+pub mod range;
+#[cfg(feature = "nfc")]
+pub mod reconcile;
+pub mod replay;
+#[cfg(feature = "nfc")]
+pub mod scan;
+pub mod schema;
+#[cfg(feature = "nfc")]
+pub mod selftest;
+#[cfg(feature = "nfc")]
+pub mod server;
+#[cfg(feature = "nfc")]
+pub mod session;
+pub mod signature;
+pub mod system;
+#[cfg(feature = "dangerous")]
+pub mod tearing;
+#[cfg(any(test, feature = "testdata"))]
+pub mod testdata;
+pub mod timeouts;
+pub mod value;
+#[cfg(feature = "nfc")]
+pub mod watch;
+pub mod webhook;
+
 /// SRIX4K memory mapping.
 pub mod mem {
     use std::ops::Range;
@@ -15,6 +201,8 @@ pub mod mem {
     pub const BLOCK_SIZE: usize = 4;
     /// Size of the UID in bytes.
     pub const UID_SIZE: u8 = 8;
+    /// Size of the EEPROM in bytes.
+    pub const EEPROM_SIZE: usize = BLOCK_COUNT * BLOCK_SIZE;
 
     /// Entire EEPROM.
     pub const EEPROM: Range<usize> = Range {
@@ -35,9 +223,401 @@ pub mod mem {
     };
     /// *System OTP bits* block.
     pub const SYSTEM_ADDR: usize = 255;
+
+    /// Offset of the first byte of EEPROM block `i` in byte oriented
+    /// formats, e.g. dump files, where blocks are least significant byte
+    /// first.
+    pub const fn byte_offset(i: usize) -> usize {
+        i * BLOCK_SIZE
+    }
+    /// EEPROM block holding the byte at `offset`, and the index of the
+    /// byte in the block data, least significant first, see
+    /// `byte_offset`.
+    pub const fn block_of_byte(offset: usize) -> (usize, usize) {
+        (offset / BLOCK_SIZE, offset % BLOCK_SIZE)
+    }
+
+    /// SRI512 memory mapping.
+    ///
+    /// | Blocks | Region |
+    /// | --- | --- |
+    /// | 0 to 4 | *Resettable OTP bits* |
+    /// | 5 to 6 | *Count down Counter* |
+    /// | 7 to 15 | *Lockable EEPROM* |
+    /// | 255 | *System OTP bits* |
+    pub mod sri512 {
+        use std::ops::Range;
+
+        /// Total number of blocks.
+        pub const BLOCK_COUNT: usize = 16;
+
+        /// Entire EEPROM.
+        pub const EEPROM: Range<usize> = Range {
+            start: 0,
+            end: BLOCK_COUNT,
+        };
+
+        /// *Resettable OTP bits* region.
+        pub const OTP: Range<usize> = Range { start: 0, end: 5 };
+        /// *Count down Counter* region.
+        pub const COUNTDOWN: Range<usize> = Range { start: 5, end: 7 };
+        /// *Lockable EEPROM* region.
+        pub const LOCKABLE: Range<usize> = Range {
+            start: 7,
+            end: BLOCK_COUNT,
+        };
+        /// *EEPROM* region, empty: every block is lockable.
+        pub const GENERIC: Range<usize> = Range {
+            start: BLOCK_COUNT,
+            end: BLOCK_COUNT,
+        };
+        /// *System OTP bits* block.
+        pub const SYSTEM_ADDR: usize = 255;
+    }
+
+    /// Address of a block of the SRIX4K, from 0 to 127 or 255 for system,
+    /// checked when built.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct BlockAddr(u8);
+
+    impl BlockAddr {
+        /// *System OTP bits* block.
+        pub const SYSTEM: BlockAddr = BlockAddr(SYSTEM_ADDR as u8);
+
+        /// Address of the block, `None` if it is neither in the EEPROM nor
+        /// the system block.
+        pub const fn new(address: usize) -> Option<BlockAddr> {
+            if address < BLOCK_COUNT || address == SYSTEM_ADDR {
+                Some(BlockAddr(address as u8))
+            } else {
+                None
+            }
+        }
+        /// Address as sent in the commands.
+        pub fn get(&self) -> u8 {
+            self.0
+        }
+        /// Address as an index of the EEPROM, or `SYSTEM_ADDR`.
+        pub fn index(&self) -> usize {
+            self.0 as usize
+        }
+    }
+
+    impl From<BlockAddr> for u8 {
+        fn from(value: BlockAddr) -> Self {
+            value.0
+        }
+    }
+
+    impl From<BlockAddr> for usize {
+        fn from(value: BlockAddr) -> Self {
+            value.0 as usize
+        }
+    }
+
+    /// Iterator of the addresses of a range of blocks, see `Blocks`.
+    #[derive(Debug, Clone)]
+    pub struct BlockAddrs(Range<usize>);
+
+    impl Iterator for BlockAddrs {
+        type Item = BlockAddr;
+
+        fn next(&mut self) -> Option<BlockAddr> {
+            self.0.next().map(|address| BlockAddr(address as u8))
+        }
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.0.size_hint()
+        }
+    }
+
+    impl DoubleEndedIterator for BlockAddrs {
+        fn next_back(&mut self) -> Option<BlockAddr> {
+            self.0.next_back().map(|address| BlockAddr(address as u8))
+        }
+    }
+
+    impl ExactSizeIterator for BlockAddrs {}
+
+    /// Ranges of the memory map usable as iterators of block addresses,
+    /// e.g. `mem::LOCKABLE.blocks()`.
+    pub trait Blocks {
+        /// Addresses of the blocks of the range inside the EEPROM.
+        fn blocks(&self) -> BlockAddrs;
+    }
+
+    impl Blocks for Range<usize> {
+        /// The range is clipped to the EEPROM, so every address yielded is
+        /// valid.
+        fn blocks(&self) -> BlockAddrs {
+            BlockAddrs(self.start.min(BLOCK_COUNT)..self.end.min(BLOCK_COUNT))
+        }
+    }
+
+    /// Region of the memory map a block belongs to.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Region {
+        /// *Resettable OTP bits*, bits can only be cleared.
+        Otp,
+        /// *Count down Counter*, can only be decremented.
+        Countdown,
+        /// *Lockable EEPROM*, can be made read only.
+        Lockable,
+        /// *EEPROM*.
+        Generic,
+        /// *System OTP bits*, bits can only be cleared.
+        System,
+    }
+
+    impl Region {
+        /// Whether writes to the region cannot be undone: OTP bits and
+        /// counters.
+        pub fn is_irreversible(&self) -> bool {
+            match self {
+                Region::Otp | Region::Countdown | Region::System => true,
+                Region::Lockable | Region::Generic => false,
+            }
+        }
+    }
+
+    /// Index of a block inside a region of the SRIX4K map.
+    ///
+    /// Indices are checked against the size of their region when built,
+    /// so code addressing e.g. the 3rd generic block cannot hit a counter
+    /// block whatever the arithmetic producing the index.
+    pub trait RegionIndex: Copy {
+        /// Region the index belongs to.
+        const REGION: Region;
+        /// Blocks of the region.
+        const RANGE: Range<usize>;
+        /// Position of the block inside the region, from 0.
+        fn index(&self) -> usize;
+        /// Address of the block.
+        fn address(&self) -> usize {
+            Self::RANGE.start + self.index()
+        }
+        /// Address of the block, as a `BlockAddr`.
+        fn block_addr(&self) -> BlockAddr {
+            BlockAddr(self.address() as u8)
+        }
+    }
+
+    macro_rules! region_index {
+        ($(#[$attr:meta])* $name:ident, $region:ident, $range:ident) => {
+            $(#[$attr])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+            pub struct $name(usize);
+
+            impl $name {
+                /// Number of blocks of the region.
+                pub const COUNT: usize = $range.end - $range.start;
+
+                /// Index of the block at `index` inside the region, `None`
+                /// if the region is smaller.
+                pub const fn new(index: usize) -> Option<$name> {
+                    if index < Self::COUNT {
+                        Some($name(index))
+                    } else {
+                        None
+                    }
+                }
+                /// Index of the block at `index` inside the region,
+                /// failing to compile in a constant if the region is
+                /// smaller.
+                ///
+                /// # Panics
+                ///
+                /// If `index` is outside the region.
+                pub const fn at(index: usize) -> $name {
+                    match $name::new(index) {
+                        Some(index) => index,
+                        None => panic!("index outside the region"),
+                    }
+                }
+                /// Every block of the region, in address order.
+                pub fn all() -> impl Iterator<Item = $name> {
+                    (0..Self::COUNT).map($name)
+                }
+            }
+
+            impl RegionIndex for $name {
+                const REGION: Region = Region::$region;
+                const RANGE: Range<usize> = $range;
+
+                fn index(&self) -> usize {
+                    self.0
+                }
+            }
+        };
+    }
+
+    region_index!(
+        /// Index of a block of the *Resettable OTP bits* region.
+        OtpIndex,
+        Otp,
+        OTP
+    );
+    region_index!(
+        /// Index of a block of the *Count down Counter* region.
+        CountdownIndex,
+        Countdown,
+        COUNTDOWN
+    );
+    region_index!(
+        /// Index of a block of the *Lockable EEPROM* region.
+        LockableIndex,
+        Lockable,
+        LOCKABLE
+    );
+    region_index!(
+        /// Index of a block of the *EEPROM* region, from 0 to 111.
+        GenericIndex,
+        Generic,
+        GENERIC
+    );
+
+    /// Memory map of a chip of the ST SRI family.
+    pub trait MemoryLayout {
+        /// Name of the chip.
+        fn name(&self) -> &'static str;
+        /// Total number of blocks.
+        fn block_count(&self) -> usize;
+        /// *Resettable OTP bits* region.
+        fn otp(&self) -> Range<usize>;
+        /// *Count down Counter* region.
+        fn countdown(&self) -> Range<usize>;
+        /// *Lockable EEPROM* region.
+        fn lockable(&self) -> Range<usize>;
+        /// *EEPROM* region.
+        fn generic(&self) -> Range<usize>;
+        /// Entire EEPROM.
+        fn eeprom(&self) -> Range<usize> {
+            0..self.block_count()
+        }
+        /// *System OTP bits* block.
+        fn system_addr(&self) -> usize {
+            SYSTEM_ADDR
+        }
+        /// Region of the block, `None` if it is outside the map.
+        fn region(&self, block_address: usize) -> Option<Region> {
+            if block_address == self.system_addr() {
+                Some(Region::System)
+            } else if self.otp().contains(&block_address) {
+                Some(Region::Otp)
+            } else if self.countdown().contains(&block_address) {
+                Some(Region::Countdown)
+            } else if self.lockable().contains(&block_address) {
+                Some(Region::Lockable)
+            } else if self.generic().contains(&block_address) {
+                Some(Region::Generic)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Memory map of the SRIX4K, the constants of this module.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Srix4kLayout;
+
+    impl MemoryLayout for Srix4kLayout {
+        fn name(&self) -> &'static str {
+            "SRIX4K"
+        }
+        fn block_count(&self) -> usize {
+            BLOCK_COUNT
+        }
+        fn otp(&self) -> Range<usize> {
+            OTP
+        }
+        fn countdown(&self) -> Range<usize> {
+            COUNTDOWN
+        }
+        fn lockable(&self) -> Range<usize> {
+            LOCKABLE
+        }
+        fn generic(&self) -> Range<usize> {
+            GENERIC
+        }
+    }
+
+    /// Memory map of the SRI512 and ST25TB512, the constants of `sri512`.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Sri512Layout;
+
+    impl MemoryLayout for Sri512Layout {
+        fn name(&self) -> &'static str {
+            "SRI512"
+        }
+        fn block_count(&self) -> usize {
+            sri512::BLOCK_COUNT
+        }
+        fn otp(&self) -> Range<usize> {
+            sri512::OTP
+        }
+        fn countdown(&self) -> Range<usize> {
+            sri512::COUNTDOWN
+        }
+        fn lockable(&self) -> Range<usize> {
+            sri512::LOCKABLE
+        }
+        fn generic(&self) -> Range<usize> {
+            sri512::GENERIC
+        }
+        fn system_addr(&self) -> usize {
+            sri512::SYSTEM_ADDR
+        }
+    }
+
+    /// Memory map of the 64 blocks ST25TB02K.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct St25tb02kLayout;
+
+    impl MemoryLayout for St25tb02kLayout {
+        fn name(&self) -> &'static str {
+            "ST25TB02K"
+        }
+        fn block_count(&self) -> usize {
+            64
+        }
+        fn otp(&self) -> Range<usize> {
+            OTP
+        }
+        fn countdown(&self) -> Range<usize> {
+            COUNTDOWN
+        }
+        fn lockable(&self) -> Range<usize> {
+            LOCKABLE
+        }
+        fn generic(&self) -> Range<usize> {
+            16..64
+        }
+    }
+
+    /// 6 bits product code of the chip, following the `D0 02` prefix of
+    /// ST UIDs.
+    pub fn chip_code(uid: u64) -> u8 {
+        (uid >> 42) as u8 & 0x3F
+    }
+
+    /// Memory map of the chip with `uid`, `None` if its product code is
+    /// not known.
+    pub fn layout_of(uid: u64) -> Option<&'static dyn MemoryLayout> {
+        match chip_code(uid) {
+            // SRIX4K, SRI4K, ST25TB04K.
+            0x03 | 0x07 | 0x1F => Some(&Srix4kLayout),
+            // SRIX512, SRI512, ST25TB512-AC, ST25TB512-AT.
+            0x04 | 0x06 | 0x1B | 0x33 => Some(&Sri512Layout),
+            // ST25TB02K.
+            0x3F => Some(&St25tb02kLayout),
+            _ => None,
+        }
+    }
 }
 
 /// Commands that can be received by SRIX4K tag in ready state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     /// `ReadBlock(block_address)`
     /// From 0 to 127, or 255 for system. Block Data(LSB)
@@ -53,210 +633,1895 @@ impl From<Command> for Vec<u8> {
     /// Convert command variant to frame that will be sent to the tag.
     fn from(value: Command) -> Self {
         match value {
-            Command::ReadBlock(address) => {
-                let mut frame = vec![0x08];
-                frame.extend(address.to_le_bytes());
-                frame
-            }
+            Command::ReadBlock(address) => iso14443b2::read_block(address),
             Command::WriteBlock(address, block_data) => {
-                let mut frame = vec![0x09];
-                frame.extend(address.to_le_bytes());
-                frame.extend(block_data.to_le_bytes());
-                frame
+                iso14443b2::write_block(address, block_data)
             }
-            Command::GetUid => vec![0x0B],
+            Command::GetUid => iso14443b2::get_uid(),
         }
     }
 }
 
+/// UID of a tag, `D0` prefix, manufacturer code, 6 bits product code and
+/// 42 bits serial number, from the most significant byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Uid(pub u64);
+
+impl Uid {
+    /// Prefix of ISO14443B-2 UIDs, `0xD0`.
+    pub fn prefix(&self) -> u8 {
+        (self.0 >> 56) as u8
+    }
+    /// Manufacturer code, `0x02` for STMicroelectronics.
+    pub fn manufacturer(&self) -> u8 {
+        (self.0 >> 48) as u8
+    }
+    /// Product code of the chip, see `mem::layout_of`.
+    pub fn chip_code(&self) -> u8 {
+        mem::chip_code(self.0)
+    }
+    /// Serial number.
+    pub fn serial(&self) -> u64 {
+        self.0 & 0x3FF_FFFF_FFFF
+    }
+    /// Whether the UID has the `D0 02` prefix of chips manufactured by
+    /// STMicroelectronics.
+    pub fn is_st_manufactured(&self) -> bool {
+        self.prefix() == 0xD0 && self.manufacturer() == 0x02
+    }
+}
+
+impl From<u64> for Uid {
+    fn from(value: u64) -> Self {
+        Uid(value)
+    }
+}
+
+impl From<Uid> for u64 {
+    fn from(value: Uid) -> Self {
+        value.0
+    }
+}
+
+impl std::fmt::Display for Uid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:016X}", self.0)
+    }
+}
+
+/// What a strict connection does with a UID that is not from ST or whose
+/// product code is not the one of the expected chip, and a restore with
+/// the dump of another tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Log a warning and connect anyway.
+    Warn,
+    /// Fail with `DeviceNotSupported`.
+    Deny,
+}
+
+/// Time between two polls of `Srix4k::wait_for_tag` and
+/// `Srix4k::wait_for_removal`.
+#[cfg(feature = "nfc")]
+pub const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Attempts of `Srix4k::read_block` on transmission errors.
+#[cfg(feature = "nfc")]
+pub const READ_ATTEMPTS: usize = 3;
+
 /// Wrapper structure for a device connected to SRIX4K.
 /// Used to send commands.
+#[cfg(feature = "nfc")]
 pub struct Srix4k<'a> {
     /// Reader that is connected to the tag.
     device: nfc1::Device<'a>,
+    /// Name of the reader, for `Debug` which cannot borrow it mutably.
+    device_name: &'static str,
+    /// Whether RF operations are logged as JSON lines.
+    json_log: bool,
+    /// Latency of the commands sent to the tag.
+    stats: metrics::LatencyStats,
+    /// Workarounds applied to the commands.
+    quirks: &'static quirks::Quirks,
+    /// Time waited after `WriteBlock`, instead of the one of the quirks.
+    write_delay: Option<Duration>,
+    /// Time the reader waits for the tag, per command.
+    timeouts: timeouts::CommandTimeouts,
+    /// UID the tag must have when selected again, see `set_expected_uid`.
+    expected_uid: Option<u64>,
+    /// Chip ID the tag was selected with, see `select_chip_id`.
+    chip_id: Option<u8>,
+    /// UID of the selected tag, once read with `GetUID`, for `Debug`.
+    uid: Option<u64>,
+    /// Frames exchanged with the tag.
+    #[cfg(feature = "instrument")]
+    instrumentation: instrument::Instrumentation,
 }
 
+#[cfg(feature = "nfc")]
 impl Srix4k<'_> {
     /// Select SRIX4K near device and connect to it.
-    pub fn connect_from<'a>(
+    pub fn connect_from<'a>(device: nfc1::Device<'a>) -> Result<Srix4k<'a>> {
+        let mut tag = Srix4k::new(device);
+        tag.select()?;
+        Ok(tag)
+    }
+    /// Set the reader properties of `config`, then select SRIX4K near
+    /// device and connect to it.
+    pub fn connect_from_configured<'a>(
         mut device: nfc1::Device<'a>,
+        config: &config::DeviceConfig,
     ) -> Result<Srix4k<'a>> {
-        debug!("Connecting to target from device {}", device.name());
-        device.initiator_list_passive_targets(
-            &nfc1::Modulation {
-                modulation_type: nfc1::ModulationType::Iso14443b,
-                baud_rate: nfc1::BaudRate::Baud106,
-            },
-            1,
-        )?;
-        device.initiator_select_passive_target(&nfc1::Modulation {
-            modulation_type: nfc1::ModulationType::Iso14443b2sr,
-            baud_rate: nfc1::BaudRate::Baud106,
-        })?;
+        config.apply(&mut device)?;
+        Srix4k::connect_from(device)
+    }
+    /// Wait until a tag enters the field of device, or `timeout` elapses,
+    /// and connect to it. `None` waits forever.
+    ///
+    /// The field is polled every `WAIT_POLL_INTERVAL` with selections that
+    /// return right away, `InfiniteSelect` is left disabled. Fails with
+    /// `Timeout`, closing the device, if no tag entered the field in time.
+    pub fn wait_for_tag<'a>(
+        mut device: nfc1::Device<'a>,
+        timeout: Option<Duration>,
+    ) -> Result<Srix4k<'a>> {
+        device.set_property_bool(nfc1::Property::InfiniteSelect, false)?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut tag = Srix4k::new(device);
+        debug!("Waiting for a tag on device {}", tag.device.name());
+        loop {
+            if tag.select().is_ok() {
+                return Ok(tag);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(nfc1::Error::Timeout);
+            }
+            thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+    /// Select SRIX4K near device and connect to it, trying again until a
+    /// tag enters the field or `deadline` passes, see `wait_for_tag`.
+    pub fn connect_from_with_retry<'a>(
+        device: nfc1::Device<'a>,
+        deadline: Instant,
+    ) -> Result<Srix4k<'a>> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        Srix4k::wait_for_tag(device, Some(timeout))
+    }
+    /// Wait until the selected tag leaves the field, or `timeout`
+    /// elapses. `None` waits forever.
+    ///
+    /// The tag is pinged every `WAIT_POLL_INTERVAL`. A tag that does not
+    /// answer is selected again once, so glitches of the coupling are not
+    /// reported as removals; another tag taking its place is. Disables
+    /// `InfiniteSelect`, so the selection fails fast on an empty field.
+    /// Fails with `Timeout` if the tag is still there in time.
+    pub fn wait_for_removal(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        self.device
+            .set_property_bool(nfc1::Property::InfiniteSelect, false)?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let uid = match self.ping() {
+            Some(uid) => uid,
+            None => return Ok(()),
+        };
+        loop {
+            if self.ping() != Some(uid) {
+                let present =
+                    self.select().is_ok() && self.ping() == Some(uid);
+                if !present {
+                    debug!("Tag left the field");
+                    return Ok(());
+                }
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(nfc1::Error::Timeout);
+            }
+            thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+    /// Check that the tag answers, returning its UID, or `0` if the tag
+    /// does not answer `GetUID` and its system block is read instead.
+    fn ping(&mut self) -> Option<u64> {
+        if self.quirks.get_uid {
+            self.send_get_uid().ok()
+        } else {
+            self.send_read_block(mem::SYSTEM_ADDR as u8).ok().map(|_| 0)
+        }
+    }
+    /// Wrap the device without selecting a tag.
+    fn new(mut device: nfc1::Device<'_>) -> Srix4k<'_> {
+        Srix4k {
+            device_name: device.name(),
+            device,
+            json_log: false,
+            stats: metrics::LatencyStats::default(),
+            quirks: &quirks::GENUINE,
+            write_delay: None,
+            timeouts: timeouts::DEFAULT,
+            expected_uid: None,
+            chip_id: None,
+            uid: None,
+            #[cfg(feature = "instrument")]
+            instrumentation: instrument::Instrumentation::default(),
+        }
+    }
+    /// Log every RF operation as a JSON line, see `oplog`.
+    #[cfg(feature = "log")]
+    pub fn set_json_log(&mut self, enabled: bool) {
+        self.json_log = enabled;
+    }
+    /// Latency of the commands sent to the tag since it was connected or
+    /// the statistics were reset.
+    pub fn stats(&self) -> &metrics::LatencyStats {
+        &self.stats
+    }
+    /// Clear the latency statistics.
+    pub fn reset_stats(&mut self) {
+        self.stats = metrics::LatencyStats::default();
+    }
+    /// Frames exchanged with the tag since it was connected or the
+    /// counters were reset, see `instrument`.
+    #[cfg(feature = "instrument")]
+    pub fn instrumentation(&self) -> &instrument::Instrumentation {
+        &self.instrumentation
+    }
+    /// Clear the instrumentation counters.
+    #[cfg(feature = "instrument")]
+    pub fn reset_instrumentation(&mut self) {
+        self.instrumentation = instrument::Instrumentation::default();
+    }
+    /// Workarounds applied to the commands, see `quirks`.
+    pub fn quirks(&self) -> &'static quirks::Quirks {
+        self.quirks
+    }
+    /// Set the workarounds applied to the commands.
+    pub fn set_quirks(&mut self, quirks: &'static quirks::Quirks) {
+        self.quirks = quirks;
+    }
+    /// Check that the tag selected again after an error has `uid`, failing
+    /// with `TagSwapped` otherwise, `None` to accept any tag. Chips not
+    /// answering `GetUID` cannot be checked.
+    pub fn set_expected_uid(&mut self, uid: Option<u64>) {
+        self.expected_uid = uid;
+    }
+    /// Chip ID the tag was selected with, `None` until `select_chip_id`:
+    /// the selection of libnfc does not report it.
+    pub fn chip_id(&self) -> Option<u8> {
+        self.chip_id
+    }
+    /// Time waited after `WriteBlock` before the next command, the one of
+    /// the quirks unless set with `set_write_delay`.
+    pub fn write_delay(&self) -> Duration {
+        self.write_delay.unwrap_or(self.quirks.write_delay)
+    }
+    /// Set the time waited after `WriteBlock`, e.g. for clones needing
+    /// longer than `iso14443b2::WRITE_TIME` to program a block. Replaces
+    /// the delay of the quirks, even when they are set again.
+    pub fn set_write_delay(&mut self, delay: Duration) {
+        self.write_delay = Some(delay);
+    }
+    /// Time the reader waits for the tag, per command, see `timeouts`.
+    pub fn timeouts(&self) -> &timeouts::CommandTimeouts {
+        &self.timeouts
+    }
+    /// Set the time the reader waits for the tag, per command.
+    pub fn set_timeouts(&mut self, timeouts: timeouts::CommandTimeouts) {
+        self.timeouts = timeouts;
+    }
+    /// Set the time the reader waits for the tag on `command`, and on the
+    /// other commands of the same kind, see `CommandTimeouts::set`.
+    pub fn set_command_timeout(
+        &mut self,
+        command: &Command,
+        timeout: Duration,
+    ) {
+        self.timeouts.set(command, timeout);
+    }
+    /// Timeout of `command`, as libnfc expects it.
+    fn timeout(&self, command: &Command) -> Timeout {
+        match self.timeouts.get(command) {
+            Duration::ZERO => Timeout::None,
+            timeout => Timeout::Duration(timeout),
+        }
+    }
+    /// Log the JSON line of the operation started at `start`, if enabled.
+    fn log_json<E: std::fmt::Display>(
+        &self,
+        name: &'static str,
+        block: Option<u8>,
+        value: Option<String>,
+        start: Instant,
+        error: Option<&E>,
+    ) {
+        if self.json_log {
+            let operation = oplog::Operation {
+                name,
+                block,
+                value,
+                duration: start.elapsed(),
+                error: error.map(|err| err as &dyn std::fmt::Display),
+            };
+            info!(target: oplog::TARGET, "{}", operation);
+        }
+    }
+    /// Select SRIX4K near device.
+    fn select(&mut self) -> Result<()> {
+        hot_trace!("Connecting to target from device {}", self.device_name);
+        let start = Instant::now();
+        let result = traced!("connect", {}, {
+            iso14443b2::select_target(&mut self.device)?;
+            self.chip_id = None;
+            self.uid = None;
 
-        info!("Connected to target from device {}", device.name());
+            info!("Connected to target from device {}", self.device_name);
 
-        Ok(Srix4k { device })
+            Ok(())
+        });
+        self.log_json("connect", None, None, start, result.as_ref().err());
+        result
+    }
+    /// Select the tag again, checking its UID, see `set_expected_uid`.
+    fn reselect(&mut self) -> Srix4kResult<()> {
+        self.select()?;
+        if let (Some(expected), true) = (self.expected_uid, self.quirks.get_uid)
+        {
+            let found = self.send_get_uid()?;
+            if found != expected {
+                warn!("Expected tag {:016X}, found {:016X}", expected, found);
+                return Err(Srix4kError::TagSwapped(expected, found));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl<'a> Srix4k<'a> {
+    /// Reader that is connected to the tag.
+    pub fn device(&self) -> &nfc1::Device<'a> {
+        &self.device
+    }
+    /// Reader that is connected to the tag, e.g. to change its properties
+    /// between commands.
+    pub fn device_mut(&mut self) -> &mut nfc1::Device<'a> {
+        &mut self.device
+    }
+    /// Release the reader, leaving the tag selected.
+    pub fn into_inner(self) -> nfc1::Device<'a> {
+        self.device
     }
 }
 
+#[cfg(feature = "nfc")]
 impl Srix4k<'_> {
     /// Send `ReadBlock` command to the tag with specified block address
     /// and return the block data.
     pub fn send_read_block(&mut self, block_address: u8) -> Result<u32> {
-        let frame: Vec<u8> = Command::ReadBlock(block_address).into();
-        let response = self.device.initiator_transceive_bytes(
-            &frame,
-            mem::BLOCK_SIZE.into(),
-            Timeout::None,
-        )?;
-        trace!("Reading block {:#04X}", block_address);
-
-        let block_data = u32::from_le_bytes(
-            response
-                .try_into()
-                .map_err(|_| nfc1::Error::RfTransmissionError)?,
+        self.read_block_once(block_address)
+            .map_err(nfc1::Error::from)
+    }
+    /// Read a block, retrying according to the kind of error:
+    /// - transmission errors and malformed answers right away, up to
+    ///   `READ_ATTEMPTS` attempts in total;
+    /// - timeouts once, after selecting the tag again;
+    /// - collisions and the other errors are not retried.
+    ///
+    /// Transmission errors that persist are reported as `Collision` if
+    /// more than one tag is in the field. Selecting another tag than the
+    /// expected one fails with `TagSwapped`, see `set_expected_uid`.
+    pub fn read_block(&mut self, block_address: u8) -> Srix4kResult<u32> {
+        let mut attempts = 0;
+        let mut reselected = false;
+        loop {
+            attempts += 1;
+            let err = match self.read_block_once(block_address) {
+                Ok(block_data) => return Ok(block_data),
+                Err(err) => err,
+            };
+            debug!("Reading block {:#04X}: {}", block_address, err);
+            match err {
+                Srix4kError::MalformedResponse(_) | Srix4kError::Transmission
+                    if attempts < READ_ATTEMPTS =>
+                {
+                    #[cfg(feature = "instrument")]
+                    self.instrumentation.retry();
+                }
+                Srix4kError::Transmission => {
+                    let targets = iso14443b2::count_targets(&mut self.device)?;
+                    self.reselect()?;
+                    return Err(if targets > 1 {
+                        Srix4kError::Collision
+                    } else {
+                        err
+                    });
+                }
+                Srix4kError::Timeout if !reselected => {
+                    reselected = true;
+                    self.reselect()?;
+                    #[cfg(feature = "instrument")]
+                    self.instrumentation.retry();
+                }
+                err => return Err(err),
+            }
+        }
+    }
+    /// Send `ReadBlock` once, telling malformed answers apart.
+    fn read_block_once(&mut self, block_address: u8) -> Srix4kResult<u32> {
+        let command = Command::ReadBlock(block_address);
+        let frame = command.frame();
+        let timeout = self.timeout(&command);
+        #[cfg(feature = "instrument")]
+        self.instrumentation.sent(&frame);
+        let start = Instant::now();
+        let response = traced!(
+            "read_block",
+            { address = block_address, bytes = frame.len() },
+            {
+                let response = self.device.initiator_transceive_bytes(
+                    &frame,
+                    mem::BLOCK_SIZE.into(),
+                    timeout,
+                )?;
+                Ok(response)
+            }
         );
-
-        trace!("{:#04X}: {:#010X}", block_address, block_data);
-
-        Ok(block_data)
+        let result = match response {
+            Ok(response) => {
+                #[cfg(feature = "instrument")]
+                self.instrumentation.received(&response);
+                codec::decode_block(&response).map_err(Srix4kError::from)
+            }
+            Err(err) => Err(Srix4kError::from(err)),
+        };
+        if let Ok(block_data) = result {
+            hot_trace!("{:#04X}: {:#010X}", block_address, block_data);
+        }
+        self.stats.read_block.observe(start.elapsed());
+        if self.json_log {
+            let value = result.as_ref().ok().map(|v| format!("{:#010X}", v));
+            let error = result.as_ref().err();
+            self.log_json(
+                "read_block",
+                Some(block_address),
+                value,
+                start,
+                error,
+            );
+        }
+        result
     }
     /// Send `WriteBlock` command to the tag
     /// with specified block address and block data.
+    ///
+    /// Fails with `InvalidArgument` for the system block, whose writes
+    /// cannot be undone: see `send_write_system`.
     pub fn send_write_block(
         &mut self,
         block_address: u8,
         block_data: u32,
     ) -> Result<()> {
-        trace!(
+        if block_address as usize == mem::SYSTEM_ADDR {
+            warn!("Refusing to write the system block without acknowledgment");
+            return Err(nfc1::Error::InvalidArgument);
+        }
+        self.write_block_unchecked(block_address, block_data)
+    }
+    /// Send `WriteBlock` command for the system block with the specified
+    /// data.
+    ///
+    /// Clearing a lock bit cannot be undone, hence the acknowledgment.
+    pub fn send_write_system(
+        &mut self,
+        block_data: u32,
+        _acknowledgment: system::IrreversibleWrite,
+    ) -> Result<()> {
+        self.write_block_unchecked(mem::SYSTEM_ADDR as u8, block_data)
+    }
+    /// Send `WriteBlock` command to the tag, the system block included.
+    fn write_block_unchecked(
+        &mut self,
+        block_address: u8,
+        block_data: u32,
+    ) -> Result<()> {
+        hot_trace!(
             "Writing {:#010X} to block {:#04X}",
             block_data,
             block_address
         );
-        let frame: Vec<u8> =
-            Command::WriteBlock(block_address, block_data).into();
-        self.device.target_send_bytes(&frame, Timeout::None)?;
-        Ok(())
+        let command = Command::WriteBlock(block_address, block_data);
+        let frame = command.frame();
+        let timeout = self.timeout(&command);
+        let write_delay = self.write_delay();
+        #[cfg(feature = "instrument")]
+        {
+            self.instrumentation.sent(&frame);
+            self.instrumentation.written(block_address);
+        }
+        let start = Instant::now();
+        let result = traced!(
+            "write_block",
+            { address = block_address, bytes = frame.len() },
+            {
+                // The tag does not answer `WriteBlock`.
+                match self.device.initiator_transceive_bytes(&frame, 0, timeout)
+                {
+                    Ok(_) | Err(nfc1::Error::Timeout) => {}
+                    Err(err) => return Err(err),
+                }
+                if write_delay > Duration::ZERO {
+                    thread::sleep(write_delay);
+                }
+                Ok(())
+            }
+        );
+        self.stats.write_block.observe(start.elapsed());
+        if self.json_log {
+            let value = Some(format!("{:#010X}", block_data));
+            let error = result.as_ref().err();
+            self.log_json(
+                "write_block",
+                Some(block_address),
+                value,
+                start,
+                error,
+            );
+        }
+        result
+    }
+    /// Select the tag again with a chip ID of its own, e.g. to address it
+    /// among others, and return it, see `chip_id`.
+    ///
+    /// The tag is sent back to inventory with `Reset_to_inventory`, draws
+    /// a chip ID answering `Initiate`, and is selected with it. Fails with
+    /// `RfTransmissionError` if the answers are not one chip ID, e.g. when
+    /// several tags are in the field, after selecting a tag like libnfc.
+    pub fn select_chip_id(&mut self) -> Result<u8> {
+        let mut transceive = |frame: &[u8], answer| {
+            self.device.initiator_transceive_bytes(
+                frame,
+                answer,
+                Timeout::Default,
+            )
+        };
+        // The tag does not answer `Reset_to_inventory`.
+        let _ = transceive(&iso14443b2::reset_to_inventory(), 0);
+        let chip_id = transceive(&iso14443b2::initiate(), 1).and_then(|id| {
+            match id[..] {
+                [chip_id] => Ok(chip_id),
+                _ => Err(nfc1::Error::RfTransmissionError),
+            }
+        });
+        let selected = chip_id.and_then(|chip_id| {
+            match transceive(&iso14443b2::select(chip_id), 1)?[..] {
+                [id] if id == chip_id => Ok(chip_id),
+                _ => Err(nfc1::Error::RfTransmissionError),
+            }
+        });
+        match selected {
+            Ok(chip_id) => {
+                debug!("Selected the tag with chip ID {:#04X}", chip_id);
+                self.chip_id = Some(chip_id);
+                Ok(chip_id)
+            }
+            Err(err) => {
+                debug!("Selecting with a chip ID: {}", err);
+                self.select()?;
+                Err(err)
+            }
+        }
     }
     /// Send `GetUID` command to the tag and return UID.
     pub fn send_get_uid(&mut self) -> Result<u64> {
-        let frame: Vec<u8> = Command::GetUid.into();
-        let response = self.device.initiator_transceive_bytes(
-            &frame,
-            mem::UID_SIZE.into(),
-            Timeout::None,
-        )?;
-        Ok(u64::from_le_bytes(
-            response
-                .try_into()
-                .map_err(|_| nfc1::Error::RfTransmissionError)?,
-        ))
+        if !self.quirks.get_uid {
+            return Err(nfc1::Error::DeviceNotSupported);
+        }
+        let frame = Command::GetUid.frame();
+        let timeout = self.timeout(&Command::GetUid);
+        #[cfg(feature = "instrument")]
+        self.instrumentation.sent(&frame);
+        let start = Instant::now();
+        let result = traced!("get_uid", { bytes = frame.len() }, {
+            let response = self.device.initiator_transceive_bytes(
+                &frame,
+                mem::UID_SIZE.into(),
+                timeout,
+            )?;
+            #[cfg(feature = "instrument")]
+            self.instrumentation.received(&response);
+            Ok(codec::decode_uid(&response)?)
+        });
+        self.stats.get_uid.observe(start.elapsed());
+        if let Ok(uid) = result {
+            self.uid = Some(uid);
+        }
+        if self.json_log {
+            let value = result.as_ref().ok().map(|v| format!("{:016X}", v));
+            self.log_json(
+                "get_uid",
+                None,
+                value,
+                start,
+                result.as_ref().err(),
+            );
+        }
+        result
+    }
+    /// Send `ReadBlock` command to the tag with specified block address
+    /// and return the block data with the response time, in reader clock
+    /// cycles, measured by libnfc.
+    pub fn send_read_block_timed(
+        &mut self,
+        block_address: u8,
+    ) -> Result<(u32, u32)> {
+        let frame = Command::ReadBlock(block_address).frame();
+        #[cfg(feature = "instrument")]
+        self.instrumentation.sent(&frame);
+        let start = Instant::now();
+        let result = traced!(
+            "read_block_timed",
+            { address = block_address, bytes = frame.len() },
+            {
+                let (response, cycles) = self
+                    .device
+                    .initiator_transceive_bytes_timed(&frame, mem::BLOCK_SIZE)?;
+                #[cfg(feature = "instrument")]
+                self.instrumentation.received(&response);
+                let block_data = codec::decode_block(&response)?;
+
+                hot_trace!(
+                    "{:#04X}: {:#010X} in {} cycles",
+                    block_address,
+                    block_data,
+                    cycles
+                );
+
+                Ok((block_data, cycles))
+            }
+        );
+        self.stats.read_block.observe(start.elapsed());
+        if self.json_log {
+            let value = result.as_ref().ok().map(|v| format!("{:#010X}", v.0));
+            let error = result.as_ref().err();
+            self.log_json(
+                "read_block_timed",
+                Some(block_address),
+                value,
+                start,
+                error,
+            );
+        }
+        result
+    }
+    /// Send `GetUID` command to the tag and return UID with the response
+    /// time, in reader clock cycles, measured by libnfc.
+    pub fn send_get_uid_timed(&mut self) -> Result<(u64, u32)> {
+        if !self.quirks.get_uid {
+            return Err(nfc1::Error::DeviceNotSupported);
+        }
+        let frame = Command::GetUid.frame();
+        #[cfg(feature = "instrument")]
+        self.instrumentation.sent(&frame);
+        let start = Instant::now();
+        let result = traced!("get_uid_timed", { bytes = frame.len() }, {
+            let (response, cycles) =
+                self.device.initiator_transceive_bytes_timed(
+                    &frame,
+                    mem::UID_SIZE.into(),
+                )?;
+            #[cfg(feature = "instrument")]
+            self.instrumentation.received(&response);
+            let uid = codec::decode_uid(&response)?;
+
+            hot_trace!("UID {:016X} in {} cycles", uid, cycles);
+
+            Ok((uid, cycles))
+        });
+        self.stats.get_uid.observe(start.elapsed());
+        if let Ok((uid, _)) = result {
+            self.uid = Some(uid);
+        }
+        if self.json_log {
+            let value = result.as_ref().ok().map(|v| format!("{:016X}", v.0));
+            self.log_json(
+                "get_uid_timed",
+                None,
+                value,
+                start,
+                result.as_ref().err(),
+            );
+        }
+        result
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl std::fmt::Debug for Srix4k<'_> {
+    /// Reader, UID of the tag if read, its chip ID otherwise if known,
+    /// quirks and JSON logging.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Srix4k");
+        debug.field("device", &self.device_name);
+        match (self.uid, self.chip_id) {
+            (Some(uid), _) => debug.field("uid", &Uid(uid).to_string()),
+            (None, Some(chip_id)) => {
+                debug.field("chip_id", &format!("{:#04X}", chip_id))
+            }
+            (None, None) => debug.field("uid", &None::<String>),
+        };
+        debug
+            .field("quirks", &self.quirks.name)
+            .field("json_log", &self.json_log)
+            .finish_non_exhaustive()
     }
 }
 
+/// When `Srix4kCached` writes the modified blocks to the tag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// On `sync`, all at once.
+    #[default]
+    WriteBack,
+    /// As soon as possible, reading every block back to verify it:
+    /// `eeprom_set` and `system_set` write right away, modifications
+    /// through the `_mut` getters are written by `flush` or `sync`.
+    WriteThrough,
+}
+
 /// This structure keeps a copy of the original blocks
 /// and a cache to access and modify the tag.  
 ///
 /// To write the modified blocks to the tag call the `sync` method.
+#[cfg(feature = "nfc")]
 pub struct Srix4kCached<'a> {
-    /// [0 to 127] EEPROM containing original and the modified value.
-    eeprom: [Option<(u32, u32)>; 128],
+    /// Memory map of the tag.
+    layout: &'static dyn mem::MemoryLayout,
+    /// EEPROM containing original and the modified value.
+    eeprom: Vec<Option<(u32, u32)>>,
     /// [225] System OTP bits
     system: Option<(u32, u32)>,
     /// [UID0, UID1] ROM
     uid: Option<u64>,
     /// Connected tag.
     tag: Srix4k<'a>,
+    /// Time between two keep-alive pings, `None` if disabled.
+    keep_alive: Option<Duration>,
+    /// Last time the tag was pinged by `keep_alive`.
+    last_ping: Instant,
+    /// Minimum time between two writes of `sync`.
+    write_pacing: Duration,
+    /// Inactivity after which the RF field is switched off, `None` if
+    /// disabled.
+    idle_power_down: Option<Duration>,
+    /// Last time a command was sent to the tag by an operation.
+    last_activity: Instant,
+    /// Whether the RF field was switched off by `power_down_if_idle`.
+    powered_down: bool,
+    /// When the modified blocks are written to the tag.
+    policy: CachePolicy,
+    /// Time after which cached counter and OTP blocks are read again,
+    /// `None` if they are cached until `sync`.
+    read_ttl: Option<Duration>,
+    /// Last time each EEPROM block was read from the tag.
+    read_at: Vec<Instant>,
+    /// Journal file of the writes of `sync`, `None` if disabled.
+    journal: Option<std::path::PathBuf>,
+    /// Order of the writes of `sync`, `None` for address order.
+    sync_order: Option<Box<SyncOrder>>,
+    /// Groups of blocks written together by `sync`.
+    sync_groups: Vec<range::BlockSet>,
+    /// Whether writing the modified system block was acknowledged.
+    system_acknowledged: bool,
+    /// Directory of the blocks saved after `sync`, `None` if disabled.
+    persist: Option<std::path::PathBuf>,
+    /// Blocks saved for the tag in `persist`, by address, only used to
+    /// skip writes, see `load_persisted`.
+    persisted: std::collections::BTreeMap<u8, u32>,
+    /// Whether staging writes to irreversible regions is refused.
+    safe_mode: bool,
+    /// Subscribers to the lifecycle events of the session.
+    subscribers: session::Subscribers,
+    /// Data the synced blocks had when first read, by address.
+    connect_data: std::collections::BTreeMap<u8, u32>,
+    /// Watched blocks and their callbacks, see `watch`.
+    watchpoints: Vec<(range::BlockSet, Box<Watchpoint>)>,
+    /// Data of the watched blocks when last read from the tag.
+    watched_data: std::collections::BTreeMap<u8, u32>,
+    /// Watched block handed out by a `_mut` getter and its data then,
+    /// until its `Staged` event is emitted.
+    staged: Option<(u8, u32)>,
 }
 
+/// Comparator of the writes of `Srix4kCached::sync`, see
+/// `Srix4kCached::set_sync_order`.
+#[cfg(feature = "nfc")]
+pub type SyncOrder = dyn Fn(&JournalEntry, &JournalEntry) -> Ordering;
+
+/// Callback of the blocks watched with `Srix4kCached::watch`.
+#[cfg(feature = "nfc")]
+pub type Watchpoint = dyn FnMut(WatchEvent);
+
+/// Access to a watched block of `Srix4kCached`.
+#[cfg(feature = "nfc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchEvent {
+    /// `Changed { address, old, new }`
+    /// A read from the tag returned other data than the previous one,
+    /// e.g. after the read TTL or in `reconcile`.
+    Changed { address: u8, old: u32, new: u32 },
+    /// `Staged { address, data }`
+    /// The block handed out for modification, by the `_mut` getters or
+    /// the setters, was modified, with its new data. Emitted by the next
+    /// operation of the cache, e.g. `sync`, or right away by the setters.
+    Staged { address: u8, data: u32 },
+}
+
+#[cfg(feature = "nfc")]
 impl Srix4kCached<'_> {
     /// Select SRIX4K near device and connect to it.
+    ///
+    /// The memory map is detected from the product code in the UID,
+    /// falling back to the SRIX4K one if the chip is unknown or does not
+    /// answer `GetUID`.
     pub fn connect_from<'a>(
         device: nfc1::Device<'a>,
     ) -> Result<Srix4kCached<'a>> {
-        Ok(Srix4kCached {
-            eeprom: [None; 128],
+        Ok(Srix4kCached::detect(Srix4k::connect_from(device)?))
+    }
+    /// Set the reader properties of `config`, then select SRIX4K near
+    /// device and connect to it like `connect_from`.
+    pub fn connect_from_configured<'a>(
+        device: nfc1::Device<'a>,
+        config: &config::DeviceConfig,
+    ) -> Result<Srix4kCached<'a>> {
+        let tag = Srix4k::connect_from_configured(device, config)?;
+        Ok(Srix4kCached::detect(tag))
+    }
+    /// Select SRIX4K near device and connect to it like `connect_from`,
+    /// trying again until a tag enters the field or `deadline` passes.
+    ///
+    /// Unlike `InfiniteSelect` the wait is bounded: fails with `Timeout`,
+    /// closing the device, if no tag entered the field in time.
+    pub fn connect_from_with_retry<'a>(
+        device: nfc1::Device<'a>,
+        deadline: Instant,
+    ) -> Result<Srix4kCached<'a>> {
+        let tag = Srix4k::connect_from_with_retry(device, deadline)?;
+        Ok(Srix4kCached::detect(tag))
+    }
+    /// Cache the connected tag, detecting its quirks and memory map.
+    fn detect(mut tag: Srix4k<'_>) -> Srix4kCached<'_> {
+        let uid = tag.send_get_uid().ok();
+        tag.set_quirks(quirks::lookup(uid));
+        debug!("Applying the quirks of {} chips", tag.quirks().name);
+        let layout = match uid.and_then(mem::layout_of) {
+            Some(layout) => layout,
+            None => {
+                warn!("Unknown chip, assuming the SRIX4K memory map");
+                &mem::Srix4kLayout
+            }
+        };
+        debug!("Detected {} chip", layout.name());
+        Srix4kCached::with_tag(tag, layout, uid)
+    }
+    /// Select a tag of the SRI family near device and connect to it,
+    /// caching the blocks of `layout`.
+    pub fn connect_from_with_layout<'a>(
+        device: nfc1::Device<'a>,
+        layout: &'static dyn mem::MemoryLayout,
+    ) -> Result<Srix4kCached<'a>> {
+        Ok(Srix4kCached::with_tag(
+            Srix4k::connect_from(device)?,
+            layout,
+            None,
+        ))
+    }
+    /// Select a tag near device, check that its UID is the one of a
+    /// genuine chip with `layout` and connect to it.
+    ///
+    /// Mislabeled clones and chips of another size are warned about or
+    /// refused according to `strictness`.
+    pub fn connect_from_strict<'a>(
+        device: nfc1::Device<'a>,
+        layout: &'static dyn mem::MemoryLayout,
+        strictness: Strictness,
+    ) -> Result<Srix4kCached<'a>> {
+        let mut tag = Srix4k::connect_from(device)?;
+        let uid = Uid(tag.send_get_uid()?);
+        let problem = if !uid.is_st_manufactured() {
+            Some("is not from an ST chip")
+        } else if mem::layout_of(uid.0).map(|chip| chip.name())
+            != Some(layout.name())
+        {
+            Some("does not have the product code of the expected chip")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            match strictness {
+                Strictness::Warn => warn!("UID {} {}", uid, problem),
+                Strictness::Deny => {
+                    warn!("Refusing UID {}: it {}", uid, problem);
+                    return Err(nfc1::Error::DeviceNotSupported);
+                }
+            }
+        }
+        tag.set_quirks(quirks::lookup(Some(uid.0)));
+        Ok(Srix4kCached::with_tag(tag, layout, Some(uid.0)))
+    }
+    /// Cache the blocks of `layout` of the connected tag.
+    fn with_tag<'a>(
+        mut tag: Srix4k<'a>,
+        layout: &'static dyn mem::MemoryLayout,
+        uid: Option<u64>,
+    ) -> Srix4kCached<'a> {
+        tag.set_expected_uid(uid);
+        Srix4kCached {
+            layout,
+            eeprom: vec![None; layout.block_count()],
             system: None,
-            uid: None,
-            tag: Srix4k::connect_from(device)?,
-        })
+            uid,
+            tag,
+            keep_alive: None,
+            last_ping: Instant::now(),
+            write_pacing: Duration::ZERO,
+            idle_power_down: None,
+            last_activity: Instant::now(),
+            powered_down: false,
+            policy: CachePolicy::WriteBack,
+            read_ttl: None,
+            read_at: vec![Instant::now(); layout.block_count()],
+            journal: None,
+            sync_order: None,
+            sync_groups: Vec::new(),
+            system_acknowledged: false,
+            persist: None,
+            persisted: std::collections::BTreeMap::new(),
+            safe_mode: false,
+            subscribers: session::Subscribers::default(),
+            connect_data: std::collections::BTreeMap::new(),
+            watchpoints: Vec::new(),
+            watched_data: std::collections::BTreeMap::new(),
+            staged: None,
+        }
+    }
+    /// Log every RF operation as a JSON line, see `oplog`.
+    #[cfg(feature = "log")]
+    pub fn set_json_log(&mut self, enabled: bool) {
+        self.tag.set_json_log(enabled);
+    }
+    /// Memory map of the tag.
+    pub fn layout(&self) -> &'static dyn mem::MemoryLayout {
+        self.layout
+    }
+    /// Wait until the tag leaves the field, see `Srix4k::wait_for_removal`.
+    pub fn wait_for_removal(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        self.tag.wait_for_removal(timeout)?;
+        self.subscribers.emit(session::SessionEvent::Removed(self.uid));
+        Ok(())
+    }
+    /// Subscribe to the lifecycle events of the session, starting with
+    /// `Connected`, see `session`.
+    pub fn events(
+        &mut self,
+    ) -> std::sync::mpsc::Receiver<session::SessionEvent> {
+        self.subscribers
+            .subscribe(session::SessionEvent::Connected(self.uid))
+    }
+    /// Latency of the commands sent to the tag, see `Srix4k::stats`.
+    pub fn stats(&self) -> &metrics::LatencyStats {
+        self.tag.stats()
+    }
+    /// Set the time between two pings of `keep_alive`, `None` to disable it.
+    pub fn set_keep_alive(&mut self, interval: Option<Duration>) {
+        self.keep_alive = interval;
+    }
+    /// When the modified blocks are written to the tag.
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+    /// Set when the modified blocks are written to the tag.
+    pub fn set_policy(&mut self, policy: CachePolicy) {
+        self.policy = policy;
+    }
+    /// Set the time after which cached *Count down Counter* and *Resettable
+    /// OTP bits* blocks are stale and read again on the next access, for
+    /// monitoring blocks that change while the tag is connected. Modified
+    /// blocks are kept until `sync`. `None` caches every block.
+    pub fn set_read_ttl(&mut self, ttl: Option<Duration>) {
+        self.read_ttl = ttl;
+    }
+    /// Set the minimum time between two writes of `sync`, on top of the
+    /// programming time, for readers that fail writes sent back to back.
+    pub fn set_write_pacing(&mut self, pacing: Duration) {
+        self.write_pacing = pacing;
+    }
+    /// Journal the writes of `sync` to the file at `path`, `None` to
+    /// disable it, see `journal`.
+    ///
+    /// The journal is removed when `sync` returns, even with failed
+    /// writes. One left behind by a crash lists the blocks that may have
+    /// been torn, see `journal::pending(path)`, and `sync` fails with `Io`
+    /// until it is removed with `journal::discard(path)`.
+    pub fn set_journal(&mut self, path: Option<std::path::PathBuf>) {
+        self.journal = path;
+    }
+    /// Whether staging writes to irreversible regions is refused, see
+    /// `set_safe_mode`.
+    pub fn safe_mode(&self) -> bool {
+        self.safe_mode
+    }
+    /// Refuse to stage writes to the *Resettable OTP bits*, *Count down
+    /// Counter* and *System OTP bits*, so inventory and diagnostic tools
+    /// cannot damage them. `eeprom_get_mut`, `system_get_mut` and the
+    /// setters built on them fail with `InvalidArgument` for their blocks.
+    ///
+    /// Blocks written through `tag_mut` bypass the cache and the check.
+    pub fn set_safe_mode(&mut self, enabled: bool) {
+        self.safe_mode = enabled;
+    }
+    /// Skip the writes of `sync` of the data the blocks saved in `dir`
+    /// for the UID of the tag hold, and save the blocks there after each
+    /// `sync`, see `persist`. Returns how many blocks were loaded.
+    ///
+    /// The saved blocks are never served as reads: blocks are still read
+    /// from the tag, and a saved block the tag contradicts is dropped.
+    /// Fails with `Io` if the saved blocks cannot be read.
+    pub fn load_persisted(
+        &mut self,
+        dir: std::path::PathBuf,
+    ) -> CachedResult<usize> {
+        let uid = self.uid_get()?;
+        let blocks = persist::load(&dir, uid).map_err(|err| {
+            warn!("Persisted blocks in {}: {}", dir.display(), err);
+            CachedError {
+                command: None,
+                block: None,
+                error: Srix4kError::Nfc(nfc1::Error::Io),
+            }
+        })?;
+        let system_addr = self.layout.system_addr();
+        let block_count = self.eeprom.len();
+        self.persisted = blocks
+            .into_iter()
+            .filter(|&(address, _)| {
+                let i = address as usize;
+                i == system_addr || i < block_count
+            })
+            .collect();
+        let loaded = self.persisted.len();
+        debug!("Loaded {} persisted blocks of UID {:016X}", loaded, uid);
+        self.persist = Some(dir);
+        Ok(loaded)
+    }
+    /// Order the writes of `sync` with `compare`, e.g. to write a backup
+    /// copy before the value it backs up,
+    /// `|a, b| (a.address != BACKUP).cmp(&(b.address != BACKUP))`.
+    ///
+    /// Writes comparing equal keep the address order, and so does the
+    /// journal.
+    pub fn set_sync_order<F>(&mut self, compare: F)
+    where
+        F: Fn(&JournalEntry, &JournalEntry) -> Ordering + 'static,
+    {
+        self.sync_order = Some(Box::new(compare));
+    }
+    /// Write the blocks of `sync` in address order again.
+    pub fn reset_sync_order(&mut self) {
+        self.sync_order = None;
+    }
+    /// Declare a group of blocks that `sync` writes together, e.g. a value
+    /// and its backup.
+    ///
+    /// `sync_blocks` refuses to write some of the modified blocks of a
+    /// group without the others, and `sync_continue_on_error` skips the
+    /// rest of a group once one of its blocks fails. Blocks of the group
+    /// written before the failure are not rolled back, so order the
+    /// backup first with `set_sync_order`.
+    pub fn add_sync_group(&mut self, blocks: range::BlockSet) {
+        self.sync_groups.push(blocks);
+    }
+    /// Forget the groups of `add_sync_group`.
+    pub fn clear_sync_groups(&mut self) {
+        self.sync_groups.clear();
+    }
+    /// Call `callback` on every access of the blocks of `blocks` that can
+    /// change them, see `WatchEvent`, e.g. to log a backtrace of the
+    /// operation modifying a sensitive block.
+    pub fn watch<F>(&mut self, blocks: range::BlockSet, callback: F)
+    where
+        F: FnMut(WatchEvent) + 'static,
+    {
+        self.watchpoints.push((blocks, Box::new(callback)));
+    }
+    /// Forget the watchpoints of `watch`.
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+        self.watched_data.clear();
+    }
+    /// Keep the tag selected during long edits: if the keep-alive interval
+    /// elapsed, ping the tag with `GetUID` and select it again if it
+    /// dropped out.
+    ///
+    /// The device cannot be shared with a background thread, so call this
+    /// from the event loop of the application, more often than the
+    /// interval. Fails with `TagSwapped` if another tag is selected in its
+    /// place, since the cached blocks are not its own.
+    pub fn keep_alive(&mut self) -> Srix4kResult<()> {
+        let interval = match self.keep_alive {
+            Some(interval) if !self.powered_down => interval,
+            _ => return Ok(()),
+        };
+        if self.last_ping.elapsed() < interval {
+            return Ok(());
+        }
+        self.last_ping = Instant::now();
+
+        if !self.tag.quirks().get_uid {
+            // Without the UID only the presence of a tag can be checked.
+            let system_addr = self.layout.system_addr() as u8;
+            if self.tag.send_read_block(system_addr).is_err() {
+                debug!("Tag dropped out, selecting it again");
+                let reselected = self.tag.reselect().and_then(|_| {
+                    Ok(self.tag.send_read_block(system_addr)?)
+                });
+                self.emit_reselected(reselected.is_ok());
+                reselected?;
+            }
+            return Ok(());
+        }
+        let uid = match self.tag.send_get_uid() {
+            Ok(uid) => uid,
+            Err(_) => {
+                debug!("Tag dropped out, selecting it again");
+                let reselected = self
+                    .tag
+                    .reselect()
+                    .and_then(|_| Ok(self.tag.send_get_uid()?));
+                self.emit_reselected(reselected.is_ok());
+                reselected?
+            }
+        };
+        match self.uid {
+            Some(cached) if cached != uid => {
+                Err(Srix4kError::TagSwapped(cached, uid))
+            }
+            _ => {
+                self.uid = Some(uid);
+                self.tag.set_expected_uid(self.uid);
+                Ok(())
+            }
+        }
+    }
+    /// Tell the subscribers whether `keep_alive` selected the tag again.
+    fn emit_reselected(&mut self, reselected: bool) {
+        let event = if reselected {
+            session::SessionEvent::Reconnected(self.uid)
+        } else {
+            session::SessionEvent::Removed(self.uid)
+        };
+        self.subscribers.emit(event);
+    }
+    /// Set the inactivity after which `power_down_if_idle` switches the RF
+    /// field off, `None` to disable it.
+    pub fn set_idle_power_down(&mut self, idle: Option<Duration>) {
+        self.idle_power_down = idle;
+    }
+    /// Switch the RF field off if no command was sent to the tag for the
+    /// idle power-down time, so it does not heat up during long
+    /// interactive sessions. Returns whether the field was switched off.
+    ///
+    /// Like `keep_alive`, call this from the event loop of the
+    /// application. The next operation needing the tag switches the field
+    /// back on and selects it again, see `wake`.
+    pub fn power_down_if_idle(&mut self) -> Result<bool> {
+        let idle = match self.idle_power_down {
+            Some(idle) if !self.powered_down => idle,
+            _ => return Ok(false),
+        };
+        if self.last_activity.elapsed() < idle {
+            return Ok(false);
+        }
+        debug!("Tag idle, switching the field off");
+        self.tag
+            .device
+            .set_property_bool(nfc1::Property::ActivateField, false)?;
+        self.powered_down = true;
+        Ok(true)
+    }
+    /// End the RF session to let the tag be removed mid-edit, keeping the
+    /// cache and its modified blocks: deactivate the tag with
+    /// `Completion` and switch the field off.
+    ///
+    /// `resume` selects the tag again once it is back, as does the next
+    /// operation needing it. Blocks changed meanwhile by another reader
+    /// are found by `reconcile`.
+    pub fn suspend(&mut self) -> Result<()> {
+        if self.powered_down {
+            return Ok(());
+        }
+        debug!("Suspending the session, switching the field off");
+        // The tag does not answer `Completion`.
+        let _ = self.tag.device.initiator_transceive_bytes(
+            &iso14443b2::completion(),
+            0,
+            Timeout::Default,
+        );
+        self.tag
+            .device
+            .set_property_bool(nfc1::Property::ActivateField, false)?;
+        self.powered_down = true;
+        Ok(())
+    }
+    /// Switch the field back on after `suspend` and select the tag again,
+    /// checking its UID.
+    ///
+    /// Fails with `TagSwapped` if another tag answers in its place, and
+    /// like the selection if no tag is in the field yet. The session stays
+    /// suspended on failure, so `resume` can be called again.
+    pub fn resume(&mut self) -> Srix4kResult<()> {
+        self.wake()
+    }
+    /// Switch the RF field back on if it was powered down and select the
+    /// tag again, then record the activity.
+    ///
+    /// Fails with `TagSwapped` if another tag answers in its place, since
+    /// the cached blocks are not its own. The field stays marked as
+    /// powered down on failure, so the next operation tries again.
+    fn wake(&mut self) -> Srix4kResult<()> {
+        if self.powered_down {
+            debug!("Switching the field on, selecting the tag again");
+            self.tag
+                .device
+                .set_property_bool(nfc1::Property::ActivateField, true)?;
+            self.tag.reselect()?;
+            self.powered_down = false;
+        }
+        self.last_activity = Instant::now();
+        Ok(())
+    }
+    /// Read a block of the tag, waking it up first.
+    fn read_block(&mut self, block_address: u8) -> CachedResult<u32> {
+        let command = Command::ReadBlock(block_address);
+        self.wake().context(command)?;
+        let block_data = self.tag.read_block(block_address).context(command)?;
+        let saved = self.persisted.get(&block_address).copied();
+        if saved.is_some_and(|saved| saved != block_data) {
+            debug!("Block {:#04X}: persisted data is stale", block_address);
+            self.persisted.remove(&block_address);
+        }
+        if self.is_watched(block_address) {
+            let old = self.watched_data.insert(block_address, block_data);
+            if let Some(old) = old.filter(|&old| old != block_data) {
+                self.notify(WatchEvent::Changed {
+                    address: block_address,
+                    old,
+                    new: block_data,
+                });
+            }
+        }
+        Ok(block_data)
+    }
+    /// Whether a watchpoint of `watch` covers `block_address`.
+    fn is_watched(&self, block_address: u8) -> bool {
+        self.watchpoints
+            .iter()
+            .any(|(blocks, _)| blocks.contains_address(block_address as usize))
+    }
+    /// Emit the `Staged` event of the block handed out by a `_mut` getter,
+    /// if it was modified since.
+    fn notify_staged(&mut self) {
+        let (address, before) = match self.staged.take() {
+            Some(staged) => staged,
+            None => return,
+        };
+        let block_data = if address as usize == self.layout.system_addr() {
+            self.system
+        } else {
+            self.eeprom[address as usize]
+        };
+        if let Some((_, data)) = block_data.filter(|&(_, data)| data != before)
+        {
+            self.notify(WatchEvent::Staged { address, data });
+        }
+    }
+    /// Call the watchpoints covering the block of `event`.
+    fn notify(&mut self, event: WatchEvent) {
+        let address = match event {
+            WatchEvent::Changed { address, .. }
+            | WatchEvent::Staged { address, .. } => address,
+        };
+        trace!("Watched block {:#04X}: {:?}", address, event);
+        for (blocks, callback) in &mut self.watchpoints {
+            if blocks.contains_address(address as usize) {
+                callback(event);
+            }
+        }
     }
 }
 
+#[cfg(feature = "nfc")]
+impl<'a> Srix4kCached<'a> {
+    /// Connected tag.
+    pub fn tag(&self) -> &Srix4k<'a> {
+        &self.tag
+    }
+    /// Connected tag. Blocks written through it bypass the cache, which
+    /// keeps their previous data.
+    pub fn tag_mut(&mut self) -> &mut Srix4k<'a> {
+        &mut self.tag
+    }
+    /// Release the connected tag, dropping the cache and its modified
+    /// blocks.
+    pub fn into_tag(self) -> Srix4k<'a> {
+        self.tag
+    }
+}
+
+#[cfg(feature = "nfc")]
 impl Srix4kCached<'_> {
     /// Get specified block.
-    pub fn eeprom_get(&mut self, i: usize) -> Result<u32> {
-        match self.eeprom[i] {
+    ///
+    /// Fails with `InvalidArgument` if the block is outside the memory map.
+    pub fn eeprom_get(&mut self, i: usize) -> CachedResult<u32> {
+        self.notify_staged();
+        self.expire_stale(i);
+        match *self.eeprom.get(i).ok_or(CachedError::invalid_block(i))? {
             Some(block_data) => Ok(block_data.1),
             None => {
-                let block_data = self.tag.send_read_block(i as u8)?;
+                let block_data = self.read_block(i as u8)?;
                 self.eeprom[i] = Some((block_data, block_data));
+                self.read_at[i] = Instant::now();
                 Ok(block_data)
             }
         }
     }
     /// Get specified block mut.
-    pub fn eeprom_get_mut(&mut self, i: usize) -> Result<&mut u32> {
+    ///
+    /// Fails with `InvalidArgument` if the block is outside the memory map,
+    /// or in an irreversible region in safe mode.
+    pub fn eeprom_get_mut(&mut self, i: usize) -> CachedResult<&mut u32> {
+        self.notify_staged();
+        if i >= self.eeprom.len() {
+            return Err(CachedError::invalid_block(i));
+        }
+        self.check_safe_mode(i)?;
+        self.expire_stale(i);
         if self.eeprom[i].is_none() {
-            let block_data = self.tag.send_read_block(i as u8)?;
+            let block_data = self.read_block(i as u8)?;
             self.eeprom[i as usize] = Some((block_data, block_data));
+            self.read_at[i] = Instant::now();
+        }
+        if self.is_watched(i as u8) {
+            self.staged = Some((i as u8, self.eeprom[i].unwrap().1));
         }
 
         Ok(&mut self.eeprom[i as usize].as_mut().unwrap().1)
     }
+    /// Get the block at `block_address`, an EEPROM block or the system
+    /// block, like `eeprom_get` and `system_get`.
+    pub fn block_get(
+        &mut self,
+        block_address: mem::BlockAddr,
+    ) -> CachedResult<u32> {
+        if block_address == mem::BlockAddr::SYSTEM {
+            self.system_get()
+        } else {
+            self.eeprom_get(block_address.index())
+        }
+    }
+    /// Get the block at `block_address` mut, like `eeprom_get_mut` and
+    /// `system_get_mut`.
+    pub fn block_get_mut(
+        &mut self,
+        block_address: mem::BlockAddr,
+    ) -> CachedResult<&mut u32> {
+        if block_address == mem::BlockAddr::SYSTEM {
+            self.system_get_mut()
+        } else {
+            self.eeprom_get_mut(block_address.index())
+        }
+    }
+    /// Get the block at `index` of a region, e.g. `GenericIndex::at(2)`
+    /// for the 3rd *EEPROM* block.
+    ///
+    /// Fails with `InvalidArgument` if the block is outside the memory
+    /// map of the tag.
+    pub fn region_get<I: mem::RegionIndex>(
+        &mut self,
+        index: I,
+    ) -> CachedResult<u32> {
+        self.eeprom_get(index.address())
+    }
+    /// Get the block at `index` of a region mut, see `region_get`.
+    pub fn region_get_mut<I: mem::RegionIndex>(
+        &mut self,
+        index: I,
+    ) -> CachedResult<&mut u32> {
+        self.eeprom_get_mut(index.address())
+    }
     /// Get the System OTP bits.
-    pub fn system_get(&mut self) -> Result<u32> {
+    pub fn system_get(&mut self) -> CachedResult<u32> {
+        self.notify_staged();
         match self.system {
             Some(system) => Ok(system.1),
             None => {
-                let system =
-                    self.tag.send_read_block(mem::SYSTEM_ADDR as u8)?;
+                let system = self.read_block(self.layout.system_addr() as u8)?;
                 self.system = Some((system, system));
                 Ok(system)
             }
         }
     }
+    /// Get the System OTP bits as a typed view, see `system`.
+    pub fn system_block(&mut self) -> CachedResult<system::SystemBlock> {
+        self.system_get().map(system::SystemBlock::from)
+    }
     /// Get the System OTP bits mut.
-    pub fn system_get_mut(&mut self) -> Result<&mut u32> {
+    ///
+    /// `sync` refuses to write the modified bits until acknowledged with
+    /// `acknowledge_system_write`. Fails with `InvalidArgument` in safe
+    /// mode.
+    pub fn system_get_mut(&mut self) -> CachedResult<&mut u32> {
+        self.notify_staged();
+        self.check_safe_mode(self.layout.system_addr())?;
+        let address = self.layout.system_addr() as u8;
         if self.system.is_none() {
-            let system = self.tag.send_read_block(mem::SYSTEM_ADDR as u8)?;
+            let system = self.read_block(address)?;
             self.system = Some((system, system));
         }
+        if self.is_watched(address) {
+            self.staged = Some((address, self.system.unwrap().1));
+        }
 
         Ok(&mut self.system.as_mut().unwrap().1)
     }
     /// Get the UID.
-    pub fn uid_get(&mut self) -> Result<u64> {
+    pub fn uid_get(&mut self) -> CachedResult<u64> {
         match self.uid {
             Some(uid) => Ok(uid),
             None => {
-                let uid = self.tag.send_get_uid()?;
+                self.wake().context(Command::GetUid)?;
+                let uid = self.tag.send_get_uid().context(Command::GetUid)?;
                 self.uid = Some(uid);
+                self.tag.set_expected_uid(self.uid);
                 Ok(uid)
             }
         }
     }
     /// Write modified data to the tag and sync the cache.
-    pub fn sync(&mut self) -> Result<()> {
-        debug!("Syncing tag {}", self.tag.device.name());
-        for (block_address, block_data) in self.eeprom.iter_mut().enumerate() {
+    ///
+    /// Blocks are written in address order, unless ordered by
+    /// `set_sync_order`. Fails on the first block that cannot be written,
+    /// or with `WriteThrough` read back, leaving it and the next ones
+    /// modified, and with `InvalidArgument`, before any block is written,
+    /// if the modified system block was not acknowledged, see
+    /// `system_set`.
+    pub fn sync(&mut self) -> CachedResult<()> {
+        self.sync_blocks(&range::BlockSet::all())
+    }
+    /// Write the modified blocks of `blocks` to the tag, like `sync`. The
+    /// other modified blocks stay in the cache.
+    ///
+    /// Fails with `InvalidArgument`, before any block is written, if
+    /// `blocks` splits the modified blocks of a group of
+    /// `add_sync_group`.
+    pub fn sync_blocks(
+        &mut self,
+        blocks: &range::BlockSet,
+    ) -> CachedResult<()> {
+        self.sync_planned(blocks, false)
+            .map_err(|errors| errors.first())
+    }
+    /// Write the modified blocks of `blocks` to the tag, going on after
+    /// the blocks that fail, which stay modified, and return all their
+    /// failures. The blocks of a group of `add_sync_group` not written yet
+    /// when one of them fails are skipped, and stay modified too.
+    ///
+    /// Fails with a single error, before any block is written, if
+    /// `blocks` splits a group, like `sync_blocks`, or if the tag cannot be
+    /// woken up or the journal written.
+    pub fn sync_continue_on_error(
+        &mut self,
+        blocks: &range::BlockSet,
+    ) -> std::result::Result<(), SyncErrors> {
+        self.sync_planned(blocks, true)
+    }
+    /// With `WriteThrough`, write the modified blocks like `sync`, e.g.
+    /// after modifying them through the `_mut` getters. With `WriteBack`
+    /// they are left for `sync`.
+    ///
+    /// A block that fails stays modified, and is written again by the
+    /// next `flush`, `eeprom_set` or `system_set` unless reverted.
+    pub fn flush(&mut self) -> CachedResult<()> {
+        self.notify_staged();
+        if self.policy == CachePolicy::WriteThrough && self.is_modified() {
+            self.sync()?;
+        }
+        Ok(())
+    }
+    /// Discard the modifications of the blocks of `blocks` not synced,
+    /// setting them back to their data on the tag, e.g. after a write
+    /// failed or to drop a system block edit not acknowledged.
+    pub fn revert(&mut self, blocks: &range::BlockSet) {
+        self.notify_staged();
+        for block_address in blocks {
+            let block_data = if block_address == mem::BlockAddr::SYSTEM {
+                self.system_acknowledged = false;
+                self.system.as_mut()
+            } else {
+                match self.eeprom.get_mut(block_address.index()) {
+                    Some(block_data) => block_data.as_mut(),
+                    None => None,
+                }
+            };
             if let Some((original, edited)) = block_data {
-                // Write data only if it changed.
                 if original != edited {
-                    self.tag.send_write_block(block_address as u8, *edited)?;
-                    *original = *edited;
+                    debug!("Reverting block {:#04X}", block_address.get());
+                    *edited = *original;
                 }
             }
         }
-        if let Some((original, edited)) = self.system.as_mut() {
-            // Write data only if it changed.
-            if original != edited {
-                self.tag.send_write_block(mem::SYSTEM_ADDR as u8, *edited)?;
-                *original = *edited;
-            }
+    }
+    /// Write the modified blocks of `blocks`, stopping at the first
+    /// failure unless `continue_on_error`.
+    fn sync_planned(
+        &mut self,
+        blocks: &range::BlockSet,
+        continue_on_error: bool,
+    ) -> std::result::Result<(), SyncErrors> {
+        debug!("Syncing tag {}", self.tag.device.name());
+        self.notify_staged();
+        self.check_groups(blocks)?;
+        let mut planned = self.planned(blocks);
+        let system_addr = self.layout.system_addr();
+        if !self.system_acknowledged
+            && planned.iter().any(|entry| entry.address as usize == system_addr)
+        {
+            warn!("Writing the system block is not acknowledged");
+            return Err(CachedError::invalid_block(system_addr).into());
         }
+        if let Some(ref compare) = self.sync_order {
+            planned.sort_by(|a, b| compare(a, b));
+        }
+        let write = match planned.first() {
+            Some(entry) => Command::WriteBlock(entry.address, entry.new),
+            None => return Ok(()),
+        };
+        self.wake().context(write)?;
+        let mut journal = match self.journal {
+            Some(ref path) => match journal::Journal::create(path, &planned) {
+                Ok(journal) => Some(journal),
+                Err(err) => {
+                    warn!("Journal {}: {}", path.display(), err);
+                    Err(nfc1::Error::Io).context(write)?
+                }
+            },
+            None => None,
+        };
+        let result = traced!("sync", {}, SyncErrors, {
+            let verify = self.policy == CachePolicy::WriteThrough;
+            let pacing = self.write_pacing;
+            let mut last_write: Option<Instant> = None;
+            let mut pace = || {
+                if let Some(last) = last_write {
+                    if let Some(wait) = pacing.checked_sub(last.elapsed()) {
+                        thread::sleep(wait);
+                    }
+                }
+                last_write = Some(Instant::now());
+            };
 
+            let mut errors = Vec::new();
+            let mut skipped = range::BlockSet::EMPTY;
+            for entry in &planned {
+                if skipped.contains_address(entry.address as usize) {
+                    let address = entry.address;
+                    warn!("Block {:#04X}: skipped with its group", address);
+                    continue;
+                }
+                pace();
+                if let Err(err) = self.write_entry(entry, verify) {
+                    if !continue_on_error {
+                        return Err(err.into());
+                    }
+                    warn!("{}", err);
+                    errors.push(err);
+                    for group in &self.sync_groups {
+                        if group.contains_address(entry.address as usize) {
+                            skipped = skipped | *group;
+                        }
+                    }
+                    continue;
+                }
+                self.mark_synced(entry.address);
+                if let Some(ref mut journal) = journal {
+                    if let Err(err) = journal.complete(entry.address) {
+                        warn!("Journal: {}", err);
+                    }
+                }
+            }
+
+            match SyncErrors::new(errors) {
+                Some(errors) => Err(errors),
+                None => Ok(()),
+            }
+        });
+        // Failed writes stay modified in the cache and are returned: only
+        // a crash leaves blocks to check.
+        if let Some(journal) = journal {
+            if let Err(err) = journal.finish() {
+                warn!("Journal: {}", err);
+            }
+        }
+        self.save_persisted();
+        let remaining = self.planned(blocks).len();
+        self.subscribers.emit(session::SessionEvent::SyncCompleted {
+            written: planned.len().saturating_sub(remaining),
+            remaining,
+        });
+        result
+    }
+    /// Set specified block. With `WriteThrough` it is written right away.
+    ///
+    /// Fails with `InvalidArgument` if the block is outside the memory map.
+    pub fn eeprom_set(
+        &mut self,
+        i: usize,
+        block_data: u32,
+    ) -> CachedResult<()> {
+        *self.eeprom_get_mut(i)? = block_data;
+        self.flush()
+    }
+    /// Set the System OTP bits. With `WriteThrough` they are written right
+    /// away.
+    ///
+    /// Clearing a lock bit cannot be undone, hence the acknowledgment.
+    pub fn system_set(
+        &mut self,
+        system: u32,
+        acknowledgment: system::IrreversibleWrite,
+    ) -> CachedResult<()> {
+        *self.system_get_mut()? = system;
+        self.acknowledge_system_write(acknowledgment);
+        self.flush()
+    }
+    /// Lock the blocks of `locks` in the system block, see `system_set`.
+    pub fn lock_blocks(
+        &mut self,
+        locks: system::LockBits,
+        acknowledgment: system::IrreversibleWrite,
+    ) -> CachedResult<()> {
+        let mut system = self.system_block()?;
+        system.lock(locks);
+        self.system_set(system.bits(), acknowledgment)
+    }
+    /// Let `sync` write the system block modified through
+    /// `system_get_mut`, until it is written.
+    pub fn acknowledge_system_write(
+        &mut self,
+        _acknowledgment: system::IrreversibleWrite,
+    ) {
+        self.system_acknowledged = true;
+    }
+    /// Writes the next `sync` plans, in address order: one per block
+    /// whose cached data differs from the tag, with its final data.
+    ///
+    /// A block staged again only keeps its last data, and one set back to
+    /// its data on the tag is not written. The order of `set_sync_order`
+    /// is applied by `sync` only.
+    pub fn planned_writes(&self) -> Vec<JournalEntry> {
+        self.planned(&range::BlockSet::all())
+    }
+    /// Differences between the blocks as first read since connecting and
+    /// their cached data, synced or not, in address order, e.g. for an
+    /// audit log of the session.
+    ///
+    /// Blocks written through `tag_mut` are not seen.
+    pub fn changes_since_connect(&self) -> dump::DumpDiff {
+        let system_addr = self.layout.system_addr();
+        let blocks = self
+            .eeprom
+            .iter()
+            .enumerate()
+            .chain(std::iter::once((system_addr, &self.system)))
+            .filter_map(|(block_address, block_data)| {
+                let (original, edited) = (*block_data)?;
+                let address = block_address as u8;
+                let old = match self.connect_data.get(&address) {
+                    Some(&old) => old,
+                    None => original,
+                };
+                if old == edited {
+                    return None;
+                }
+                Some(dump::BlockDiff {
+                    address,
+                    old,
+                    new: edited,
+                })
+            })
+            .collect();
+        dump::DumpDiff { uid: None, blocks }
+    }
+    /// Fail with `InvalidArgument` if block `i` is in an irreversible
+    /// region in safe mode.
+    fn check_safe_mode(&self, i: usize) -> CachedResult<()> {
+        let irreversible = self
+            .layout
+            .region(i)
+            .is_some_and(|region| region.is_irreversible());
+        if self.safe_mode && irreversible {
+            warn!("Safe mode: refusing to modify block {:#04X}", i);
+            return Err(CachedError::invalid_block(i));
+        }
+        Ok(())
+    }
+    /// Drop block `i` from the cache if it is a counter or OTP block, not
+    /// modified, read longer than the read TTL ago.
+    fn expire_stale(&mut self, i: usize) {
+        let ttl = match self.read_ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+        let volatile = self.layout.countdown().contains(&i)
+            || self.layout.otp().contains(&i);
+        if let Some(Some((original, edited))) = self.eeprom.get(i) {
+            if volatile
+                && original == edited
+                && self.read_at[i].elapsed() >= ttl
+            {
+                trace!("Block {:#04X} is stale, reading it again", i);
+                self.eeprom[i] = None;
+            }
+        }
+    }
+    /// Fail with `InvalidArgument` on the first modified block of a group
+    /// outside `blocks` while others of the group are in.
+    fn check_groups(&self, blocks: &range::BlockSet) -> CachedResult<()> {
+        let modified: range::BlockSet = self
+            .planned(&range::BlockSet::all())
+            .iter()
+            .filter_map(|entry| mem::BlockAddr::new(entry.address as usize))
+            .collect();
+        for group in &self.sync_groups {
+            let pending = modified & *group;
+            let split = pending - *blocks;
+            if split != pending {
+                if let Some(block_address) = split.iter().next() {
+                    warn!(
+                        "Block {:#04X} is left out of its group",
+                        block_address.index()
+                    );
+                    return Err(CachedError::invalid_block(
+                        block_address.index(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Whether a cached block was modified since the last sync.
+    fn is_modified(&self) -> bool {
+        !self.planned(&range::BlockSet::all()).is_empty()
+    }
+    /// Writes of the blocks of `blocks` modified since the last sync, in
+    /// address order.
+    fn planned(&self, blocks: &range::BlockSet) -> Vec<JournalEntry> {
+        let system_addr = self.layout.system_addr();
+        self.eeprom
+            .iter()
+            .enumerate()
+            .chain(std::iter::once((system_addr, &self.system)))
+            .filter(|&(block_address, _)| {
+                blocks.contains_address(block_address)
+            })
+            .filter_map(|(block_address, block_data)| match block_data {
+                Some((original, edited))
+                    if original != edited
+                        && self.persisted.get(&(block_address as u8))
+                            != Some(edited) =>
+                {
+                    Some(JournalEntry {
+                        address: block_address as u8,
+                        old: *original,
+                        new: *edited,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /// Write a planned block, reading it back if `verify`.
+    fn write_entry(
+        &mut self,
+        entry: &JournalEntry,
+        verify: bool,
+    ) -> CachedResult<()> {
+        let write = Command::WriteBlock(entry.address, entry.new);
+        if entry.address as usize == self.layout.system_addr() {
+            // `sync` only plans it once acknowledged.
+            let acknowledgment = system::acknowledge_permanent_damage();
+            self.tag
+                .send_write_system(entry.new, acknowledgment)
+                .context(write)?;
+        } else {
+            self.tag
+                .send_write_block(entry.address, entry.new)
+                .context(write)?;
+        }
+        if verify
+            && self
+                .tag
+                .read_block(entry.address)
+                .context(Command::ReadBlock(entry.address))?
+                != entry.new
+        {
+            warn!("Block {:#04X}: write not verified", entry.address);
+            return Err(nfc1::Error::RfTransmissionError).context(write);
+        }
         Ok(())
     }
+    /// Record that the cached block was written to the tag.
+    fn mark_synced(&mut self, block_address: u8) {
+        let block_data = if block_address as usize == self.layout.system_addr()
+        {
+            self.system_acknowledged = false;
+            self.system.as_mut()
+        } else {
+            self.eeprom[block_address as usize].as_mut()
+        };
+        if let Some((original, edited)) = block_data {
+            self.connect_data.entry(block_address).or_insert(*original);
+            *original = *edited;
+        }
+    }
+    /// Save the blocks known to be on the tag, see `load_persisted`.
+    fn save_persisted(&mut self) {
+        let (dir, uid) = match (&self.persist, self.uid) {
+            (Some(dir), Some(uid)) => (dir, uid),
+            _ => return,
+        };
+        let system_addr = self.layout.system_addr();
+        let cached = self
+            .eeprom
+            .iter()
+            .enumerate()
+            .chain(std::iter::once((system_addr, &self.system)))
+            .filter_map(|(block_address, block_data)| {
+                block_data.map(|(original, _)| (block_address as u8, original))
+            });
+        self.persisted.extend(cached);
+        let blocks: Vec<(u8, u32)> = self
+            .persisted
+            .iter()
+            .map(|(&address, &block_data)| (address, block_data))
+            .collect();
+        if let Err(err) = persist::save(dir, uid, &blocks) {
+            warn!("Persisted blocks in {}: {}", dir.display(), err);
+        }
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl std::fmt::Debug for Srix4kCached<'_> {
+    /// Reader, chip, UID if known and number of cached and modified
+    /// blocks, the system block included.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let blocks = self.eeprom.iter().chain(std::iter::once(&self.system));
+        let (cached, dirty) =
+            blocks.flatten().fold((0, 0), |(cached, dirty), block| {
+                (cached + 1, dirty + (block.0 != block.1) as usize)
+            });
+        f.debug_struct("Srix4kCached")
+            .field("device", &self.tag.device_name)
+            .field("chip", &self.layout.name())
+            .field("uid", &self.uid.map(|uid| Uid(uid).to_string()))
+            .field("cached", &cached)
+            .field("dirty", &dirty)
+            .finish_non_exhaustive()
+    }
 }
@@ -1,14 +1,486 @@
 extern crate srix4k;
 extern crate nfc1;
 
-use nfc1::{Result};
-use srix4k::{Srix4kCached, mem};
+use std::env;
+use std::error::Error;
+use std::fs;
+use srix4k::{Srix4k, Srix4kCached, mem, range};
+use srix4k::alias::{self, Aliases};
+use srix4k::config::DeviceConfig;
+use srix4k::dump::Srix4kDump;
+use srix4k::inventory::{self, Inventory};
+use srix4k::mqtt::{Client, Publisher};
+use srix4k::notes::Notes;
+use srix4k::otlp::Exporter;
+use srix4k::scan::ScanOptions;
+use srix4k::schema::Schema;
+use srix4k::selftest;
+use srix4k::server::Server;
+use srix4k::signature;
+use srix4k::webhook::Webhook;
+
+/// Address the HTTP server listens on when `--listen` is not given.
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+/// Reader configuration waiting for a tag to enter the field.
+const WAIT_FOR_TAG: DeviceConfig = DeviceConfig {
+    infinite_select: Some(true),
+    command_timeout: None,
+    com_timeout: None,
+    atr_timeout: None,
+    activate_field: None,
+    handle_crc: None,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Commands not needing the reader.
+    match args.first().map(String::as_str) {
+        Some("alias") => return alias(&args[1..]),
+        Some("db") => return db(&args[1..]),
+        Some("diff") => return diff(&args[1..]),
+        Some("verify") => return verify(&args[1..]),
+        _ => {}
+    }
 
-fn main() -> Result<()> {
     let mut context = nfc1::Context::new()?;
-	let mut device = context.open()?;
-    device.set_property_bool(nfc1::Property::InfiniteSelect, true)?;
-    let mut tag = Srix4kCached::connect_from(device)?;
+    let device = context.open()?;
+
+    match args.first().map(String::as_str) {
+        Some("serve") => serve(device, &args[1..]),
+        Some("mqtt") => mqtt(device, &args[1..]),
+        Some("dump") => dump(device, &args[1..]),
+        Some("fingerprint") => fingerprint(device),
+        Some("info") => info(device, &args[1..]),
+        Some("probe") => probe(device),
+        Some("scan") => scan(device, &args[1..]),
+        Some("selftest") => selftest(device),
+        #[cfg(feature = "dbus")]
+        Some("dbus") => dbus(device),
+        Some(command) => Err(format!("unknown command `{}`", command).into()),
+        None => demo(device),
+    }
+}
+
+/// `srix4k serve [--listen <address>] [--otlp <endpoint>]
+/// [--webhook <url>]... [--block <blocks>]...`
+fn serve(device: nfc1::Device, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut listen = DEFAULT_LISTEN;
+    let mut exporter = None;
+    let mut webhooks = Vec::new();
+    let mut blocks = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing {} value", arg));
+        match arg.as_str() {
+            "--listen" => listen = value()?,
+            "--otlp" => exporter = Some(Exporter::new(value()?)?),
+            "--webhook" => webhooks.push(Webhook::new(value()?)?),
+            "--block" => blocks.extend(parse_blocks(value()?)?),
+            _ => return Err(format!("unknown argument `{}`", arg).into()),
+        }
+    }
+
+    let mut server = Server::bind(device, listen)?;
+    for mut webhook in webhooks {
+        webhook.set_blocks(blocks.clone());
+        server.add_webhook(webhook);
+    }
+    if let Some(exporter) = exporter {
+        server.set_exporter(exporter);
+    }
+    println!("listening on http://{}", server.local_addr()?);
+    server.run()?;
+
+    Ok(())
+}
+
+/// `srix4k mqtt --broker <address> [--topic <prefix>] [--client-id <id>]
+/// [--username <username> --password <password>] [--block <blocks>]...`
+fn mqtt(device: nfc1::Device, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut broker = None;
+    let mut topic = None;
+    let mut client_id = "srix4k";
+    let mut username = None;
+    let mut password = None;
+    let mut blocks = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing {} value", arg));
+        match arg.as_str() {
+            "--broker" => broker = Some(value()?),
+            "--topic" => topic = Some(value()?),
+            "--client-id" => client_id = value()?,
+            "--username" => username = Some(value()?),
+            "--password" => password = Some(value()?),
+            "--block" => blocks.extend(parse_blocks(value()?)?),
+            _ => return Err(format!("unknown argument `{}`", arg).into()),
+        }
+    }
+
+    let broker = broker.ok_or("missing --broker address")?;
+    let credentials = match (username, password) {
+        (Some(username), Some(password)) => {
+            Some((username.as_str(), password.as_str()))
+        }
+        (None, None) => None,
+        _ => return Err("--username and --password go together".into()),
+    };
+    let client = Client::connect(broker.as_str(), client_id, credentials)?;
+    let mut publisher = Publisher::new(device, client)?;
+    if let Some(topic) = topic {
+        publisher.set_topic_prefix(topic);
+    }
+    publisher.set_blocks(blocks);
+    println!("publishing tag events to {}", broker);
+    publisher.run()?;
+
+    Ok(())
+}
+
+/// `srix4k dbus`
+#[cfg(feature = "dbus")]
+fn dbus(device: nfc1::Device) -> Result<(), Box<dyn Error>> {
+    let mut service = srix4k::dbus_service::Service::new(device)?;
+    println!(
+        "serving {} on the session bus",
+        srix4k::dbus_service::BUS_NAME
+    );
+    service.run()?;
+
+    Ok(())
+}
+
+/// `srix4k alias [<uid> [<name>]]`
+///
+/// Lists the aliases, prints the alias of `uid` or names it, an empty
+/// name removing its alias.
+fn alias(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = alias::default_path().ok_or("no aliases file, set HOME")?;
+    let mut aliases = Aliases::load(&path)?;
+    match args {
+        [] => {
+            for (uid, name) in aliases.iter() {
+                println!("{} {}", uid, name);
+            }
+        }
+        [uid] => match aliases.get(u64::from_str_radix(uid, 16)?) {
+            Some(name) => println!("{}", name),
+            None => return Err(format!("no alias for {}", uid).into()),
+        },
+        [uid, name] => {
+            aliases.set(u64::from_str_radix(uid, 16)?, name);
+            aliases.save(&path)?;
+        }
+        _ => return Err("usage: srix4k alias [<uid> [<name>]]".into()),
+    }
+
+    Ok(())
+}
+
+/// `srix4k db export --csv [--dir <dir>]`
+///
+/// Prints the inventory of the tag database, `--dir` or
+/// `inventory::default_dir`.
+fn db(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: srix4k db export --csv [--dir <dir>]";
+    let dir = match args {
+        [export, csv] if export == "export" && csv == "--csv" => {
+            inventory::default_dir().ok_or("no tag database, set HOME")?
+        }
+        [export, csv, flag, dir]
+            if export == "export" && csv == "--csv" && flag == "--dir" =>
+        {
+            dir.into()
+        }
+        _ => return Err(usage.into()),
+    };
+    print!("{}", Inventory::scan(dir)?.to_csv(&aliases()?));
+
+    Ok(())
+}
+
+/// `srix4k diff <old> <new> [--schema <file>] [--notes <file>]`
+///
+/// Prints the blocks that differ between two dump files, decoded with the
+/// schema if any, and their notes, by default the sidecar notes of `new`.
+fn diff(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage =
+        "usage: srix4k diff <old> <new> [--schema <file>] [--notes <file>]";
+    let (old, new, options) = match args {
+        [old, new, options @ ..] => (old, new, options),
+        _ => return Err(usage.into()),
+    };
+    let (schema, notes) = annotations(options, Some(new), usage)?;
+    let old = Srix4kDump::parse(&fs::read(old)?)?;
+    let new = Srix4kDump::parse(&fs::read(new)?)?;
+    let diff = old.diff(&new);
+    if let Some((old, new)) = diff.uid {
+        println!("uid: {:016X} -> {:016X}", old, new);
+    }
+    for block in &diff.blocks {
+        print!(
+            "{:#04X}: {:#010X} -> {:#010X}",
+            block.address, block.old, block.new
+        );
+        if let (Some(old), Some(new)) = (
+            schema.annotate(block.address, block.old),
+            schema.annotate(block.address, block.new),
+        ) {
+            print!("  {} -> {}", old, new);
+        }
+        println!("{}", note(&notes, block.address));
+    }
+
+    Ok(())
+}
+
+/// `srix4k verify <file> --key <public key>...`
+///
+/// Checks that the signed dump file is signed by one of the keys, given as
+/// 64 hexadecimal digits, and prints the key that signed it.
+fn verify(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: srix4k verify <file> --key <public key>...";
+    let (file, options) = match args {
+        [file, options @ ..] if !options.is_empty() => (file, options),
+        _ => return Err(usage.into()),
+    };
+    let mut trusted = Vec::new();
+    for option in options.chunks(2) {
+        match option {
+            [flag, key] if flag == "--key" => trusted.push(
+                signature::parse_public_key(key)
+                    .ok_or_else(|| format!("invalid public key `{}`", key))?,
+            ),
+            _ => return Err(usage.into()),
+        }
+    }
+    let public_key = verify_file(&fs::read(file)?, &trusted)?;
+    for byte in public_key.iter() {
+        print!("{:02x}", byte);
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Key of `trusted` that signed the dump file `bytes`.
+#[cfg(feature = "ed25519")]
+fn verify_file(
+    bytes: &[u8],
+    trusted: &[signature::PublicKey],
+) -> Result<signature::PublicKey, Box<dyn Error>> {
+    let verifier = signature::Ed25519Verifier;
+    Ok(signature::verify(bytes, &verifier, trusted)?.1)
+}
+
+/// Key of `trusted` that signed the dump file, which needs the ed25519
+/// feature.
+#[cfg(not(feature = "ed25519"))]
+fn verify_file(
+    _bytes: &[u8],
+    _trusted: &[signature::PublicKey],
+) -> Result<signature::PublicKey, Box<dyn Error>> {
+    Err("verify needs the ed25519 feature".into())
+}
+
+/// `--schema <file>` and `--notes <file>` of `args`, the notes defaulting
+/// to the sidecar notes of `dump`, if any.
+fn annotations(
+    args: &[String],
+    dump: Option<&String>,
+    usage: &str,
+) -> Result<(Schema, Notes), Box<dyn Error>> {
+    let mut schema = Schema::default();
+    let mut notes = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(usage)?;
+        match arg.as_str() {
+            "--schema" => schema = load_schema(value)?,
+            "--notes" => notes = Some(Notes::load(value)?),
+            _ => return Err(usage.into()),
+        }
+    }
+    let notes = match (notes, dump) {
+        (Some(notes), _) => notes,
+        (None, Some(dump)) => Notes::load_sidecar(dump)?,
+        (None, None) => Notes::default(),
+    };
+    Ok((schema, notes))
+}
+
+/// Note of the block at `address` to end its line with, if any.
+fn note(notes: &Notes, address: u8) -> String {
+    match notes.get(address) {
+        Some(note) => format!("  # {}", note.replace('\n', " ")),
+        None => String::new(),
+    }
+}
+
+/// Schema file given with `--schema`.
+#[cfg(any(feature = "schema-toml", feature = "schema-yaml"))]
+fn load_schema(path: &str) -> Result<Schema, Box<dyn Error>> {
+    Ok(Schema::load(path)?)
+}
+
+/// Schema file given with `--schema`, which needs a schema feature.
+#[cfg(not(any(feature = "schema-toml", feature = "schema-yaml")))]
+fn load_schema(_path: &str) -> Result<Schema, Box<dyn Error>> {
+    Err("--schema needs the schema-toml or schema-yaml feature".into())
+}
+
+/// Aliases of the user, none if there is no aliases file.
+fn aliases() -> Result<Aliases, Box<dyn Error>> {
+    match alias::default_path() {
+        Some(path) => Ok(Aliases::load(path)?),
+        None => Ok(Aliases::default()),
+    }
+}
+
+/// `srix4k fingerprint`
+fn fingerprint(device: nfc1::Device) -> Result<(), Box<dyn Error>> {
+    let aliases = aliases()?;
+    let mut tag = Srix4k::connect_from_configured(device, &WAIT_FOR_TAG)?;
+    let fingerprint = tag.fingerprint()?;
+    if let Some(name) = aliases.get(fingerprint.uid) {
+        println!("alias: {}", name);
+    }
+    println!("{}", fingerprint);
+
+    Ok(())
+}
+
+/// `srix4k info [--schema <file>] [--notes <file>]`
+///
+/// Prints the tag information, the blocks of the schema decoded and the
+/// blocks with a note.
+fn info(device: nfc1::Device, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: srix4k info [--schema <file>] [--notes <file>]";
+    let (schema, notes) = annotations(args, None, usage)?;
+    let aliases = aliases()?;
+    let mut tag = Srix4kCached::connect_from_configured(device, &WAIT_FOR_TAG)?;
+    let info = tag.info()?;
+    if let Some(name) = aliases.get(info.uid) {
+        println!("Alias: {}", name);
+    }
+    print!("{}", info);
+    for block in &schema.blocks {
+        let block_address = mem::BlockAddr::new(block.address as usize)
+            .ok_or("invalid block address in the schema")?;
+        let block_data = tag.block_get(block_address)?;
+        if let Some(annotation) = schema.annotate(block.address, block_data) {
+            println!("Block {:#04X}: {}", block.address, annotation);
+        }
+    }
+    for (address, _) in notes.iter() {
+        let block_address = mem::BlockAddr::new(address as usize)
+            .ok_or("invalid block address in the notes")?;
+        let block_data = tag.block_get(block_address)?;
+        println!(
+            "Block {:#04X}: {:#010X}{}",
+            address,
+            block_data,
+            note(&notes, address)
+        );
+    }
+
+    Ok(())
+}
+
+/// `srix4k dump [<file>] [--notes <file>]`
+///
+/// Reads the tag in the field, saves it to `file` if given, as a zstd
+/// archive if it ends with `.zst`, and prints its blocks with their notes,
+/// by default the sidecar notes of `file`.
+fn dump(device: nfc1::Device, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: srix4k dump [<file>] [--notes <file>]";
+    let (file, options) = match args {
+        [file, options @ ..] if !file.starts_with("--") => {
+            (Some(file), options)
+        }
+        options => (None, options),
+    };
+    let (_, notes) = annotations(options, file, usage)?;
+    let mut tag = Srix4k::connect_from_configured(device, &WAIT_FOR_TAG)?;
+    let dump = tag.dump()?;
+    if let Some(file) = file {
+        fs::write(file, dump_file(&dump, file)?)?;
+    }
+    print!("{}", notes.dump(&dump));
+
+    Ok(())
+}
+
+/// Bytes of the file at `path` of `dump`, a zstd archive for `.zst` files.
+fn dump_file(dump: &Srix4kDump, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if path.ends_with(".zst") {
+        #[cfg(feature = "zstd")]
+        return Ok(dump.serialize_compressed(0)?);
+        #[cfg(not(feature = "zstd"))]
+        return Err("`.zst` dumps need the zstd feature".into());
+    }
+    Ok(dump.serialize())
+}
+
+/// `srix4k probe`
+fn probe(device: nfc1::Device) -> Result<(), Box<dyn Error>> {
+    let aliases = aliases()?;
+    let mut tag = Srix4k::connect_from_configured(device, &WAIT_FOR_TAG)?;
+    let probe = tag.probe()?;
+    println!("{}", probe);
+    if let Some(name) = probe.uid.and_then(|uid| aliases.get(uid)) {
+        println!("     Alias: {}", name);
+    }
+
+    Ok(())
+}
+
+/// `srix4k scan [--with-arguments] [--max <probes>]`
+fn scan(
+    device: nfc1::Device,
+    args: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let mut options = ScanOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--with-arguments" => options.read_only = false,
+            "--max" => {
+                options.max_probes =
+                    args.next().ok_or("missing --max value")?.parse()?;
+            }
+            _ => return Err(format!("unknown argument `{}`", arg).into()),
+        }
+    }
+
+    let mut tag = Srix4k::connect_from_configured(device, &WAIT_FOR_TAG)?;
+    for hit in tag.scan_commands(&options)? {
+        println!("{:#04X}: {:02X?}", hit.code, hit.response);
+    }
+
+    Ok(())
+}
+
+/// `srix4k selftest`
+fn selftest(mut device: nfc1::Device) -> Result<(), Box<dyn Error>> {
+    let capabilities = selftest::probe_capabilities(&mut device);
+    println!("{}", capabilities);
+    if !capabilities.is_usable() {
+        return Err("the reader cannot read ISO14443B-2 SR tags".into());
+    }
+
+    Ok(())
+}
+
+/// Parse a block range expression, see `srix4k::range`.
+fn parse_blocks(expression: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let blocks = range::parse(expression)?;
+    Ok(blocks.into_iter().map(|block_address| block_address.get()).collect())
+}
+
+fn demo(device: nfc1::Device) -> Result<(), Box<dyn Error>> {
+    let mut tag =
+        Srix4kCached::connect_from_configured(device, &WAIT_FOR_TAG)?;
 
     println!("uid: 0x{:X}", tag.uid_get()?);
     let block00 = tag.eeprom_get_mut(mem::EEPROM.start)?;
@@ -0,0 +1,180 @@
+//! Counters of a reader station, rendered in the Prometheus text format,
+//! and latency statistics of the commands sent to tags.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Upper bounds in seconds of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 9] =
+    [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+/// Activity of a reader since the service started.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// Tags that entered the field.
+    tags_seen: u64,
+    /// Blocks read from tags.
+    blocks_read: u64,
+    /// Blocks written to tags.
+    blocks_written: u64,
+    /// Failed operations on tags.
+    errors: u64,
+    /// Latency of the operations.
+    latency: Histogram,
+}
+
+/// Latency histogram over `LATENCY_BUCKETS`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Histogram {
+    /// Observations per bucket, not cumulative,
+    /// the last one counting those above every bound.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    /// Total of the observations in seconds.
+    sum: f64,
+}
+
+/// Latency of every command sent to a tag, failed ones included,
+/// per command type.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatencyStats {
+    /// `ReadBlock` commands.
+    pub read_block: Histogram,
+    /// `WriteBlock` commands.
+    pub write_block: Histogram,
+    /// `GetUID` commands.
+    pub get_uid: Histogram,
+}
+
+impl Histogram {
+    /// Record an observation.
+    pub fn observe(&mut self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+    }
+    /// Observations per bucket of `LATENCY_BUCKETS`, not cumulative,
+    /// the last one counting those above every bound.
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+    /// Total of the observations in seconds.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+    /// Number of observations.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+    /// Average observation, `None` if there is none.
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            count => Some(Duration::from_secs_f64(self.sum / count as f64)),
+        }
+    }
+}
+
+impl LatencyStats {
+    /// `(name, histogram)` of every command type.
+    pub fn commands(&self) -> [(&'static str, &Histogram); 3] {
+        [
+            ("read_block", &self.read_block),
+            ("write_block", &self.write_block),
+            ("get_uid", &self.get_uid),
+        ]
+    }
+}
+
+impl Metrics {
+    /// Count a tag that entered the field.
+    pub fn tag_seen(&mut self) {
+        self.tags_seen += 1;
+    }
+    /// Count a block read from a tag.
+    pub fn block_read(&mut self) {
+        self.blocks_read += 1;
+    }
+    /// Count a block written to a tag.
+    pub fn block_written(&mut self) {
+        self.blocks_written += 1;
+    }
+    /// Count a failed operation on a tag.
+    pub fn error(&mut self) {
+        self.errors += 1;
+    }
+    /// Record how long an operation on a tag took.
+    pub fn observe_latency(&mut self, latency: Duration) {
+        self.latency.observe(latency);
+    }
+    /// `(name, description, value)` of every counter.
+    pub fn counters(&self) -> [(&'static str, &'static str, u64); 4] {
+        [
+            ("tags_seen", "Tags that entered the field.", self.tags_seen),
+            ("blocks_read", "Blocks read from tags.", self.blocks_read),
+            (
+                "blocks_written",
+                "Blocks written to tags.",
+                self.blocks_written,
+            ),
+            ("errors", "Failed operations on tags.", self.errors),
+        ]
+    }
+    /// Operations per bucket of `LATENCY_BUCKETS`, not cumulative,
+    /// the last one counting those above every bound.
+    pub fn latency_buckets(&self) -> &[u64] {
+        self.latency.buckets()
+    }
+    /// Total latency of the operations in seconds.
+    pub fn latency_sum(&self) -> f64 {
+        self.latency.sum()
+    }
+}
+
+impl fmt::Display for Metrics {
+    /// Prometheus text exposition format.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, help, value) in self.counters().iter() {
+            writeln!(f, "# HELP srix4k_{}_total {}", name, help)?;
+            writeln!(f, "# TYPE srix4k_{}_total counter", name)?;
+            writeln!(f, "srix4k_{}_total {}", name, value)?;
+        }
+
+        let name = "srix4k_operation_duration_seconds";
+        writeln!(f, "# HELP {} Duration of operations on tags.", name)?;
+        writeln!(f, "# TYPE {} histogram", name)?;
+        let mut count = 0;
+        for (bound, operations) in
+            LATENCY_BUCKETS.iter().zip(self.latency_buckets().iter())
+        {
+            count += operations;
+            writeln!(f, "{}_bucket{{le=\"{}\"}} {}", name, bound, count)?;
+        }
+        count += self.latency_buckets()[LATENCY_BUCKETS.len()];
+        writeln!(f, "{}_bucket{{le=\"+Inf\"}} {}", name, count)?;
+        writeln!(f, "{}_sum {}", name, self.latency_sum())?;
+        writeln!(f, "{}_count {}", name, count)
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    /// One line per command type with its count and mean latency.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, histogram) in self.commands().iter() {
+            match histogram.mean() {
+                Some(mean) => writeln!(
+                    f,
+                    "{}: {} commands, mean {:?}",
+                    name,
+                    histogram.count(),
+                    mean
+                )?,
+                None => writeln!(f, "{}: no commands", name)?,
+            }
+        }
+        Ok(())
+    }
+}
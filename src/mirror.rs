@@ -0,0 +1,169 @@
+//! Records mirrored in two slots of blocks, for data that must survive
+//! torn writes.
+//!
+//! Each slot holds a header block, followed by the blocks of the record:
+//!
+//! | Block | Content |
+//! | --- | --- |
+//! | 0 | sequence number (16 high bits), CRC_B of the slot (16 low bits) |
+//! | 1 to `len` | record |
+//!
+//! The CRC covers the sequence number and the record, least significant
+//! byte first. `MirroredRecord::write` writes the slot not holding the
+//! newest copy, with the next sequence number, so a torn write only ever
+//! damages the older copy. `MirroredRecord::read` returns the record of
+//! the valid slot with the newest sequence number.
+
+#[cfg(feature = "nfc")]
+use error::{CachedError, CachedResult};
+use iso14443b2;
+use mem::BlockAddr;
+#[cfg(feature = "nfc")]
+use range::BlockSet;
+#[cfg(feature = "nfc")]
+use Srix4kCached;
+
+/// Record of `len` blocks mirrored in two slots of `len + 1` blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MirroredRecord {
+    /// First block of each slot.
+    pub slots: [BlockAddr; 2],
+    /// Blocks of the record, without the header.
+    pub len: usize,
+}
+
+/// Valid copy of a record.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecordCopy {
+    /// Slot holding the copy, 0 or 1.
+    pub slot: usize,
+    /// Sequence number of the copy.
+    pub sequence: u16,
+    /// Blocks of the record.
+    pub record: Vec<u32>,
+}
+
+impl MirroredRecord {
+    /// Record of `len` blocks, in the slots starting at `first` and
+    /// `second`, which must not overlap.
+    pub fn new(
+        first: BlockAddr,
+        second: BlockAddr,
+        len: usize,
+    ) -> MirroredRecord {
+        MirroredRecord {
+            slots: [first, second],
+            len,
+        }
+    }
+    /// Addresses of the blocks of `slot`, header first.
+    pub fn blocks(&self, slot: usize) -> impl Iterator<Item = usize> {
+        let start = self.slots[slot].index();
+        start..start + self.len + 1
+    }
+    /// Blocks of a slot holding `record` with `sequence`.
+    pub fn encode(&self, sequence: u16, record: &[u32]) -> Vec<u32> {
+        let header =
+            (sequence as u32) << 16 | checksum(sequence, record) as u32;
+        let mut blocks = Vec::with_capacity(record.len() + 1);
+        blocks.push(header);
+        blocks.extend_from_slice(record);
+        blocks
+    }
+    /// Sequence number and record of the blocks of a slot, `None` if the
+    /// slot does not hold a valid copy.
+    pub fn decode(&self, blocks: &[u32]) -> Option<(u16, Vec<u32>)> {
+        let (&header, record) = blocks.split_first()?;
+        let sequence = (header >> 16) as u16;
+        if record.len() != self.len
+            || checksum(sequence, record) != header as u16
+        {
+            return None;
+        }
+        Some((sequence, record.to_vec()))
+    }
+    /// Newest valid copy among the blocks of each slot.
+    pub fn newest(&self, slots: [&[u32]; 2]) -> Option<RecordCopy> {
+        let mut copies =
+            slots.iter().enumerate().filter_map(|(slot, blocks)| {
+                self.decode(blocks).map(|(sequence, record)| RecordCopy {
+                    slot,
+                    sequence,
+                    record,
+                })
+            });
+        let first = copies.next();
+        match (first, copies.next()) {
+            (Some(first), Some(second)) if is_newer(&second, &first) => {
+                Some(second)
+            }
+            (first, second) => first.or(second),
+        }
+    }
+}
+
+/// Whether `copy` was written after `other`, sequence numbers wrapping.
+fn is_newer(copy: &RecordCopy, other: &RecordCopy) -> bool {
+    (copy.sequence.wrapping_sub(other.sequence) as i16) > 0
+}
+
+/// CRC_B of `sequence` and `record`, least significant byte first.
+fn checksum(sequence: u16, record: &[u32]) -> u16 {
+    let mut bytes = sequence.to_le_bytes().to_vec();
+    for block_data in record {
+        bytes.extend_from_slice(&block_data.to_le_bytes());
+    }
+    iso14443b2::crc_b(&bytes)
+}
+
+#[cfg(feature = "nfc")]
+impl MirroredRecord {
+    /// Read both slots, from the cache or the tag, and return the newest
+    /// valid copy, `None` if neither slot holds one.
+    pub fn read(
+        &self,
+        tag: &mut Srix4kCached,
+    ) -> CachedResult<Option<RecordCopy>> {
+        let first = self.read_slot(tag, 0)?;
+        let second = self.read_slot(tag, 1)?;
+        Ok(self.newest([&first, &second]))
+    }
+    /// Write `record` to the slot not holding the newest copy, with the
+    /// next sequence number, and sync it. Other modified blocks stay in
+    /// the cache.
+    ///
+    /// Fails with `InvalidArgument` if `record` is not `len` blocks long
+    /// or a slot is outside the memory map.
+    pub fn write(
+        &self,
+        tag: &mut Srix4kCached,
+        record: &[u32],
+    ) -> CachedResult<()> {
+        if record.len() != self.len {
+            return Err(CachedError::invalid_block(self.slots[0].index()));
+        }
+        let (slot, sequence) = match self.read(tag)? {
+            Some(newest) => (1 - newest.slot, newest.sequence.wrapping_add(1)),
+            None => (0, 0),
+        };
+        let mut blocks = BlockSet::EMPTY;
+        for (i, block_data) in
+            self.blocks(slot).zip(self.encode(sequence, record))
+        {
+            tag.eeprom_set(i, block_data)?;
+            blocks.insert(
+                BlockAddr::new(i).ok_or(CachedError::invalid_block(i))?,
+            );
+        }
+        debug!("Writing record {} to slot {}", sequence, slot);
+        tag.sync_blocks(&blocks)
+    }
+    /// Blocks of `slot`.
+    fn read_slot(
+        &self,
+        tag: &mut Srix4kCached,
+        slot: usize,
+    ) -> CachedResult<Vec<u32>> {
+        self.blocks(slot).map(|i| tag.eeprom_get(i)).collect()
+    }
+}
@@ -0,0 +1,124 @@
+//! Tracking of the tag in the field of a reader.
+
+use Srix4k;
+
+/// Change of the tag in the field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagEvent {
+    /// `Arrival(uid)`
+    /// A tag entered the field and has been selected.
+    Arrival(u64),
+    /// `Removal(uid)`
+    /// The selected tag left the field.
+    Removal(u64),
+}
+
+impl TagEvent {
+    /// Name of the event, `arrival` or `removal`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagEvent::Arrival(_) => "arrival",
+            TagEvent::Removal(_) => "removal",
+        }
+    }
+    /// UID of the tag the event refers to.
+    pub fn uid(&self) -> u64 {
+        match *self {
+            TagEvent::Arrival(uid) | TagEvent::Removal(uid) => uid,
+        }
+    }
+}
+
+/// Reader that keeps track of the tag in its field.
+///
+/// Call `poll` periodically to select tags entering the field
+/// and notice when they leave.
+pub struct Monitor<'a> {
+    /// Reader, which may or may not have a tag selected.
+    tag: Srix4k<'a>,
+    /// UID of the selected tag, `None` if there is no tag in the field.
+    uid: Option<u64>,
+}
+
+impl Monitor<'_> {
+    /// Monitor the field of the device.
+    pub fn new(mut device: nfc1::Device<'_>) -> nfc1::Result<Monitor<'_>> {
+        // Selection must fail fast when the field is empty,
+        // otherwise polling would block.
+        device.set_property_bool(nfc1::Property::InfiniteSelect, false)?;
+        Ok(Monitor {
+            tag: Srix4k::new(device),
+            uid: None,
+        })
+    }
+}
+
+impl<'a> Monitor<'a> {
+    /// Check whether a tag entered or left the field.
+    pub fn poll(&mut self) -> Option<TagEvent> {
+        match self.uid {
+            Some(uid) => {
+                if self.tag.send_get_uid().ok() == Some(uid) {
+                    return None;
+                }
+                info!("Tag {:016X} left the field", uid);
+                self.uid = None;
+                Some(TagEvent::Removal(uid))
+            }
+            None => {
+                self.tag.select().ok()?;
+                let uid = self.tag.send_get_uid().ok()?;
+                info!("Tag {:016X} entered the field", uid);
+                self.uid = Some(uid);
+                Some(TagEvent::Arrival(uid))
+            }
+        }
+    }
+    /// UID of the tag in the field, as of the last poll.
+    pub fn uid(&self) -> Option<u64> {
+        self.uid
+    }
+    /// Selected tag, `None` if there is no tag in the field.
+    pub fn tag(&mut self) -> Option<&mut Srix4k<'a>> {
+        match self.uid {
+            Some(_) => Some(&mut self.tag),
+            None => None,
+        }
+    }
+    /// JSON object describing the event with the data of the blocks,
+    /// included only for arrivals and if they can be read:
+    ///
+    /// ```json
+    /// {"event":"arrival","uid":"D0023C0012345678","blocks":{"5":"0xFFFFFFFE"}}
+    /// ```
+    pub fn event_json(&mut self, event: TagEvent, blocks: &[u8]) -> String {
+        let mut json = format!(
+            "{{\"event\":\"{}\",\"uid\":\"{:016X}\"",
+            event.name(),
+            event.uid()
+        );
+        if let (TagEvent::Arrival(_), Some(tag)) = (event, self.tag()) {
+            let mut block_values = Vec::with_capacity(blocks.len());
+            for &block_address in blocks {
+                match tag.send_read_block(block_address) {
+                    Ok(block_data) => block_values.push(format!(
+                        "\"{}\":\"{:#010X}\"",
+                        block_address, block_data
+                    )),
+                    Err(err) => {
+                        warn!(
+                            "Cannot read block {:#04X}: {}",
+                            block_address, err
+                        )
+                    }
+                }
+            }
+            json.push_str(&format!(
+                ",\"blocks\":{{{}}}",
+                block_values.join(",")
+            ));
+        }
+        json.push('}');
+        json
+    }
+}
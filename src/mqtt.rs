@@ -0,0 +1,228 @@
+//! Publishing of tag events to an MQTT broker.
+//!
+//! Every time a tag enters the field a message is published on
+//! `<prefix>/arrival`, and when it leaves on `<prefix>/removal`.
+//! The payload is a JSON object with the event, the UID and, for arrivals,
+//! the data of the configured blocks:
+//!
+//! ```json
+//! {"event":"arrival","uid":"D0023C0012345678","blocks":{"5":"0xFFFFFFFE"}}
+//! ```
+//!
+//! Only the small subset of MQTT 3.1.1 needed to publish QoS 0 messages is
+//! implemented.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use monitor::{Monitor, TagEvent};
+
+/// Keep alive interval announced to the broker.
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+/// Default topic prefix.
+const TOPIC_PREFIX: &str = "srix4k";
+/// Default time between two checks of the field.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// MQTT control packet types.
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PINGREQ: u8 = 0xC0;
+const DISCONNECT: u8 = 0xE0;
+
+/// Minimal MQTT client, only able to publish QoS 0 messages.
+pub struct Client {
+    /// Connection to the broker.
+    stream: TcpStream,
+    /// Last time a packet was sent to the broker.
+    last_sent: Instant,
+}
+
+impl Client {
+    /// Connect to the broker, optionally authenticating with
+    /// `(username, password)`.
+    pub fn connect<A: ToSocketAddrs>(
+        broker: A,
+        client_id: &str,
+        credentials: Option<(&str, &str)>,
+    ) -> io::Result<Client> {
+        let mut stream = TcpStream::connect(broker)?;
+
+        // Clean session.
+        let mut flags = 0x02;
+        let mut body = Vec::new();
+        encode_str(&mut body, "MQTT");
+        body.push(0x04);
+        let flags_index = body.len();
+        body.push(0);
+        body.extend((KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+        encode_str(&mut body, client_id);
+        if let Some((username, password)) = credentials {
+            flags |= 0xC0;
+            encode_str(&mut body, username);
+            encode_str(&mut body, password);
+        }
+        body[flags_index] = flags;
+        stream.write_all(&packet(CONNECT, &body))?;
+
+        let mut connack = [0; 4];
+        stream.read_exact(&mut connack)?;
+        if connack[0] != CONNACK || connack[1] != 0x02 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected answer to CONNECT",
+            ));
+        }
+        if connack[3] != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("broker refused connection ({})", connack[3]),
+            ));
+        }
+        // Only PINGRESP packets are expected from now on, drop them.
+        stream.set_nonblocking(true)?;
+
+        Ok(Client {
+            stream,
+            last_sent: Instant::now(),
+        })
+    }
+    /// Publish a QoS 0 message.
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        retain: bool,
+    ) -> io::Result<()> {
+        debug!("Publishing {} bytes on {}", payload.len(), topic);
+        let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+        encode_str(&mut body, topic);
+        body.extend_from_slice(payload);
+        self.send(&packet(PUBLISH | retain as u8, &body))
+    }
+    /// Ping the broker if nothing has been sent for a while,
+    /// so it does not drop the connection.
+    pub fn keep_alive(&mut self) -> io::Result<()> {
+        let mut discarded = [0; 64];
+        loop {
+            match self.stream.read(&mut discarded) {
+                Ok(0) => return Err(io::ErrorKind::ConnectionAborted.into()),
+                Ok(_) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    break
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        if self.last_sent.elapsed() >= KEEP_ALIVE / 2 {
+            self.send(&[PINGREQ, 0])?;
+        }
+
+        Ok(())
+    }
+    /// Send a packet, waiting for the socket to accept it.
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(packet);
+        self.stream.set_nonblocking(true)?;
+        self.last_sent = Instant::now();
+        result
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.send(&[DISCONNECT, 0]);
+    }
+}
+
+/// Service publishing the events of a reader.
+pub struct Publisher<'a> {
+    /// Reader and the tag in its field.
+    monitor: Monitor<'a>,
+    /// Connection to the broker.
+    client: Client,
+    /// Prefix of the topics.
+    topic_prefix: String,
+    /// Blocks whose data is included in the arrival messages.
+    blocks: Vec<u8>,
+    /// Time between two checks of the field.
+    poll_interval: Duration,
+}
+
+impl Publisher<'_> {
+    /// Publish the events of the device with the client.
+    pub fn new(
+        device: nfc1::Device<'_>,
+        client: Client,
+    ) -> nfc1::Result<Publisher<'_>> {
+        Ok(Publisher {
+            monitor: Monitor::new(device)?,
+            client,
+            topic_prefix: TOPIC_PREFIX.to_string(),
+            blocks: Vec::new(),
+            poll_interval: POLL_INTERVAL,
+        })
+    }
+    /// Set the prefix of the topics, `srix4k` by default.
+    pub fn set_topic_prefix(&mut self, topic_prefix: &str) {
+        self.topic_prefix = topic_prefix.trim_end_matches('/').to_string();
+    }
+    /// Set the blocks whose data is included in arrival messages.
+    /// From 0 to 127, or 255 for system.
+    pub fn set_blocks(&mut self, blocks: Vec<u8>) {
+        self.blocks = blocks;
+    }
+    /// Set the time between two checks of the field.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+    /// Publish events forever.
+    pub fn run(&mut self) -> io::Result<()> {
+        info!("Publishing tag events on {}/#", self.topic_prefix);
+        loop {
+            if let Some(event) = self.monitor.poll() {
+                self.publish(event)?;
+            }
+            self.client.keep_alive()?;
+            thread::sleep(self.poll_interval);
+        }
+    }
+    /// Publish a single event.
+    fn publish(&mut self, event: TagEvent) -> io::Result<()> {
+        let payload = self.monitor.event_json(event, &self.blocks);
+        let topic = format!("{}/{}", self.topic_prefix, event.name());
+        self.client.publish(&topic, payload.as_bytes(), false)
+    }
+}
+
+/// Build a packet with the fixed header.
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    packet.push(kind);
+    // Remaining length, 7 bits at a time.
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Append a length prefixed UTF-8 string.
+fn encode_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend((s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
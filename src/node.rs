@@ -0,0 +1,167 @@
+//! Node.js bindings, enabled by the `node` feature.
+//!
+//! Build the native addon with [napi-rs](https://napi.rs):
+//!
+//! ```sh
+//! napi build --release --features node
+//! ```
+//!
+//! ```js
+//! const { Reader } = require('./srix4k.node');
+//!
+//! const reader = new Reader();
+//! setInterval(() => {
+//!   const event = reader.poll();
+//!   if (event && event.kind === 'arrival') {
+//!     console.log(event.uid, reader.readBlock(5));
+//!   }
+//! }, 250);
+//! ```
+//!
+//! UIDs are hexadecimal strings, since JavaScript numbers cannot hold them.
+
+use napi;
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+use mem;
+use monitor::{Monitor, TagEvent};
+use system;
+use Srix4k;
+
+/// Convert a libnfc error to a JavaScript `Error`.
+fn js_error<E: ToString>(err: E) -> napi::Error {
+    napi::Error::from_reason(err.to_string())
+}
+
+/// Change of the tag in the field.
+#[napi(object, js_name = "TagEvent")]
+pub struct JsTagEvent {
+    /// `arrival` or `removal`.
+    pub kind: String,
+    /// UID of the tag the event refers to.
+    pub uid: String,
+}
+
+impl From<TagEvent> for JsTagEvent {
+    fn from(event: TagEvent) -> Self {
+        JsTagEvent {
+            kind: event.name().to_string(),
+            uid: format!("{:016X}", event.uid()),
+        }
+    }
+}
+
+/// Reader keeping track of the tag in its field.
+#[napi(js_name = "Reader")]
+pub struct JsReader {
+    /// Reader and the tag in its field, dropped before the context.
+    monitor: Option<Monitor<'static>>,
+    /// Context the device was opened from.
+    context: *mut nfc1::Context<'static>,
+}
+
+impl Drop for JsReader {
+    fn drop(&mut self) {
+        self.monitor = None;
+        // SAFETY: the context was leaked by `new`
+        // and nothing borrows it anymore.
+        unsafe { drop(Box::from_raw(self.context)) };
+    }
+}
+
+impl JsReader {
+    /// Selected tag, or an error if there is no tag in the field.
+    fn tag(&mut self) -> napi::Result<&mut Srix4k<'static>> {
+        self.monitor
+            .as_mut()
+            .expect("monitor is open until dropped")
+            .tag()
+            .ok_or_else(|| js_error("no tag in the field"))
+    }
+}
+
+#[napi]
+impl JsReader {
+    /// Open the device, the default one if `connstring` is not given.
+    #[napi(constructor)]
+    pub fn new(connstring: Option<String>) -> napi::Result<Self> {
+        let context =
+            Box::into_raw(Box::new(nfc1::Context::new().map_err(js_error)?));
+        // From now on `reader` frees the context.
+        let mut reader = JsReader {
+            monitor: None,
+            context,
+        };
+
+        // SAFETY: the context lives until `reader` is dropped.
+        let device = unsafe {
+            match connstring {
+                Some(ref connstring) => {
+                    (*context).open_with_connstring(connstring)
+                }
+                None => (*context).open(),
+            }
+        };
+        reader.monitor =
+            Some(device.and_then(Monitor::new).map_err(js_error)?);
+        Ok(reader)
+    }
+    /// Check whether a tag entered or left the field.
+    #[napi]
+    pub fn poll(&mut self) -> Option<JsTagEvent> {
+        self.monitor
+            .as_mut()
+            .expect("monitor is open until dropped")
+            .poll()
+            .map(JsTagEvent::from)
+    }
+    /// UID of the tag in the field, as of the last poll.
+    #[napi]
+    pub fn uid(&self) -> Option<String> {
+        self.monitor
+            .as_ref()
+            .expect("monitor is open until dropped")
+            .uid()
+            .map(|uid| format!("{:016X}", uid))
+    }
+    /// Read a block, from 0 to 127, or 255 for system.
+    #[napi]
+    pub fn read_block(&mut self, address: u8) -> napi::Result<u32> {
+        self.tag()?.send_read_block(address).map_err(js_error)
+    }
+    /// Write a block, from 0 to 127, or 255 for system.
+    ///
+    /// Writing the system block cannot be undone, it needs
+    /// `acknowledgePermanentDamage` to be `true`.
+    #[napi]
+    pub fn write_block(
+        &mut self,
+        address: u8,
+        block_data: u32,
+        acknowledge_permanent_damage: Option<bool>,
+    ) -> napi::Result<()> {
+        if address as usize != mem::SYSTEM_ADDR {
+            return self
+                .tag()?
+                .send_write_block(address, block_data)
+                .map_err(js_error);
+        }
+        if acknowledge_permanent_damage != Some(true) {
+            return Err(js_error(
+                "writing the system block cannot be undone, pass \
+                 acknowledgePermanentDamage = true",
+            ));
+        }
+        let acknowledgment = system::acknowledge_permanent_damage();
+        self.tag()?
+            .send_write_system(block_data, acknowledgment)
+            .map_err(js_error)
+    }
+    /// Full dump of the tag, in the format of `srix4k::dump`.
+    #[napi]
+    pub fn dump(&mut self) -> napi::Result<Buffer> {
+        let dump = self.tag()?.dump().map_err(js_error)?;
+        Ok(dump.serialize().into())
+    }
+}
@@ -0,0 +1,345 @@
+//! User notes on blocks, kept in a sidecar file next to a dump so that
+//! reverse-engineering annotations travel with it.
+//!
+//! The sidecar of `tag.bin` is `tag.bin.notes.json`, a JSON object of
+//! notes keyed by block address, decimal or `0x`-prefixed hexadecimal:
+//!
+//! ```json
+//! {
+//!   "0x05": "rides left, decremented at each gate",
+//!   "0x10": "last station ID"
+//! }
+//! ```
+//!
+//! `Notes::dump` and `Notes::diff` show the notes alongside the block data
+//! of a dump and the differences of two dumps.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use dump::{DumpDiff, Srix4kDump};
+use mem;
+
+/// Notes on blocks, by block address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Notes(BTreeMap<u8, String>);
+
+/// Errors that can occur while loading notes.
+#[derive(Debug)]
+pub enum NotesError {
+    /// Notes file could not be read.
+    Io(io::Error),
+    /// `Syntax(offset)`
+    /// Notes file is not a JSON object of strings, from byte `offset`.
+    Syntax(usize),
+    /// Key is not the address of an EEPROM block or the system block.
+    InvalidAddress(String),
+    /// Block has more than one note.
+    DuplicateBlock(u8),
+}
+
+impl fmt::Display for NotesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotesError::Io(err) => write!(f, "cannot read notes: {}", err),
+            NotesError::Syntax(offset) => {
+                write!(f, "invalid notes JSON at byte {}", offset)
+            }
+            NotesError::InvalidAddress(key) => {
+                write!(f, "invalid block address `{}`", key)
+            }
+            NotesError::DuplicateBlock(address) => {
+                write!(f, "block {:#04X} has two notes", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotesError {}
+
+impl From<io::Error> for NotesError {
+    fn from(err: io::Error) -> Self {
+        NotesError::Io(err)
+    }
+}
+
+/// Sidecar notes file of the dump at `dump`.
+pub fn sidecar_path<P: AsRef<Path>>(dump: P) -> PathBuf {
+    let mut path = OsString::from(dump.as_ref());
+    path.push(".notes.json");
+    PathBuf::from(path)
+}
+
+impl Notes {
+    /// Load the notes file at `path`, no notes if it does not exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Notes, NotesError> {
+        match fs::read_to_string(path) {
+            Ok(source) => Notes::parse(&source),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(Notes::default())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+    /// Load the sidecar notes of the dump at `dump`, see `sidecar_path`.
+    pub fn load_sidecar<P: AsRef<Path>>(dump: P) -> Result<Notes, NotesError> {
+        Notes::load(sidecar_path(dump))
+    }
+    /// Parse notes from JSON.
+    pub fn parse(source: &str) -> Result<Notes, NotesError> {
+        let mut parser = Parser { source, offset: 0 };
+        let mut notes = Notes::default();
+        parser.expect('{')?;
+        if !parser.accept('}') {
+            loop {
+                let key = parser.string()?;
+                let address = parse_address(&key)
+                    .ok_or(NotesError::InvalidAddress(key))?;
+                parser.expect(':')?;
+                let note = parser.string()?;
+                if notes.0.insert(address, note).is_some() {
+                    return Err(NotesError::DuplicateBlock(address));
+                }
+                if parser.accept('}') {
+                    break;
+                }
+                parser.expect(',')?;
+            }
+        }
+        parser.skip_whitespace();
+        if parser.offset != source.len() {
+            return Err(NotesError::Syntax(parser.offset));
+        }
+        Ok(notes)
+    }
+    /// Save the notes to `path`, replacing the file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+    /// Save the notes as the sidecar of the dump at `dump`.
+    pub fn save_sidecar<P: AsRef<Path>>(&self, dump: P) -> io::Result<()> {
+        self.save(sidecar_path(dump))
+    }
+    /// Note on the block at `address`.
+    pub fn get(&self, address: u8) -> Option<&str> {
+        self.0.get(&address).map(String::as_str)
+    }
+    /// Set the note on the block at `address`, returning the previous one.
+    pub fn set(&mut self, address: u8, note: String) -> Option<String> {
+        self.0.insert(address, note)
+    }
+    /// Remove the note on the block at `address`.
+    pub fn remove(&mut self, address: u8) -> Option<String> {
+        self.0.remove(&address)
+    }
+    /// Iterate over `(address, note)`, in address order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &str)> {
+        self.0
+            .iter()
+            .map(|(&address, note)| (address, note.as_str()))
+    }
+    /// Whether there is no note.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Blocks of `dump` with their notes.
+    pub fn dump<'a>(&'a self, dump: &'a Srix4kDump) -> NotedDump<'a> {
+        NotedDump { notes: self, dump }
+    }
+    /// Differences of `diff` with the notes of their blocks.
+    pub fn diff<'a>(&'a self, diff: &'a DumpDiff) -> NotedDiff<'a> {
+        NotedDiff { notes: self, diff }
+    }
+}
+
+impl fmt::Display for Notes {
+    /// Format as the JSON of the notes file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (address, note)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "\n  \"{:#04X}\": \"", address)?;
+            for c in note.chars() {
+                match c {
+                    '"' => write!(f, "\\\"")?,
+                    '\\' => write!(f, "\\\\")?,
+                    '\n' => write!(f, "\\n")?,
+                    c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            write!(f, "\"")?;
+        }
+        if !self.is_empty() {
+            writeln!(f)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Blocks of a dump with their notes, see `Notes::dump`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotedDump<'a> {
+    notes: &'a Notes,
+    dump: &'a Srix4kDump,
+}
+
+impl fmt::Display for NotedDump<'_> {
+    /// One line per block, `address: data`, followed by the note if any.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let system = self.dump.system.map(|system| (mem::SYSTEM_ADDR, system));
+        let blocks = self.dump.eeprom.iter().cloned().enumerate();
+        for (i, block_data) in blocks.chain(system) {
+            write!(f, "{:#04X}: {:#010X}", i, block_data)?;
+            noted(f, self.notes.get(i as u8))?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences of two dumps with the notes of their blocks, see
+/// `Notes::diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotedDiff<'a> {
+    notes: &'a Notes,
+    diff: &'a DumpDiff,
+}
+
+impl fmt::Display for NotedDiff<'_> {
+    /// One line per difference, `address: old -> new`, followed by the
+    /// note if any.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((old, new)) = self.diff.uid {
+            writeln!(f, "uid: {:016X} -> {:016X}", old, new)?;
+        }
+        for block in &self.diff.blocks {
+            write!(
+                f,
+                "{:#04X}: {:#010X} -> {:#010X}",
+                block.address, block.old, block.new
+            )?;
+            noted(f, self.notes.get(block.address))?;
+        }
+        Ok(())
+    }
+}
+
+/// End a line of block data with `note`, if any.
+fn noted(f: &mut fmt::Formatter, note: Option<&str>) -> fmt::Result {
+    match note {
+        Some(note) => writeln!(f, "  # {}", note.replace('\n', " ")),
+        None => writeln!(f),
+    }
+}
+
+/// Address of a notes key, `None` if it is not an EEPROM block or the
+/// system block.
+fn parse_address(key: &str) -> Option<u8> {
+    let address = match key.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+        None => key.parse().ok()?,
+    };
+    if mem::EEPROM.contains(&address) || address == mem::SYSTEM_ADDR {
+        Some(address as u8)
+    } else {
+        None
+    }
+}
+
+/// Reader of the JSON subset of notes files: one object of strings.
+struct Parser<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl Parser<'_> {
+    /// Next character, consumed.
+    fn next(&mut self) -> Option<char> {
+        let c = self.source[self.offset..].chars().next()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+    /// Skip the whitespace before the next token.
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.offset..];
+        let token = rest.trim_start_matches([' ', '\t', '\n', '\r']);
+        self.offset += rest.len() - token.len();
+    }
+    /// Consume `c` if it is the next token.
+    fn accept(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.source[self.offset..].starts_with(c);
+        if found {
+            self.offset += c.len_utf8();
+        }
+        found
+    }
+    /// Consume `c`, which must be the next token.
+    fn expect(&mut self, c: char) -> Result<(), NotesError> {
+        if self.accept(c) {
+            Ok(())
+        } else {
+            Err(NotesError::Syntax(self.offset))
+        }
+    }
+    /// Consume a string token.
+    fn string(&mut self) -> Result<String, NotesError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let start = self.offset;
+            let c = match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => self.escape(),
+                Some(c) if !c.is_control() => Some(c),
+                _ => None,
+            };
+            string.push(c.ok_or(NotesError::Syntax(start))?);
+        }
+    }
+    /// Character of the escape sequence after a backslash.
+    fn escape(&mut self) -> Option<char> {
+        match self.next()? {
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            '/' => Some('/'),
+            'b' => Some('\u{8}'),
+            'f' => Some('\u{c}'),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            'u' => {
+                let high = self.code_unit()?;
+                if !(0xD800..0xDC00).contains(&high) {
+                    return char::from_u32(high);
+                }
+                if self.next()? != '\\' || self.next()? != 'u' {
+                    return None;
+                }
+                let low = self.code_unit()?;
+                if !(0xDC00..0xE000).contains(&low) {
+                    return None;
+                }
+                let offset = ((high - 0xD800) << 10) | (low - 0xDC00);
+                char::from_u32(0x10000 + offset)
+            }
+            _ => None,
+        }
+    }
+    /// UTF-16 code unit of the 4 hexadecimal digits of a `\u` escape.
+    fn code_unit(&mut self) -> Option<u32> {
+        let digits = self.source.get(self.offset..self.offset + 4)?;
+        if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return None;
+        }
+        let code_unit = u32::from_str_radix(digits, 16).ok()?;
+        self.offset += 4;
+        Some(code_unit)
+    }
+}
@@ -0,0 +1,56 @@
+//! Machine-parsable log lines describing RF operations.
+//!
+//! Once enabled with `Srix4k::set_json_log`, every RF operation is logged at
+//! `info` level on the `srix4k::json` target as a single JSON object. The
+//! lines go through the `log` crate, so `set_json_log` needs the `log`
+//! feature:
+//!
+//! ```json
+//! {"operation":"read_block","block":5,"value":"0xFFFFFFFE","duration_us":1843,"outcome":"ok"}
+//! {"operation":"get_uid","duration_us":2210,"outcome":"error","error":"RF Transmission Error"}
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+/// Log target of the JSON lines.
+pub const TARGET: &str = "srix4k::json";
+
+/// RF operation, displayed as a JSON object.
+pub struct Operation<'a> {
+    /// `connect`, `read_block`, `write_block`, `get_uid`,
+    /// `read_block_timed` or `get_uid_timed`.
+    pub name: &'static str,
+    /// Address of the block read or written.
+    pub block: Option<u8>,
+    /// Block data or UID, in hexadecimal.
+    pub value: Option<String>,
+    /// Time the operation took.
+    pub duration: Duration,
+    /// Error the operation failed with, `None` if it succeeded.
+    pub error: Option<&'a dyn fmt::Display>,
+}
+
+impl fmt::Display for Operation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{\"operation\":\"{}\"", self.name)?;
+        if let Some(block) = self.block {
+            write!(f, ",\"block\":{}", block)?;
+        }
+        if let Some(ref value) = self.value {
+            write!(f, ",\"value\":\"{}\"", value)?;
+        }
+        write!(f, ",\"duration_us\":{}", self.duration.as_micros())?;
+        match self.error {
+            None => write!(f, ",\"outcome\":\"ok\"}}"),
+            Some(err) => {
+                let message: String = err
+                    .to_string()
+                    .chars()
+                    .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+                    .collect();
+                write!(f, ",\"outcome\":\"error\",\"error\":\"{}\"}}", message)
+            }
+        }
+    }
+}
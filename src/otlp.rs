@@ -0,0 +1,276 @@
+//! Export of the metrics and operations of a reader to an OpenTelemetry
+//! collector, with the OTLP/HTTP JSON protocol.
+//!
+//! Metrics are sent to `<endpoint>/v1/metrics` as cumulative sums and a
+//! latency histogram, and every operation on a tag becomes a server span
+//! sent to `<endpoint>/v1/traces`. Each top-level operation starts a trace,
+//! shared by the operations nested in it, see `SpanContext`. Like webhooks,
+//! only plain `http://` endpoints are supported.
+
+use std::collections::hash_map::RandomState;
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use metrics::{Metrics, LATENCY_BUCKETS};
+use webhook::Webhook;
+
+/// Value of the `service.name` resource attribute.
+const SERVICE_NAME: &str = "srix4k";
+
+/// Position of an operation in its trace, to nest other operations in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpanContext {
+    /// Trace of the top-level operation.
+    pub trace_id: u128,
+    /// Span of the operation.
+    pub span_id: u64,
+    /// Span of the operation this one is nested in, `None` for a
+    /// top-level operation.
+    pub parent_span_id: Option<u64>,
+}
+
+impl SpanContext {
+    /// Top-level operation, starting a new trace.
+    pub fn root() -> SpanContext {
+        SpanContext {
+            trace_id: (random_u64() as u128) << 64 | random_u64() as u128,
+            span_id: random_u64(),
+            parent_span_id: None,
+        }
+    }
+    /// Operation nested in this one, in the same trace.
+    pub fn child(&self) -> SpanContext {
+        SpanContext {
+            trace_id: self.trace_id,
+            span_id: random_u64(),
+            parent_span_id: Some(self.span_id),
+        }
+    }
+}
+
+/// Operation on a tag, waiting to be exported.
+struct Span {
+    /// Position of the operation in its trace.
+    context: SpanContext,
+    /// Operation, e.g. `GET /dump`.
+    name: String,
+    /// When the operation started.
+    start: SystemTime,
+    /// Time the operation took.
+    duration: Duration,
+    /// Error the operation failed with, `None` if it succeeded.
+    error: Option<String>,
+}
+
+/// Exporter to an OpenTelemetry collector.
+pub struct Exporter {
+    /// `/v1/metrics` endpoint.
+    metrics: Webhook,
+    /// `/v1/traces` endpoint.
+    traces: Webhook,
+    /// When the exported metrics started being collected.
+    start: SystemTime,
+    /// Spans recorded since the last export.
+    spans: Vec<Span>,
+}
+
+impl Exporter {
+    /// Export to the collector at the `http://host[:port]` endpoint,
+    /// usually listening on port 4318.
+    pub fn new(endpoint: &str) -> io::Result<Exporter> {
+        let endpoint = endpoint.trim_end_matches('/');
+        Ok(Exporter {
+            metrics: Webhook::new(&format!("{}/v1/metrics", endpoint))?,
+            traces: Webhook::new(&format!("{}/v1/traces", endpoint))?,
+            start: SystemTime::now(),
+            spans: Vec::new(),
+        })
+    }
+    /// Record an operation on a tag that started at `start`, at `context`
+    /// in its trace.
+    pub fn record_span(
+        &mut self,
+        context: SpanContext,
+        name: &str,
+        start: SystemTime,
+        duration: Duration,
+        error: Option<String>,
+    ) {
+        self.spans.push(Span {
+            context,
+            name: name.to_string(),
+            start,
+            duration,
+            error,
+        });
+    }
+    /// Send the metrics and the spans recorded since the last export.
+    ///
+    /// The spans are dropped even if they cannot be sent, so they do not
+    /// pile up while the collector is down. Fails with the errors of both
+    /// requests if any.
+    pub fn export(&mut self, metrics: &Metrics) -> io::Result<()> {
+        let metrics = self.metrics.notify(&self.metrics_json(metrics));
+        let traces = if self.spans.is_empty() {
+            Ok(())
+        } else {
+            let traces = self.traces_json();
+            self.spans.clear();
+            self.traces.notify(&traces)
+        };
+        match (metrics, traces) {
+            (Ok(()), Ok(())) => Ok(()),
+            (Err(err), Ok(())) => {
+                Err(io::Error::new(err.kind(), format!("metrics: {}", err)))
+            }
+            (Ok(()), Err(err)) => {
+                Err(io::Error::new(err.kind(), format!("traces: {}", err)))
+            }
+            (Err(metrics), Err(traces)) => Err(io::Error::new(
+                metrics.kind(),
+                format!("metrics: {}, traces: {}", metrics, traces),
+            )),
+        }
+    }
+    /// `ExportMetricsServiceRequest` with the current metrics.
+    fn metrics_json(&self, metrics: &Metrics) -> String {
+        let start = unix_nanos(self.start);
+        let now = unix_nanos(SystemTime::now());
+        let mut json = String::new();
+        for (name, help, value) in metrics.counters().iter() {
+            let _ = write!(
+                json,
+                "{{\"name\":\"srix4k.{}\",\"description\":\"{}\",\
+                 \"sum\":{{\"aggregationTemporality\":2,\"isMonotonic\":true,\
+                 \"dataPoints\":[{{\"startTimeUnixNano\":\"{}\",\
+                 \"timeUnixNano\":\"{}\",\"asInt\":\"{}\"}}]}}}},",
+                name, help, start, now, value
+            );
+        }
+        let buckets = metrics.latency_buckets();
+        let _ = write!(
+            json,
+            "{{\"name\":\"srix4k.operation.duration\",\"unit\":\"s\",\
+             \"description\":\"Duration of operations on tags.\",\
+             \"histogram\":{{\"aggregationTemporality\":2,\
+             \"dataPoints\":[{{\"startTimeUnixNano\":\"{}\",\
+             \"timeUnixNano\":\"{}\",\"count\":\"{}\",\"sum\":{},\
+             \"bucketCounts\":[{}],\"explicitBounds\":[{}]}}]}}}}",
+            start,
+            now,
+            buckets.iter().sum::<u64>(),
+            metrics.latency_sum(),
+            join(buckets.iter().map(|count| format!("\"{}\"", count))),
+            join(LATENCY_BUCKETS.iter().map(|bound| bound.to_string()))
+        );
+        format!(
+            "{{\"resourceMetrics\":[{{\"resource\":{},\
+             \"scopeMetrics\":[{{\"scope\":{{\"name\":\"{}\"}},\
+             \"metrics\":[{}]}}]}}]}}",
+            resource_json(),
+            SERVICE_NAME,
+            json
+        )
+    }
+    /// `ExportTraceServiceRequest` with the recorded spans.
+    fn traces_json(&self) -> String {
+        let spans = self.spans.iter().map(|span| {
+            let start = unix_nanos(span.start);
+            let status = match span.error {
+                Some(ref err) => format!(
+                    "{{\"code\":2,\"message\":\"{}\"}}",
+                    err.replace(['"', '\\'], "")
+                ),
+                None => "{\"code\":1}".to_string(),
+            };
+            // Server spans for the top-level operations, internal ones for
+            // the nested ones.
+            let (parent, kind) = match span.context.parent_span_id {
+                Some(parent) => {
+                    (format!("\"parentSpanId\":\"{:016x}\",", parent), 1)
+                }
+                None => (String::new(), 2),
+            };
+            format!(
+                "{{\"traceId\":\"{:032x}\",\"spanId\":\"{:016x}\",{}\
+                 \"name\":\"{}\",\"kind\":{},\"startTimeUnixNano\":\"{}\",\
+                 \"endTimeUnixNano\":\"{}\",\"status\":{}}}",
+                span.context.trace_id,
+                span.context.span_id,
+                parent,
+                span.name,
+                kind,
+                start,
+                start + span.duration.as_nanos(),
+                status
+            )
+        });
+        format!(
+            "{{\"resourceSpans\":[{{\"resource\":{},\
+             \"scopeSpans\":[{{\"scope\":{{\"name\":\"{}\"}},\
+             \"spans\":[{}]}}]}}]}}",
+            resource_json(),
+            SERVICE_NAME,
+            join(spans)
+        )
+    }
+}
+
+/// Resource describing the service.
+fn resource_json() -> String {
+    format!(
+        "{{\"attributes\":[{{\"key\":\"service.name\",\
+         \"value\":{{\"stringValue\":\"{}\"}}}}]}}",
+        SERVICE_NAME
+    )
+}
+
+/// Nanoseconds since the Unix epoch.
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_nanos())
+        .unwrap_or(0)
+}
+
+/// Random identifier, from the randomly seeded hasher of the standard library.
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Join the JSON values with commas.
+fn join<I: Iterator<Item = String>>(values: I) -> String {
+    values.collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_spans_share_the_trace() {
+        let root = SpanContext::root();
+        let child = root.child();
+        assert_eq!(child.trace_id, root.trace_id);
+        assert_eq!(child.parent_span_id, Some(root.span_id));
+        assert_ne!(child.span_id, root.span_id);
+        assert_ne!(SpanContext::root().trace_id, root.trace_id);
+    }
+
+    #[test]
+    fn traces_link_nested_spans() {
+        let mut exporter = Exporter::new("http://127.0.0.1:4318").unwrap();
+        let root = SpanContext::root();
+        let child = root.child();
+        let start = SystemTime::now();
+        let duration = Duration::from_millis(1);
+        exporter.record_span(child, "select", start, duration, None);
+        exporter.record_span(root, "GET /dump", start, duration, None);
+        let json = exporter.traces_json();
+        let trace_id = format!("\"traceId\":\"{:032x}\"", root.trace_id);
+        assert_eq!(json.matches(&trace_id).count(), 2);
+        let parent = format!("\"parentSpanId\":\"{:016x}\"", root.span_id);
+        assert_eq!(json.matches(&parent).count(), 1);
+    }
+}
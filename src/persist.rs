@@ -0,0 +1,87 @@
+//! Blocks last verified on each tag, kept across sessions.
+//!
+//! Once enabled with `Srix4kCached::load_persisted`, `sync` skips the
+//! writes of the data the blocks saved for the UID of the tag hold, and
+//! the cache saves the blocks it knows the tag holds after each `sync`.
+//! Running the same restore twice then spares the EEPROM.
+//!
+//! The saved blocks are never served as reads. They go stale if another
+//! reader writes the tag, or its counters are decremented: a saved block
+//! the tag contradicts when read is dropped, but only persist tags this
+//! application alone writes.
+//!
+//! One file per UID, e.g. `D0023C0012345678.blocks`, one block per line:
+//!
+//! ```text
+//! 0x10 0xDEADBEEF
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use journal::parse_hex;
+
+/// File of the blocks of the tag with `uid` in `dir`.
+pub fn path<P: AsRef<Path>>(dir: P, uid: u64) -> PathBuf {
+    dir.as_ref().join(format!("{:016X}.blocks", uid))
+}
+
+/// Blocks saved for the tag with `uid` in `dir`, as `(address, data)`,
+/// none if nothing is saved.
+///
+/// Fails with `InvalidData` if a line is not a block.
+pub fn load<P: AsRef<Path>>(dir: P, uid: u64) -> io::Result<Vec<(u8, u32)>> {
+    let blocks = match fs::read_to_string(path(dir, uid)) {
+        Ok(blocks) => blocks,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new())
+        }
+        Err(err) => return Err(err),
+    };
+    blocks
+        .lines()
+        .map(|line| {
+            let mut fields = line.split(' ');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(address), Some(block_data), None) => {
+                    match (parse_hex(address), parse_hex(block_data)) {
+                        (Some(address), Some(block_data)) if address < 256 => {
+                            Ok((address as u8, block_data))
+                        }
+                        _ => Err(invalid(line)),
+                    }
+                }
+                _ => Err(invalid(line)),
+            }
+        })
+        .collect()
+}
+
+/// Save the blocks of the tag with `uid` in `dir`, replacing the previous
+/// ones.
+///
+/// The file is written next to its destination, then renamed over it, so
+/// a crash leaves either the old or the new blocks.
+pub fn save<P: AsRef<Path>>(
+    dir: P,
+    uid: u64,
+    blocks: &[(u8, u32)],
+) -> io::Result<()> {
+    let path = path(dir, uid);
+    let mut contents = String::new();
+    for &(address, block_data) in blocks {
+        contents.push_str(&format!("{:#04X} {:#010X}\n", address, block_data));
+    }
+    let temporary = path.with_extension("blocks.tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, &path)
+}
+
+/// Error of a line of a blocks file.
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid persisted block `{}`", line),
+    )
+}
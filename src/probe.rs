@@ -0,0 +1,116 @@
+//! Target information in the format of `nfc-list`, decoded for ST short
+//! range tags, for users coming from the libnfc tools.
+//!
+//! `nfc-list` only prints the raw UID of ISO14443B-2 SR targets, least
+//! significant byte first. `Srix4k::probe`, or `srix4k probe`, prints it
+//! the same way, followed by its fields, the chip the product code stands
+//! for, and the fixed chip ID and lock bits of the system block:
+//!
+//! ```text
+//! NFC device: ACR122U PICC Interface opened
+//! 1 ISO14443B-2 ST SRx passive target(s) found:
+//! ISO/IEC 14443-2B ST SRx (106 kbps) target:
+//!        UID: 78 56 34 12 00 0c 02 d0
+//!             prefix D0, manufacturer 02 (STMicroelectronics)
+//!             product 03 (SRIX4K), serial 0x0012345678
+//!    Chip ID: 5A
+//!     Locked: none
+//! ```
+
+use std::fmt;
+
+use nfc1::Result;
+use system::SystemBlock;
+use {mem, Srix4k, Uid};
+
+/// Manufacturer code of STMicroelectronics.
+const ST_MANUFACTURER: u8 = 0x02;
+
+/// What `Srix4k::probe` learned about the tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Probe {
+    /// Name of the reader.
+    pub device: String,
+    /// UID of the tag, `None` if it does not answer `GetUID`.
+    pub uid: Option<u64>,
+    /// System block, `None` if it could not be read.
+    pub system: Option<u32>,
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "NFC device: {} opened", self.device)?;
+        writeln!(f, "1 ISO14443B-2 ST SRx passive target(s) found:")?;
+        write!(f, "ISO/IEC 14443-2B ST SRx (106 kbps) target:")?;
+        match self.uid.map(Uid) {
+            Some(uid) => {
+                write!(f, "\n       UID:")?;
+                for byte in uid.0.to_le_bytes().iter() {
+                    write!(f, " {:02x}", byte)?;
+                }
+                let manufacturer = match uid.manufacturer() {
+                    ST_MANUFACTURER => "STMicroelectronics",
+                    _ => "unknown",
+                };
+                write!(
+                    f,
+                    "\n            prefix {:02X}, manufacturer {:02X} ({})",
+                    uid.prefix(),
+                    uid.manufacturer(),
+                    manufacturer
+                )?;
+                let chip = mem::layout_of(uid.0)
+                    .map_or("unknown", |layout| layout.name());
+                write!(
+                    f,
+                    "\n            product {:02X} ({}), serial {:#012X}",
+                    uid.chip_code(),
+                    chip,
+                    uid.serial()
+                )?;
+            }
+            None => write!(f, "\n       UID: not answered")?,
+        }
+        match self.system.map(SystemBlock::from) {
+            Some(system) => {
+                write!(f, "\n   Chip ID: {:02X}", system.chip_id())?;
+                let locked: Vec<_> = mem::LOCKABLE
+                    .filter(|&i| system.is_locked(i as u8) == Some(true))
+                    .map(|i| format!("{:#04X}", i))
+                    .collect();
+                if locked.is_empty() {
+                    write!(f, "\n    Locked: none")
+                } else {
+                    write!(f, "\n    Locked: {}", locked.join(", "))
+                }
+            }
+            None => write!(f, "\n    System: not read"),
+        }
+    }
+}
+
+impl Srix4k<'_> {
+    /// Read what identifies the tag: UID and system block.
+    ///
+    /// Fails if the tag does not answer `GetUID` while its quirks say it
+    /// should. A system block that cannot be read is left out.
+    pub fn probe(&mut self) -> Result<Probe> {
+        let uid = if self.quirks().get_uid {
+            Some(self.send_get_uid()?)
+        } else {
+            None
+        };
+        let system = match self.send_read_block(mem::SYSTEM_ADDR as u8) {
+            Ok(system) => Some(system),
+            Err(err) => {
+                debug!("Cannot read the system block: {}", err);
+                None
+            }
+        };
+        Ok(Probe {
+            device: self.device_name.to_string(),
+            uid,
+            system,
+        })
+    }
+}
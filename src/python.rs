@@ -0,0 +1,207 @@
+//! Python bindings, enabled by the `python` feature.
+//!
+//! Build the `srix4k` module with [maturin](https://www.maturin.rs):
+//!
+//! ```sh
+//! maturin develop --features python
+//! ```
+//!
+//! ```python
+//! import srix4k
+//!
+//! tag = srix4k.Srix4k()
+//! print(hex(tag.uid()))
+//! tag.write_block(7, tag.read_block(7) | 1)
+//! tag.sync()
+//! ```
+
+use std::fmt;
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use dump::Srix4kDump;
+use mem;
+use system;
+use Srix4kCached;
+
+/// Convert a libnfc or cached access error to a Python `IOError`.
+fn io_error<E: fmt::Display>(err: E) -> PyErr {
+    PyIOError::new_err(err.to_string())
+}
+
+/// Address of a block, from 0 to 127, or 255 for system.
+fn block_addr(address: usize) -> PyResult<mem::BlockAddr> {
+    mem::BlockAddr::new(address)
+        .ok_or_else(|| PyValueError::new_err("invalid block address"))
+}
+
+/// Connection to a tag with cached access to its blocks.
+#[pyclass(unsendable, name = "Srix4k")]
+struct PySrix4k {
+    /// Connected tag, dropped before the context.
+    tag: Option<Srix4kCached<'static>>,
+    /// Context the device of the tag was opened from.
+    context: *mut nfc1::Context<'static>,
+}
+
+impl Drop for PySrix4k {
+    fn drop(&mut self) {
+        self.tag = None;
+        // SAFETY: the context was leaked by `new`
+        // and nothing borrows it anymore.
+        unsafe { drop(Box::from_raw(self.context)) };
+    }
+}
+
+impl PySrix4k {
+    fn tag(&mut self) -> &mut Srix4kCached<'static> {
+        self.tag.as_mut().expect("tag is connected until dropped")
+    }
+}
+
+#[pymethods]
+impl PySrix4k {
+    /// Open the device, the default one if `connstring` is `None`,
+    /// and connect to the SRIX4K in its field.
+    #[new]
+    #[pyo3(signature = (connstring = None))]
+    fn new(connstring: Option<&str>) -> PyResult<Self> {
+        let context =
+            Box::into_raw(Box::new(nfc1::Context::new().map_err(io_error)?));
+        // From now on `tag` frees the context.
+        let mut tag = PySrix4k { tag: None, context };
+
+        // SAFETY: the context lives until `tag` is dropped.
+        let device = unsafe {
+            match connstring {
+                Some(connstring) => {
+                    (*context).open_with_connstring(connstring)
+                }
+                None => (*context).open(),
+            }
+        };
+        tag.tag = Some(
+            device
+                .and_then(Srix4kCached::connect_from)
+                .map_err(io_error)?,
+        );
+        Ok(tag)
+    }
+    /// UID of the tag.
+    fn uid(&mut self) -> PyResult<u64> {
+        self.tag().uid_get().map_err(io_error)
+    }
+    /// Block data, from 0 to 127, or 255 for system.
+    fn read_block(&mut self, address: usize) -> PyResult<u32> {
+        let block_address = block_addr(address)?;
+        self.tag().block_get(block_address).map_err(io_error)
+    }
+    /// Stage block data, written by `sync`.
+    ///
+    /// Writing the system block cannot be undone, it needs
+    /// `acknowledge_permanent_damage=True`.
+    #[pyo3(
+        signature = (address, block_data, acknowledge_permanent_damage = false)
+    )]
+    fn write_block(
+        &mut self,
+        address: usize,
+        block_data: u32,
+        acknowledge_permanent_damage: bool,
+    ) -> PyResult<()> {
+        let block_address = block_addr(address)?;
+        if block_address == mem::BlockAddr::SYSTEM {
+            if !acknowledge_permanent_damage {
+                return Err(PyValueError::new_err(
+                    "writing the system block cannot be undone, pass \
+                     acknowledge_permanent_damage=True",
+                ));
+            }
+            let acknowledgment = system::acknowledge_permanent_damage();
+            return self
+                .tag()
+                .system_set(block_data, acknowledgment)
+                .map_err(io_error);
+        }
+        *self.tag().block_get_mut(block_address).map_err(io_error)? =
+            block_data;
+        Ok(())
+    }
+    /// Write the staged blocks to the tag.
+    fn sync(&mut self) -> PyResult<()> {
+        self.tag().sync().map_err(io_error)
+    }
+    /// Read the whole tag memory. Blocks past the EEPROM of the chip are
+    /// 0.
+    fn dump(&mut self) -> PyResult<PyDump> {
+        let tag = self.tag();
+        let mut dump = Srix4kDump {
+            uid: Some(tag.uid_get().map_err(io_error)?),
+            eeprom: [0; mem::BLOCK_COUNT],
+            system: Some(tag.system_get().map_err(io_error)?),
+        };
+        for i in tag.layout().eeprom() {
+            dump.eeprom[i] = tag.eeprom_get(i).map_err(io_error)?;
+        }
+        Ok(PyDump { dump })
+    }
+}
+
+/// Copy of the tag memory.
+#[pyclass(name = "Dump")]
+struct PyDump {
+    dump: Srix4kDump,
+}
+
+#[pymethods]
+impl PyDump {
+    /// Parse an EEPROM or full dump file.
+    #[staticmethod]
+    fn parse(bytes: &[u8]) -> PyResult<Self> {
+        Srix4kDump::parse(bytes)
+            .map(|dump| PyDump { dump })
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+    /// Serialize to a dump file.
+    fn serialize<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.dump.serialize())
+    }
+    /// UID of the tag, if known.
+    #[getter]
+    fn uid(&self) -> Option<u64> {
+        self.dump.uid
+    }
+    /// Blocks 0 to 127.
+    #[getter]
+    fn eeprom(&self) -> Vec<u32> {
+        self.dump.eeprom.to_vec()
+    }
+    /// System block, if known.
+    #[getter]
+    fn system(&self) -> Option<u32> {
+        self.dump.system
+    }
+    /// Block data, from 0 to 127, or 255 for system.
+    fn block(&self, address: u8) -> Option<u32> {
+        self.dump.block(address)
+    }
+    /// `(address, old, new)` of every block that differs in `new`.
+    fn diff(&self, new: &PyDump) -> Vec<(u8, u32, u32)> {
+        self.dump
+            .diff(&new.dump)
+            .blocks
+            .iter()
+            .map(|block| (block.address, block.old, block.new))
+            .collect()
+    }
+}
+
+/// High-level access to SRIX4K NFC tags.
+#[pymodule]
+fn srix4k(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySrix4k>()?;
+    m.add_class::<PyDump>()?;
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Workarounds for chips and clones deviating from the SRIX4K datasheet.
+//!
+//! `Srix4kCached::connect_from` looks up the quirks of the tag from its
+//! UID and `Srix4k` applies them to every command. Quirks of a reader
+//! and tag combination the lookup cannot detect can be set with
+//! `Srix4k::set_quirks`, the write delay alone with
+//! `Srix4k::set_write_delay`.
+
+use std::time::Duration;
+
+use iso14443b2;
+use Uid;
+
+/// Deviations of a chip from the datasheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// Name of the chip or clone family.
+    pub name: &'static str,
+    /// Time to wait after `WriteBlock` before the next command, on top of
+    /// the time the reader takes.
+    pub write_delay: Duration,
+    /// Whether the chip answers `GetUID`.
+    pub get_uid: bool,
+}
+
+/// Chips behaving as documented.
+pub static GENUINE: Quirks = Quirks {
+    name: "genuine",
+    write_delay: Duration::ZERO,
+    get_uid: true,
+};
+
+/// Clones, recognized by a UID without the ST prefix. Some of them
+/// answer the next command while still programming the block, the whole
+/// programming time is waited after every write.
+pub static CLONE: Quirks = Quirks {
+    name: "clone",
+    write_delay: iso14443b2::WRITE_TIME,
+    get_uid: true,
+};
+
+/// Clones not answering `GetUID`.
+pub static NO_GET_UID: Quirks = Quirks {
+    name: "clone without GetUID",
+    write_delay: iso14443b2::WRITE_TIME,
+    get_uid: false,
+};
+
+/// Quirks of the tag with `uid`, `None` if it did not answer `GetUID`.
+pub fn lookup(uid: Option<u64>) -> &'static Quirks {
+    match uid {
+        Some(uid) if Uid(uid).is_st_manufactured() => &GENUINE,
+        Some(_) => &CLONE,
+        None => &NO_GET_UID,
+    }
+}
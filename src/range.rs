@@ -0,0 +1,428 @@
+//! Sets of blocks and the range expressions denoting them, as taken by
+//! the command line.
+//!
+//! An expression is a comma separated list of addresses and ranges, each
+//! number decimal or `0x` prefixed hexadecimal, e.g. `0-4,16..32,0x70-0x7F`:
+//!
+//! | Item | Blocks |
+//! | --- | --- |
+//! | `5` | block 5 |
+//! | `0-4`, `0..=4` | blocks 0 to 4 |
+//! | `16..32` | blocks 16 to 31 |
+//!
+//! Every block must be in the EEPROM or be the system block.
+//!
+//! `BlockSet` selects the blocks of selective operations, e.g.
+//! `Srix4k::restore`, `Srix4kCached::sync_blocks` and `DumpDiff::filter`,
+//! and displays as an expression parsing back to it.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitOr, Range, Sub};
+use std::str::FromStr;
+
+use mem::{self, BlockAddr};
+
+/// Set of block addresses, iterated in address order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BlockSet([u64; 4]);
+
+impl BlockSet {
+    /// No block.
+    pub const EMPTY: BlockSet = BlockSet([0; 4]);
+
+    /// Blocks of the EEPROM.
+    pub fn eeprom() -> BlockSet {
+        BlockSet::from(mem::EEPROM)
+    }
+    /// Blocks of the EEPROM and the system block.
+    pub fn all() -> BlockSet {
+        let mut blocks = BlockSet::eeprom();
+        blocks.insert(BlockAddr::SYSTEM);
+        blocks
+    }
+    /// Add a block, returning whether it was not in the set.
+    pub fn insert(&mut self, block_address: BlockAddr) -> bool {
+        let (word, bit) = BlockSet::position(block_address);
+        let absent = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        absent
+    }
+    /// Remove a block, returning whether it was in the set.
+    pub fn remove(&mut self, block_address: BlockAddr) -> bool {
+        let present = self.contains(block_address);
+        let (word, bit) = BlockSet::position(block_address);
+        self.0[word] &= !bit;
+        present
+    }
+    /// Whether the block is in the set.
+    pub fn contains(&self, block_address: BlockAddr) -> bool {
+        let (word, bit) = BlockSet::position(block_address);
+        self.0[word] & bit != 0
+    }
+    /// Whether the block at `address`, an EEPROM index or `SYSTEM_ADDR`,
+    /// is in the set.
+    pub fn contains_address(&self, address: usize) -> bool {
+        BlockAddr::new(address).is_some_and(|block| self.contains(block))
+    }
+    /// Number of blocks.
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+    /// Whether there is no block.
+    pub fn is_empty(&self) -> bool {
+        *self == BlockSet::EMPTY
+    }
+    /// Blocks in either set.
+    pub fn union(&self, other: &BlockSet) -> BlockSet {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0.iter()) {
+            *word |= other;
+        }
+        BlockSet(words)
+    }
+    /// Blocks in both sets.
+    pub fn intersection(&self, other: &BlockSet) -> BlockSet {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0.iter()) {
+            *word &= other;
+        }
+        BlockSet(words)
+    }
+    /// Blocks of this set not in `other`.
+    pub fn difference(&self, other: &BlockSet) -> BlockSet {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0.iter()) {
+            *word &= !other;
+        }
+        BlockSet(words)
+    }
+    /// Blocks in address order.
+    pub fn iter(&self) -> Iter {
+        Iter {
+            blocks: *self,
+            next: 0,
+        }
+    }
+    /// Word and bit of the block.
+    fn position(block_address: BlockAddr) -> (usize, u64) {
+        let address = block_address.index();
+        (address / 64, 1 << (address % 64))
+    }
+}
+
+/// Iterator of the blocks of a `BlockSet`, in address order.
+#[derive(Debug, Clone)]
+pub struct Iter {
+    /// Set iterated.
+    blocks: BlockSet,
+    /// Next address to check.
+    next: usize,
+}
+
+impl Iterator for Iter {
+    type Item = BlockAddr;
+
+    fn next(&mut self) -> Option<BlockAddr> {
+        while self.next <= mem::SYSTEM_ADDR {
+            let address = self.next;
+            self.next += 1;
+            if self.blocks.contains_address(address) {
+                return BlockAddr::new(address);
+            }
+        }
+        None
+    }
+}
+
+impl IntoIterator for BlockSet {
+    type Item = BlockAddr;
+    type IntoIter = Iter;
+
+    fn into_iter(self) -> Iter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &BlockSet {
+    type Item = BlockAddr;
+    type IntoIter = Iter;
+
+    fn into_iter(self) -> Iter {
+        self.iter()
+    }
+}
+
+impl FromIterator<BlockAddr> for BlockSet {
+    fn from_iter<T: IntoIterator<Item = BlockAddr>>(iter: T) -> Self {
+        let mut blocks = BlockSet::EMPTY;
+        blocks.extend(iter);
+        blocks
+    }
+}
+
+impl Extend<BlockAddr> for BlockSet {
+    fn extend<T: IntoIterator<Item = BlockAddr>>(&mut self, iter: T) {
+        for block_address in iter {
+            self.insert(block_address);
+        }
+    }
+}
+
+impl From<Range<usize>> for BlockSet {
+    /// Blocks of a range of the memory map, clipped to the EEPROM.
+    fn from(range: Range<usize>) -> Self {
+        mem::Blocks::blocks(&range).collect()
+    }
+}
+
+impl BitOr for BlockSet {
+    type Output = BlockSet;
+
+    fn bitor(self, rhs: BlockSet) -> BlockSet {
+        self.union(&rhs)
+    }
+}
+
+impl BitAnd for BlockSet {
+    type Output = BlockSet;
+
+    fn bitand(self, rhs: BlockSet) -> BlockSet {
+        self.intersection(&rhs)
+    }
+}
+
+impl Sub for BlockSet {
+    type Output = BlockSet;
+
+    fn sub(self, rhs: BlockSet) -> BlockSet {
+        self.difference(&rhs)
+    }
+}
+
+impl FromStr for BlockSet {
+    type Err = RangeError;
+
+    fn from_str(expression: &str) -> Result<BlockSet, RangeError> {
+        parse(expression)
+    }
+}
+
+impl fmt::Display for BlockSet {
+    /// Range expression of the set, e.g. `0-4,16-31,255`, empty if the
+    /// set is.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut separator = "";
+        let mut blocks = self.iter().map(|block| block.index()).peekable();
+        while let Some(start) = blocks.next() {
+            let mut end = start;
+            while blocks.peek() == Some(&(end + 1)) {
+                end += 1;
+                blocks.next();
+            }
+            if start == end {
+                write!(f, "{}{}", separator, start)?;
+            } else {
+                write!(f, "{}{}-{}", separator, start, end)?;
+            }
+            separator = ",";
+        }
+        Ok(())
+    }
+}
+
+/// Errors that can occur while parsing a block range expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeError {
+    /// `InvalidNumber(text)`
+    /// The text is neither a decimal nor a hexadecimal number.
+    InvalidNumber(String),
+    /// `InvalidAddress(item, address)`
+    /// A block of the item is neither in the EEPROM nor the system block.
+    InvalidAddress(String, usize),
+    /// `EmptyRange(item)`
+    /// The range has no block, its end is before its start.
+    EmptyRange(String),
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeError::InvalidNumber(text) => write!(
+                f,
+                "invalid block address `{}`, expected a decimal or 0x \
+                 hexadecimal number",
+                text
+            ),
+            RangeError::InvalidAddress(item, address) => write!(
+                f,
+                "block {:#04X} of `{}` is outside the memory map",
+                address, item
+            ),
+            RangeError::EmptyRange(item) => {
+                write!(f, "range `{}` has no block", item)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+/// Parse a block range expression into the set of its blocks.
+pub fn parse(expression: &str) -> Result<BlockSet, RangeError> {
+    let mut blocks = BlockSet::EMPTY;
+    for item in expression.split(',').map(str::trim) {
+        let (start, end) = if let Some((start, end)) = item.split_once("..=") {
+            (number(start)?, number(end)?)
+        } else if let Some((start, end)) = item.split_once("..") {
+            let end = number(end)?;
+            if end == 0 {
+                return Err(RangeError::EmptyRange(item.to_string()));
+            }
+            (number(start)?, end - 1)
+        } else if let Some((start, end)) = item.split_once('-') {
+            (number(start)?, number(end)?)
+        } else {
+            let address = number(item)?;
+            (address, address)
+        };
+        if end < start {
+            return Err(RangeError::EmptyRange(item.to_string()));
+        }
+        for address in start..=end {
+            let block_address = BlockAddr::new(address).ok_or_else(|| {
+                RangeError::InvalidAddress(item.to_string(), address)
+            })?;
+            blocks.insert(block_address);
+        }
+    }
+    Ok(blocks)
+}
+
+/// Value of a decimal or `0x` prefixed hexadecimal number.
+fn number(text: &str) -> Result<usize, RangeError> {
+    let text = text.trim();
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| RangeError::InvalidNumber(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(blocks: &BlockSet) -> Vec<usize> {
+        blocks.iter().map(|block| block.index()).collect()
+    }
+
+    #[test]
+    fn items() {
+        assert_eq!(addresses(&parse("5").unwrap()), [5]);
+        assert_eq!(addresses(&parse("0-4").unwrap()), [0, 1, 2, 3, 4]);
+        assert_eq!(parse("0..=4"), parse("0-4"));
+        assert_eq!(parse("0..5"), parse("0-4"));
+        assert_eq!(parse("0x10-0x12"), parse("16-18"));
+        assert_eq!(parse(" 1 , 3 - 4 "), parse("1,3-4"));
+        assert_eq!(parse("0-127"), Ok(BlockSet::eeprom()));
+    }
+
+    #[test]
+    fn system_block() {
+        assert_eq!(addresses(&parse("255").unwrap()), [mem::SYSTEM_ADDR]);
+        assert_eq!(parse("0xFF"), parse("255"));
+        assert_eq!(parse("0-127,255"), Ok(BlockSet::all()));
+        assert_eq!(
+            parse("127-255"),
+            Err(RangeError::InvalidAddress("127-255".to_string(), 128))
+        );
+    }
+
+    #[test]
+    fn overlaps_are_merged() {
+        let blocks = parse("0-10,5-15,7,15").unwrap();
+        assert_eq!(blocks, parse("0-15").unwrap());
+        assert_eq!(blocks.len(), 16);
+    }
+
+    #[test]
+    fn reversed_ranges() {
+        assert_eq!(
+            parse("4-0"),
+            Err(RangeError::EmptyRange("4-0".to_string()))
+        );
+        assert_eq!(
+            parse("4..=3"),
+            Err(RangeError::EmptyRange("4..=3".to_string()))
+        );
+        assert_eq!(
+            parse("4..4"),
+            Err(RangeError::EmptyRange("4..4".to_string()))
+        );
+        assert_eq!(
+            parse("0..0"),
+            Err(RangeError::EmptyRange("0..0".to_string()))
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(
+            parse("128"),
+            Err(RangeError::InvalidAddress("128".to_string(), 128))
+        );
+        assert_eq!(
+            parse("0..129"),
+            Err(RangeError::InvalidAddress("0..129".to_string(), 128))
+        );
+        assert_eq!(
+            parse("256"),
+            Err(RangeError::InvalidAddress("256".to_string(), 256))
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(parse(""), Err(RangeError::InvalidNumber(String::new())));
+        assert_eq!(
+            parse("1,,2"),
+            Err(RangeError::InvalidNumber(String::new()))
+        );
+        assert_eq!(parse("1-"), Err(RangeError::InvalidNumber(String::new())));
+        assert_eq!(
+            parse("0xG"),
+            Err(RangeError::InvalidNumber("0xG".to_string()))
+        );
+        assert_eq!(parse("-1"), Err(RangeError::InvalidNumber(String::new())));
+    }
+
+    #[test]
+    fn display_parses_back() {
+        let expressions = ["0-4,16-31,255", "7", "0-127", "1,3,5"];
+        for &expression in &expressions {
+            let blocks = parse(expression).unwrap();
+            assert_eq!(blocks.to_string(), expression);
+            assert_eq!(blocks.to_string().parse(), Ok(blocks));
+        }
+        assert_eq!(BlockSet::EMPTY.to_string(), "");
+    }
+
+    #[test]
+    fn set_operations() {
+        let low = parse("0-7").unwrap();
+        let high = parse("4-11").unwrap();
+        assert_eq!(low | high, parse("0-11").unwrap());
+        assert_eq!(low & high, parse("4-7").unwrap());
+        assert_eq!(low - high, parse("0-3").unwrap());
+        assert!((low - low).is_empty());
+
+        let mut blocks = BlockSet::EMPTY;
+        assert!(blocks.insert(BlockAddr::SYSTEM));
+        assert!(!blocks.insert(BlockAddr::SYSTEM));
+        assert!(blocks.contains_address(mem::SYSTEM_ADDR));
+        assert!(!blocks.contains_address(128));
+        assert!(blocks.remove(BlockAddr::SYSTEM));
+        assert!(blocks.is_empty());
+    }
+}
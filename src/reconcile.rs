@@ -0,0 +1,78 @@
+//! Reconciliation of the cache with blocks changed on the tag by another
+//! reader since they were read.
+//!
+//! `Srix4kCached::reconcile` reads every cached block again. A block the
+//! application did not modify takes the data of the tag. A block modified
+//! on both sides is a `Conflict`: the cache keeps the local data, which
+//! `sync` writes over the tag's unless the application sets the block
+//! back to `Conflict::tag`.
+
+use std::time::Instant;
+
+use error::CachedResult;
+use Srix4kCached;
+
+/// Block modified both on the tag and in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Conflict {
+    /// From 0 to 127, or 255 for system.
+    pub address: u8,
+    /// Block data when it was read.
+    pub original: u32,
+    /// Block data on the tag.
+    pub tag: u32,
+    /// Block data in the cache.
+    pub local: u32,
+}
+
+impl Srix4kCached<'_> {
+    /// Read the cached blocks again, taking the changes of the tag into
+    /// the cache, and return the blocks modified on both sides, in
+    /// address order.
+    ///
+    /// Fails on the first block that cannot be read, leaving the next
+    /// ones as they were.
+    pub fn reconcile(&mut self) -> CachedResult<Vec<Conflict>> {
+        let system_addr = self.layout.system_addr();
+        let cached: Vec<usize> = self
+            .eeprom
+            .iter()
+            .enumerate()
+            .chain(std::iter::once((system_addr, &self.system)))
+            .filter(|&(_, block_data)| block_data.is_some())
+            .map(|(block_address, _)| block_address)
+            .collect();
+        let mut conflicts = Vec::new();
+        for block_address in cached {
+            let address = block_address as u8;
+            let tag = self.read_block(address)?;
+            let block_data = if block_address == system_addr {
+                &mut self.system
+            } else {
+                self.read_at[block_address] = Instant::now();
+                &mut self.eeprom[block_address]
+            };
+            let (original, local) = match block_data {
+                Some(block_data) => block_data,
+                None => continue,
+            };
+            if tag == *original {
+                continue;
+            }
+            if *original != *local && tag != *local {
+                warn!("Block {:#04X} changed on the tag and here", address);
+                conflicts.push(Conflict {
+                    address,
+                    original: *original,
+                    tag,
+                    local: *local,
+                });
+            } else if *original == *local {
+                debug!("Block {:#04X} changed on the tag", address);
+                *local = tag;
+            }
+            *original = tag;
+        }
+        Ok(conflicts)
+    }
+}
@@ -0,0 +1,161 @@
+//! Recording of the commands of a session and their replay.
+//!
+//! A `Recorder` wraps a `Transport`, e.g. a connected `Srix4k`, and logs
+//! every exchange in a `CommandLog`. Replaying the log against another
+//! transport, a reader, a tag or a simulation, sends the same commands and
+//! reports the responses that differ, so reader interactions can be
+//! regression tested. With the `serde` feature logs can be saved in any
+//! serde format.
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use codec::Response;
+use Command;
+#[cfg(feature = "nfc")]
+use Srix4k;
+
+/// Something commands can be sent to.
+pub trait Transport {
+    /// Error of the exchanges.
+    type Error;
+
+    /// Send `command` and return the response of the tag, `None` for
+    /// `WriteBlock`.
+    fn execute(
+        &mut self,
+        command: &Command,
+    ) -> Result<Option<Response>, Self::Error>;
+}
+
+#[cfg(feature = "nfc")]
+impl Transport for Srix4k<'_> {
+    type Error = nfc1::Error;
+
+    fn execute(&mut self, command: &Command) -> nfc1::Result<Option<Response>> {
+        match *command {
+            Command::ReadBlock(block_address) => self
+                .send_read_block(block_address)
+                .map(|block_data| Some(Response::Block(block_data))),
+            Command::WriteBlock(block_address, block_data) => self
+                .send_write_block(block_address, block_data)
+                .map(|_| None),
+            Command::GetUid => {
+                self.send_get_uid().map(|uid| Some(Response::Uid(uid)))
+            }
+        }
+    }
+}
+
+/// Command sent and the response of the tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Exchange {
+    /// Command sent.
+    pub command: Command,
+    /// Response of the tag, `None` for `WriteBlock`.
+    pub response: Option<Response>,
+}
+
+/// Exchanges of a session, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommandLog {
+    /// Exchanges that completed. Failed commands are not recorded.
+    pub exchanges: Vec<Exchange>,
+}
+
+/// Response of a replay differing from the recorded one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Divergence {
+    /// Index of the exchange in the log.
+    pub index: usize,
+    /// Recorded exchange.
+    pub expected: Exchange,
+    /// Response of the transport the log was replayed against.
+    pub actual: Option<Response>,
+}
+
+impl CommandLog {
+    /// Send the commands of the log to `transport`, in order, and return
+    /// the exchanges whose response differs.
+    ///
+    /// Stops at the first error of the transport.
+    pub fn replay<T: Transport>(
+        &self,
+        transport: &mut T,
+    ) -> Result<Vec<Divergence>, T::Error> {
+        let mut divergences = Vec::new();
+        for (index, expected) in self.exchanges.iter().enumerate() {
+            let actual = transport.execute(&expected.command)?;
+            if actual != expected.response {
+                debug!("Exchange {}: {:?} differs", index, expected.command);
+                divergences.push(Divergence {
+                    index,
+                    expected: *expected,
+                    actual,
+                });
+            }
+        }
+        Ok(divergences)
+    }
+}
+
+impl fmt::Display for Divergence {
+    /// `index: command: expected response, got actual response`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:?}: expected {:?}, got {:?}",
+            self.index,
+            self.expected.command,
+            self.expected.response,
+            self.actual
+        )
+    }
+}
+
+/// Transport logging every exchange of the transport it wraps.
+#[derive(Debug)]
+pub struct Recorder<T> {
+    /// Transport the commands are sent to.
+    inner: T,
+    /// Exchanges so far.
+    log: CommandLog,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Record the exchanges with `inner`.
+    pub fn new(inner: T) -> Recorder<T> {
+        Recorder {
+            inner,
+            log: CommandLog::default(),
+        }
+    }
+    /// Exchanges so far.
+    pub fn log(&self) -> &CommandLog {
+        &self.log
+    }
+    /// Stop recording, returning the transport and the log.
+    pub fn into_parts(self) -> (T, CommandLog) {
+        (self.inner, self.log)
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    type Error = T::Error;
+
+    fn execute(
+        &mut self,
+        command: &Command,
+    ) -> Result<Option<Response>, T::Error> {
+        let response = self.inner.execute(command)?;
+        self.log.exchanges.push(Exchange {
+            command: *command,
+            response,
+        });
+        Ok(response)
+    }
+}
@@ -0,0 +1,122 @@
+//! Research mode probing the command codes the SRIX4K does not document,
+//! to characterize clones and undocumented chip behavior.
+//!
+//! Undocumented commands may do anything, including changing the tag
+//! irreversibly. The scan is limited by `ScanOptions`; keep `read_only`
+//! set unless the tag is expendable.
+
+use std::ops::RangeInclusive;
+use std::thread;
+use std::time::Duration;
+
+use nfc1::{Result, Timeout};
+use Srix4k;
+
+/// Command codes documented in the SRIX4K datasheet, never probed:
+/// `Initiate` and `Pcall16`, which share 0x06 and differ by parameter,
+/// `Read_block`, `Write_block`, `Get_UID`, `Reset_to_inventory`, `Select`,
+/// `Completion` and the `Slot_marker` of slots 1 to 15, 0x16 to 0xF6.
+pub const DOCUMENTED_CODES: [u8; 22] = [
+    0x06, 0x08, 0x09, 0x0B, 0x0C, 0x0E, 0x0F, 0x16, 0x26, 0x36, 0x46, 0x56,
+    0x66, 0x76, 0x86, 0x96, 0xA6, 0xB6, 0xC6, 0xD6, 0xE6, 0xF6,
+];
+
+/// Limits of a command scan.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Command codes to probe, skipping `DOCUMENTED_CODES`.
+    pub codes: RangeInclusive<u8>,
+    /// Maximum number of frames to send.
+    pub max_probes: usize,
+    /// Send the bare command code, without any argument byte that
+    /// an undocumented write could store.
+    pub read_only: bool,
+    /// Argument bytes sent after the code when not `read_only`.
+    pub arguments: Vec<u8>,
+    /// Pause between two probes.
+    pub delay: Duration,
+}
+
+/// Command code that elicited a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanHit {
+    /// Command code.
+    pub code: u8,
+    /// Frame sent.
+    pub frame: Vec<u8>,
+    /// Response of the tag.
+    pub response: Vec<u8>,
+}
+
+impl Default for ScanOptions {
+    /// Every undocumented code, read only, 10 ms apart.
+    fn default() -> Self {
+        ScanOptions {
+            codes: 0x00..=0xFF,
+            max_probes: 256,
+            read_only: true,
+            arguments: vec![0x00],
+            delay: Duration::from_millis(10),
+        }
+    }
+}
+
+impl Srix4k<'_> {
+    /// Probe the undocumented command codes and return those the tag
+    /// answered.
+    ///
+    /// The tag is selected again after every answered probe, and after a
+    /// silent one if it stopped answering `Get_UID`. The scan stops early
+    /// if the tag cannot be selected anymore, e.g. because a command
+    /// deactivated it or it left the field.
+    pub fn scan_commands(
+        &mut self,
+        options: &ScanOptions,
+    ) -> Result<Vec<ScanHit>> {
+        let uid = self.send_get_uid()?;
+        let mut hits = Vec::new();
+        let codes = options
+            .codes
+            .clone()
+            .filter(|code| !DOCUMENTED_CODES.contains(code))
+            .take(options.max_probes);
+        for code in codes {
+            let mut frame = vec![code];
+            if !options.read_only {
+                frame.extend_from_slice(&options.arguments);
+            }
+            debug!("Probing command {:#04X}", code);
+            let answer = self.device.initiator_transceive_bytes(
+                &frame,
+                64,
+                Timeout::Default,
+            );
+            let answered = match answer {
+                Ok(response) if !response.is_empty() => {
+                    info!("Command {:#04X} answered {:02X?}", code, response);
+                    hits.push(ScanHit {
+                        code,
+                        frame,
+                        response,
+                    });
+                    true
+                }
+                _ => false,
+            };
+
+            // An answered command may have changed the state of the tag:
+            // select it again. Either way, make sure the same tag is still
+            // there and selected.
+            let selected = (!answered && self.send_get_uid().ok() == Some(uid))
+                || self.select().and_then(|_| self.send_get_uid()).ok()
+                    == Some(uid);
+            if !selected {
+                warn!("Tag lost after command {:#04X}, stopping", code);
+                break;
+            }
+            thread::sleep(options.delay);
+        }
+
+        Ok(hits)
+    }
+}
@@ -0,0 +1,250 @@
+//! Application specific annotations of the tag memory.
+//!
+//! A schema gives a name to the blocks used by an application and
+//! describes the bitfields stored inside them, so block data can be shown
+//! as decoded values instead of raw hex.
+//!
+//! Schemas can be loaded from TOML (`schema-toml` feature) or YAML
+//! (`schema-yaml` feature) files:
+//!
+//! ```toml
+//! [[block]]
+//! address = 0x05
+//! label = "Counter"
+//!
+//! [[block.field]]
+//! name = "rides"
+//! offset = 0
+//! width = 21
+//! ```
+
+use mem;
+use std::fmt;
+#[cfg(any(feature = "schema-toml", feature = "schema-yaml"))]
+use std::fs;
+#[cfg(any(feature = "schema-toml", feature = "schema-yaml"))]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+/// Annotations for the blocks of a tag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct Schema {
+    /// Annotated blocks.
+    #[cfg_attr(feature = "serde", serde(default, rename = "block"))]
+    pub blocks: Vec<BlockSchema>,
+}
+
+/// Meaning of a single block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct BlockSchema {
+    /// From 0 to 127, or 255 for system.
+    pub address: u8,
+    /// Name of the block.
+    pub label: String,
+    /// Bitfields stored in the block.
+    #[cfg_attr(feature = "serde", serde(default, rename = "field"))]
+    pub fields: Vec<FieldSchema>,
+}
+
+/// Bitfield inside a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct FieldSchema {
+    /// Name of the field.
+    pub name: String,
+    /// Position of the least significant bit of the field.
+    pub offset: u8,
+    /// Number of bits of the field.
+    pub width: u8,
+}
+
+/// Errors that can occur while loading a schema.
+#[derive(Debug)]
+pub enum SchemaError {
+    /// Schema file could not be read.
+    Io(std::io::Error),
+    /// Schema file is not valid TOML.
+    #[cfg(feature = "schema-toml")]
+    Toml(toml::de::Error),
+    /// Schema file is not valid YAML.
+    #[cfg(feature = "schema-yaml")]
+    Yaml(serde_yaml::Error),
+    /// Schema file extension is not a known format.
+    UnknownFormat,
+    /// Block address is neither in the EEPROM nor the system block.
+    InvalidAddress(u8),
+    /// Block is described more than once.
+    DuplicateBlock(u8),
+    /// Field `(block_address, field_name)` is empty or exceeds the block.
+    InvalidField(u8, String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::Io(err) => write!(f, "cannot read schema: {}", err),
+            #[cfg(feature = "schema-toml")]
+            SchemaError::Toml(err) => {
+                write!(f, "invalid TOML schema: {}", err)
+            }
+            #[cfg(feature = "schema-yaml")]
+            SchemaError::Yaml(err) => {
+                write!(f, "invalid YAML schema: {}", err)
+            }
+            SchemaError::UnknownFormat => write!(f, "unknown schema format"),
+            SchemaError::InvalidAddress(address) => {
+                write!(f, "invalid block address {:#04X}", address)
+            }
+            SchemaError::DuplicateBlock(address) => {
+                write!(f, "block {:#04X} is described twice", address)
+            }
+            SchemaError::InvalidField(address, name) => write!(
+                f,
+                "field `{}` of block {:#04X} does not fit in the block",
+                name, address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<std::io::Error> for SchemaError {
+    fn from(err: std::io::Error) -> Self {
+        SchemaError::Io(err)
+    }
+}
+
+#[cfg(any(feature = "schema-toml", feature = "schema-yaml"))]
+impl Schema {
+    /// Load a schema file, choosing the format by its extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Schema, SchemaError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "schema-toml")]
+            Some("toml") => Schema::from_toml_str(&source),
+            #[cfg(feature = "schema-yaml")]
+            Some("yaml") | Some("yml") => Schema::from_yaml_str(&source),
+            _ => Err(SchemaError::UnknownFormat),
+        }
+    }
+}
+
+#[cfg(feature = "schema-toml")]
+impl Schema {
+    /// Parse a TOML schema.
+    pub fn from_toml_str(source: &str) -> Result<Schema, SchemaError> {
+        let schema: Schema =
+            toml::from_str(source).map_err(SchemaError::Toml)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+}
+
+#[cfg(feature = "schema-yaml")]
+impl Schema {
+    /// Parse a YAML schema.
+    pub fn from_yaml_str(source: &str) -> Result<Schema, SchemaError> {
+        let schema: Schema =
+            serde_yaml::from_str(source).map_err(SchemaError::Yaml)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+}
+
+impl Schema {
+    /// Check that every block and field fits the tag memory.
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        let mut seen = Vec::with_capacity(self.blocks.len());
+        for block in &self.blocks {
+            if !mem::EEPROM.contains(&(block.address as usize))
+                && block.address as usize != mem::SYSTEM_ADDR
+            {
+                return Err(SchemaError::InvalidAddress(block.address));
+            }
+            if seen.contains(&block.address) {
+                return Err(SchemaError::DuplicateBlock(block.address));
+            }
+            seen.push(block.address);
+            for field in &block.fields {
+                if field.width == 0
+                    || field.offset as u32 + field.width as u32 > 32
+                {
+                    return Err(SchemaError::InvalidField(
+                        block.address,
+                        field.name.clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+    /// Get the description of the specified block.
+    pub fn block(&self, address: u8) -> Option<&BlockSchema> {
+        self.blocks.iter().find(|block| block.address == address)
+    }
+    /// Annotate block data read from the specified block.
+    pub fn annotate(
+        &self,
+        address: u8,
+        block_data: u32,
+    ) -> Option<Annotation<'_>> {
+        self.block(address)
+            .map(|block| Annotation { block, block_data })
+    }
+}
+
+impl FieldSchema {
+    /// Extract the field value from the block data.
+    pub fn extract(&self, block_data: u32) -> u32 {
+        let mask = match self.width {
+            32 => u32::MAX,
+            width => (1 << width) - 1,
+        };
+        block_data.checked_shr(self.offset as u32).unwrap_or(0) & mask
+    }
+}
+
+/// Block data decoded with its schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Annotation<'a> {
+    /// Description of the block.
+    pub block: &'a BlockSchema,
+    /// Raw block data.
+    pub block_data: u32,
+}
+
+impl Annotation<'_> {
+    /// Iterate over `(field_name, field_value)` pairs.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, u32)> {
+        let block_data = self.block_data;
+        self.block
+            .fields
+            .iter()
+            .map(move |field| (field.name.as_str(), field.extract(block_data)))
+    }
+}
+
+impl fmt::Display for Annotation<'_> {
+    /// Format as `label (field=value, ...)`,
+    /// or `label (0x...)` for blocks without fields.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (", self.block.label)?;
+        if self.block.fields.is_empty() {
+            write!(f, "{:#010X}", self.block_data)?;
+        }
+        for (i, (name, value)) in self.fields().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}={}", name, value)?;
+        }
+        write!(f, ")")
+    }
+}
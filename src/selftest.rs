@@ -0,0 +1,215 @@
+//! Self-test of the reader, telling which features of the crate the
+//! hardware can use.
+//!
+//! `probe_capabilities`, or `srix4k selftest`, asks the reader for the
+//! modulations it supports and tries the properties the crate relies on:
+//!
+//! | Capability | Used by |
+//! | --- | --- |
+//! | ISO14443B-2 SR modulation | everything, required |
+//! | field control | `tearing` (`dangerous`), `power_down_if_idle` |
+//! | raw frames | `DeviceConfig::handle_crc`, `iso14443b2::crc_b` |
+//! | target mode | emulating a tag with the reader |
+//! | timed transceive | `fingerprint` timing, the `_timed` commands |
+//!
+//! Timed transceive can only be tried on a tag: it is untested if the
+//! field is empty.
+//!
+//! libnfc cannot read the properties back, so the probes restore the
+//! values the caller gives in a `DeviceConfig`, or the ones libnfc sets
+//! when it initializes an initiator.
+
+use std::fmt;
+
+use nfc1::{Mode, ModulationType, Property};
+use config::DeviceConfig;
+use iso14443b2;
+use mem;
+use Command;
+
+/// Capabilities of a reader, see the `selftest` module.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// Name of the reader.
+    pub name: String,
+    /// Whether the reader reads ISO14443B-2 SR tags as an initiator.
+    pub iso14443b2sr: bool,
+    /// Whether the reader can switch the RF field off and on.
+    pub field_control: bool,
+    /// Whether the reader sends and receives frames without handling
+    /// their CRC.
+    pub raw_frames: bool,
+    /// Whether the reader supports any modulation as a target.
+    pub target_mode: bool,
+    /// Whether the reader measures response times, `None` if no tag was
+    /// in the field to try.
+    pub timed_transceive: Option<bool>,
+}
+
+impl Capabilities {
+    /// Whether the reader can be used with the crate at all.
+    pub fn is_usable(&self) -> bool {
+        self.iso14443b2sr
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = [
+            ("ISO14443B-2 SR modulation", Some(self.iso14443b2sr)),
+            ("field control", Some(self.field_control)),
+            ("raw frames", Some(self.raw_frames)),
+            ("target mode", Some(self.target_mode)),
+            ("timed transceive", self.timed_transceive),
+        ];
+        write!(f, "reader: {}", self.name)?;
+        for &(capability, supported) in &rows {
+            let supported = match supported {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "untested, no tag",
+            };
+            write!(f, "\n{:<26} {}", capability, supported)?;
+        }
+        Ok(())
+    }
+}
+
+/// Values libnfc gives the properties tried by the probes when it
+/// initializes an initiator.
+pub const INITIATOR_DEFAULTS: DeviceConfig = DeviceConfig {
+    infinite_select: Some(true),
+    command_timeout: None,
+    com_timeout: None,
+    atr_timeout: None,
+    activate_field: Some(true),
+    handle_crc: Some(true),
+};
+
+/// Probe the capabilities of `device`, set up as an initiator with the
+/// `INITIATOR_DEFAULTS`, restoring the properties it tries.
+///
+/// Selects the tag in the field, if any, to try timed transceive: select
+/// it again before sending it commands. Probes the reader does not answer
+/// count as unsupported.
+pub fn probe_capabilities(device: &mut nfc1::Device) -> Capabilities {
+    probe_capabilities_restoring(device, &INITIATOR_DEFAULTS)
+}
+
+/// Probe the capabilities of `device` like `probe_capabilities`, setting
+/// the properties it tries back to the values of `prior`, or to the
+/// `INITIATOR_DEFAULTS` for those it leaves unset.
+pub fn probe_capabilities_restoring(
+    device: &mut nfc1::Device,
+    prior: &DeviceConfig,
+) -> Capabilities {
+    let iso14443b2sr = device
+        .get_supported_modulation(Mode::Initiator)
+        .map(|modulations| modulations.contains(&ModulationType::Iso14443b2sr))
+        .unwrap_or(false);
+    let target_mode = device
+        .get_supported_modulation(Mode::Target)
+        .map(|modulations| !modulations.is_empty())
+        .unwrap_or(false);
+    let field_control = toggle(
+        device,
+        Property::ActivateField,
+        prior_value(prior, |config| config.activate_field),
+    );
+    let raw_frames = toggle(
+        device,
+        Property::HandleCrc,
+        prior_value(prior, |config| config.handle_crc),
+    );
+    let timed_transceive = if iso14443b2sr {
+        probe_timed(device, prior_value(prior, |config| config.infinite_select))
+    } else {
+        None
+    };
+    Capabilities {
+        name: device.name().to_string(),
+        iso14443b2sr,
+        field_control,
+        raw_frames,
+        target_mode,
+        timed_transceive,
+    }
+}
+
+/// Value of a property in `prior`, or in the `INITIATOR_DEFAULTS`.
+fn prior_value(
+    prior: &DeviceConfig,
+    property: fn(&DeviceConfig) -> Option<bool>,
+) -> bool {
+    property(prior)
+        .or_else(|| property(&INITIATOR_DEFAULTS))
+        .unwrap_or(true)
+}
+
+/// Boolean property of a device, set back to its prior value when the
+/// guard is dropped, whichever way the probe returns.
+struct PropertyGuard<'a, 'b> {
+    device: &'a mut nfc1::Device<'b>,
+    property: Property,
+    prior: bool,
+}
+
+impl<'a, 'b> PropertyGuard<'a, 'b> {
+    /// Set `property` of `device` to `enabled`, guarding its `prior`
+    /// value. The guard restores it even if setting it fails, as the
+    /// reader may have applied it partly.
+    fn set(
+        device: &'a mut nfc1::Device<'b>,
+        property: Property,
+        enabled: bool,
+        prior: bool,
+    ) -> (PropertyGuard<'a, 'b>, nfc1::Result<()>) {
+        let result = device.set_property_bool(property, enabled);
+        let guard = PropertyGuard {
+            device,
+            property,
+            prior,
+        };
+        (guard, result)
+    }
+}
+
+impl Drop for PropertyGuard<'_, '_> {
+    fn drop(&mut self) {
+        if let Err(err) =
+            self.device.set_property_bool(self.property, self.prior)
+        {
+            warn!("Cannot restore {:?}: {}", self.property, err);
+        }
+    }
+}
+
+/// Whether `device` accepts disabling `property`, set back to `prior`
+/// after.
+fn toggle(device: &mut nfc1::Device, property: Property, prior: bool) -> bool {
+    let (_guard, result) = PropertyGuard::set(device, property, false, prior);
+    result.is_ok()
+}
+
+/// Whether `device` measures the response time of the tag in the field,
+/// `None` if there is none. Infinite select is disabled for the probe and
+/// set back to `prior` after.
+fn probe_timed(device: &mut nfc1::Device, prior: bool) -> Option<bool> {
+    let (guard, result) =
+        PropertyGuard::set(device, Property::InfiniteSelect, false, prior);
+    if let Err(err) = result {
+        debug!("Cannot disable infinite select: {}", err);
+        return None;
+    }
+    if let Err(err) = iso14443b2::select_target(guard.device) {
+        debug!("No tag to try timed transceive: {}", err);
+        return None;
+    }
+    let frame = Command::GetUid.frame();
+    Some(
+        guard
+            .device
+            .initiator_transceive_bytes_timed(&frame, mem::UID_SIZE.into())
+            .is_ok(),
+    )
+}
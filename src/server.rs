@@ -0,0 +1,539 @@
+//! HTTP interface to a reader, so the tag can be driven without linking
+//! this crate.
+//!
+//! Endpoints:
+//! - `GET /uid`: UID of the tag in the field.
+//! - `GET /dump`: UID, EEPROM and system block of the tag in the field,
+//!   the EEPROM of its chip only, e.g. 16 blocks for a SRIX512.
+//! - `GET /blocks/<address>`: data of a block.
+//! - `PUT /blocks/<address>`: write the hexadecimal request body to a block
+//!   of the EEPROM. Writing the system block cannot be undone and is
+//!   refused with 403.
+//! - `GET /events`: server-sent events stream with an `arrival` or
+//!   `removal` event each time a tag enters or leaves the field.
+//! - `GET /metrics`: counters of the reader in the Prometheus text format.
+//!
+//! Tag events are also `POST`ed to the webhooks added with `add_webhook`,
+//! and the metrics and operations exported to the OpenTelemetry collector
+//! set with `set_exporter`.
+//!
+//! Block addresses are decimal or `0x` prefixed hexadecimal, from 0 to 127,
+//! or 255 for system. Every other response except `/metrics` is a JSON
+//! object.
+//!
+//! `/metrics` counts tags seen, blocks read and written and failed
+//! operations, with a histogram of the duration of the operations.
+//!
+//! Clients are read from and written to by a thread each, and webhooks
+//! notified by a thread each, so a slow peer does not stall the reader:
+//! only the operations on the tag run on the thread of `run`.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use mem;
+use metrics::Metrics;
+use monitor::{Monitor, TagEvent};
+use otlp::{Exporter, SpanContext};
+use webhook::Webhook;
+use Srix4k;
+
+/// Maximum time to wait for a client to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum size of the request head and body.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Maximum size of the request body, larger ones are refused before being
+/// read. A block is written with at most a few tens of bytes.
+const MAX_BODY: usize = 1024;
+/// Default time between two checks of the field.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Time between two exports to the OpenTelemetry collector.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// HTTP server owning a reader.
+pub struct Server<'a> {
+    /// Socket waiting for clients.
+    listener: TcpListener,
+    /// Reader and the tag in its field.
+    monitor: Monitor<'a>,
+    /// Requests read by the client threads, with the channel of their
+    /// answer.
+    requests: mpsc::Receiver<Pending>,
+    /// Sender of `requests`, cloned for each client thread.
+    requests_sender: mpsc::Sender<Pending>,
+    /// Message channels of the `/events` clients.
+    subscribers: Vec<mpsc::Sender<String>>,
+    /// Time between two checks of the field.
+    poll_interval: Duration,
+    /// Activity of the reader, served on `/metrics`.
+    metrics: Metrics,
+    /// Endpoints notified of tag events, with the channel of the payloads
+    /// of their thread.
+    webhooks: Vec<(Webhook, mpsc::Sender<String>)>,
+    /// Exporter of the metrics and operations, if any.
+    exporter: Option<Exporter>,
+}
+
+/// Parsed HTTP request.
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// HTTP response, with a JSON body unless stated otherwise.
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+/// Request waiting for the server to answer it.
+struct Pending {
+    request: Request,
+    reply: mpsc::Sender<Reply>,
+}
+
+/// Answer of the server to a request.
+enum Reply {
+    /// Response to send, closing the connection.
+    Response(Response),
+    /// Messages of the `/events` stream to send as they come.
+    Events(mpsc::Receiver<String>),
+}
+
+impl Server<'_> {
+    /// Listen on the specified address using the device as reader.
+    pub fn bind<'a, A: ToSocketAddrs>(
+        device: nfc1::Device<'a>,
+        addr: A,
+    ) -> io::Result<Server<'a>> {
+        let monitor = Monitor::new(device)?;
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        info!("Listening on {}", listener.local_addr()?);
+        let (requests_sender, requests) = mpsc::channel();
+
+        Ok(Server {
+            listener,
+            monitor,
+            requests,
+            requests_sender,
+            subscribers: Vec::new(),
+            poll_interval: POLL_INTERVAL,
+            metrics: Metrics::default(),
+            webhooks: Vec::new(),
+            exporter: None,
+        })
+    }
+    /// Address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+    /// Set the time between two checks of the field.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+    /// Notify the webhook of every tag event, in order, from a thread of
+    /// its own.
+    pub fn add_webhook(&mut self, webhook: Webhook) {
+        let (sender, payloads) = mpsc::channel::<String>();
+        let endpoint = webhook.clone();
+        thread::spawn(move || {
+            for payload in payloads {
+                if let Err(err) = endpoint.notify(&payload) {
+                    warn!("Cannot notify webhook: {}", err);
+                }
+            }
+        });
+        self.webhooks.push((webhook, sender));
+    }
+    /// Periodically export the metrics and operations with the exporter.
+    pub fn set_exporter(&mut self, exporter: Exporter) {
+        self.exporter = Some(exporter);
+    }
+    /// Serve clients forever.
+    pub fn run(&mut self) -> io::Result<()> {
+        self.poll_tag();
+        let mut last_poll = Instant::now();
+        let mut last_export = Instant::now();
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    debug!("Accepted connection from {}", peer);
+                    let requests = self.requests_sender.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = serve(stream, requests) {
+                            warn!("Connection from {} failed: {}", peer, err);
+                        }
+                    });
+                    continue;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+            while let Ok(pending) = self.requests.try_recv() {
+                let reply = self.answer(&pending.request);
+                // The client thread is gone if its connection failed.
+                let _ = pending.reply.send(reply);
+            }
+
+            if last_poll.elapsed() >= self.poll_interval {
+                self.poll_tag();
+                last_poll = Instant::now();
+            }
+            if last_export.elapsed() >= EXPORT_INTERVAL {
+                if let Some(ref mut exporter) = self.exporter {
+                    if let Err(err) = exporter.export(&self.metrics) {
+                        warn!("Cannot export to OpenTelemetry: {}", err);
+                    }
+                }
+                last_export = Instant::now();
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Server<'_> {
+    /// Check whether a tag entered or left the field.
+    fn poll_tag(&mut self) {
+        if let Some(event) = self.monitor.poll() {
+            if let TagEvent::Arrival(_) = event {
+                self.metrics.tag_seen();
+            }
+            self.broadcast(event);
+            self.notify_webhooks(event);
+        }
+    }
+    /// Queue an event for every webhook thread.
+    fn notify_webhooks(&mut self, event: TagEvent) {
+        for (webhook, payloads) in &self.webhooks {
+            let payload = self.monitor.event_json(event, webhook.blocks());
+            // The thread only stops with the server.
+            let _ = payloads.send(payload);
+        }
+    }
+    /// Send an event to every `/events` client.
+    fn broadcast(&mut self, event: TagEvent) {
+        let message = event_message(event);
+        self.subscribers
+            .retain(|subscriber| subscriber.send(message.clone()).is_ok());
+    }
+    /// Answer a request read by a client thread.
+    fn answer(&mut self, request: &Request) -> Reply {
+        debug!("{} {}", request.method, request.path);
+        if request.method == "GET" && request.path == "/events" {
+            let (sender, messages) = mpsc::channel();
+            // Only the new client is told about the tag already there.
+            if let Some(uid) = self.monitor.uid() {
+                let _ = sender.send(event_message(TagEvent::Arrival(uid)));
+            }
+            self.subscribers.push(sender);
+            return Reply::Events(messages);
+        }
+        Reply::Response(self.route(request))
+    }
+    /// Execute the request on the tag.
+    fn route(&mut self, request: &Request) -> Response {
+        let path: Vec<&str> =
+            request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), path.as_slice()) {
+            ("GET", ["metrics"]) => Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4",
+                body: self.metrics.to_string(),
+            },
+            ("GET", ["uid"]) => self.with_tag("GET /uid", |tag, _| {
+                let uid = tag.send_get_uid()?;
+                Ok(format!("{{\"uid\":\"{:016X}\"}}", uid))
+            }),
+            ("GET", ["dump"]) => self.with_tag("GET /dump", |tag, metrics| {
+                let dump = tag.dump()?;
+                let eeprom: Vec<String> = dump
+                    .layout()
+                    .eeprom()
+                    .map(|i| format!("\"{:#010X}\"", dump.eeprom[i]))
+                    .collect();
+                for _ in 0..=eeprom.len() {
+                    metrics.block_read();
+                }
+                let uid = match dump.uid {
+                    Some(uid) => format!("\"{:016X}\"", uid),
+                    None => "null".to_string(),
+                };
+                let system = match dump.system {
+                    Some(system) => format!("\"{:#010X}\"", system),
+                    None => "null".to_string(),
+                };
+                Ok(format!(
+                    "{{\"uid\":{},\"eeprom\":[{}],\"system\":{}}}",
+                    uid,
+                    eeprom.join(","),
+                    system
+                ))
+            }),
+            ("GET", ["blocks", address]) => match parse_address(address) {
+                Some(block_address) => {
+                    self.with_tag("GET /blocks", |tag, metrics| {
+                        let block_data = tag.send_read_block(block_address)?;
+                        metrics.block_read();
+                        Ok(block_json(block_address, block_data))
+                    })
+                }
+                None => Response::error(400, "invalid block address"),
+            },
+            ("PUT", ["blocks", address]) => {
+                let block_data = match std::str::from_utf8(&request.body) {
+                    Ok(body) => parse_block_data(body.trim()),
+                    Err(_) => None,
+                };
+                match (parse_address(address), block_data) {
+                    (Some(block_address), _)
+                        if block_address as usize == mem::SYSTEM_ADDR =>
+                    {
+                        Response::error(
+                            403,
+                            "writing the system block cannot be undone",
+                        )
+                    }
+                    (Some(block_address), Some(block_data)) => {
+                        self.with_tag("PUT /blocks", |tag, metrics| {
+                            tag.send_write_block(block_address, block_data)?;
+                            metrics.block_written();
+                            Ok(block_json(block_address, block_data))
+                        })
+                    }
+                    (None, _) => Response::error(400, "invalid block address"),
+                    (_, None) => Response::error(400, "invalid block data"),
+                }
+            }
+            (_, ["metrics"])
+            | (_, ["uid"])
+            | (_, ["dump"])
+            | (_, ["blocks", _]) => Response::error(405, "method not allowed"),
+            _ => Response::error(404, "not found"),
+        }
+    }
+    /// Run the named operation on the tag in the field, selecting it if
+    /// necessary, and record its latency. The request is the top-level
+    /// span of its trace, the selection a span nested in it.
+    fn with_tag<F>(&mut self, name: &str, operation: F) -> Response
+    where
+        F: FnOnce(&mut Srix4k<'_>, &mut Metrics) -> nfc1::Result<String>,
+    {
+        let context = SpanContext::root();
+        let begin = Instant::now();
+        let begin_time = SystemTime::now();
+        let mut selection = None;
+        if self.monitor.uid().is_none() {
+            self.poll_tag();
+            selection = Some(begin.elapsed());
+        }
+        let tag = match self.monitor.tag() {
+            Some(tag) => tag,
+            None => return Response::error(503, "no tag in the field"),
+        };
+
+        let start = Instant::now();
+        let result = operation(tag, &mut self.metrics);
+        let latency = start.elapsed();
+        self.metrics.observe_latency(latency);
+        if let Some(ref mut exporter) = self.exporter {
+            if let Some(duration) = selection {
+                let child = context.child();
+                exporter
+                    .record_span(child, "select", begin_time, duration, None);
+            }
+            let error = result.as_ref().err().map(|err| err.to_string());
+            let duration = begin.elapsed();
+            exporter.record_span(context, name, begin_time, duration, error);
+        }
+        match result {
+            Ok(body) => Response {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(err) => {
+                self.metrics.error();
+                Response::error(502, &err.to_string())
+            }
+        }
+    }
+}
+
+/// Read a request from the client, have the server answer it and send the
+/// answer, on the thread of the client.
+fn serve(
+    mut stream: TcpStream,
+    requests: mpsc::Sender<Pending>,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let request = match Request::read(&mut stream) {
+        Ok(request) => request,
+        Err(response) => return response.write(&mut stream),
+    };
+    let (reply, answer) = mpsc::channel();
+    let stopped = || Response::error(503, "server stopped");
+    if requests.send(Pending { request, reply }).is_err() {
+        return stopped().write(&mut stream);
+    }
+    match answer.recv() {
+        Ok(Reply::Response(response)) => response.write(&mut stream),
+        Ok(Reply::Events(messages)) => {
+            stream.write_all(
+                b"HTTP/1.1 200 OK\r\n\
+                  Content-Type: text/event-stream\r\n\
+                  Cache-Control: no-cache\r\n\r\n",
+            )?;
+            for message in messages {
+                stream.write_all(message.as_bytes())?;
+            }
+            Ok(())
+        }
+        Err(_) => stopped().write(&mut stream),
+    }
+}
+
+impl Request {
+    /// Read request line, headers and body from the stream, or the error
+    /// response to send if the request is invalid or too large.
+    fn read(stream: &mut TcpStream) -> Result<Request, Response> {
+        let invalid = |msg| Response::error(400, msg);
+        let failed = |err: io::Error| Response::error(400, &err.to_string());
+        let mut reader =
+            BufReader::new(Read::by_ref(stream).take(MAX_REQUEST_SIZE as u64));
+
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(failed)?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next().ok_or_else(|| invalid("missing method"))?;
+        let path = parts.next().ok_or_else(|| invalid("missing path"))?;
+        let mut request = Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: Vec::new(),
+        };
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).map_err(failed)? == 0 {
+                return Err(invalid("truncated request"));
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid("invalid content length"))?;
+                }
+            }
+        }
+
+        if content_length > MAX_BODY {
+            return Err(Response::error(413, "request body too large"));
+        }
+        request.body.resize(content_length, 0);
+        reader.read_exact(&mut request.body).map_err(failed)?;
+
+        Ok(request)
+    }
+}
+
+impl Response {
+    /// JSON error response.
+    fn error(status: u16, message: &str) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: format!("{{\"error\":\"{}\"}}", escape(message)),
+        }
+    }
+    /// Send the response and close the connection.
+    fn write(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            _ => "",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\n\
+             Content-Type: {}\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal block address.
+fn parse_address(address: &str) -> Option<u8> {
+    let address = match address.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok()?,
+        None => address.parse().ok()?,
+    };
+    if mem::EEPROM.contains(&(address as usize))
+        || address as usize == mem::SYSTEM_ADDR
+    {
+        Some(address)
+    } else {
+        None
+    }
+}
+
+/// Parse hexadecimal block data, with or without `0x` prefix.
+fn parse_block_data(block_data: &str) -> Option<u32> {
+    let hex = block_data.strip_prefix("0x").unwrap_or(block_data);
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Server-sent event of a tag event.
+fn event_message(event: TagEvent) -> String {
+    format!(
+        "event: {}\ndata: {{\"uid\":\"{:016X}\"}}\n\n",
+        event.name(),
+        event.uid()
+    )
+}
+
+/// JSON object describing a block.
+fn block_json(block_address: u8, block_data: u32) -> String {
+    format!(
+        "{{\"address\":{},\"data\":\"{:#010X}\"}}",
+        block_address, block_data
+    )
+}
+
+/// Escape a string to be embedded in JSON.
+fn escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            c if c.is_control() => vec![' '],
+            c => vec![c],
+        })
+        .collect()
+}
@@ -0,0 +1,99 @@
+//! Lifecycle of the connection to a tag, for applications following it.
+//!
+//! `Srix4kCached::events` subscribes to the `SessionEvent`s of a cache: the
+//! tag dropping out and coming back under `keep_alive`, leaving the field
+//! and the end of each `sync`. GUIs get a single subscription point instead
+//! of polling each of them.
+//!
+//! `Srix4kSessionLoop` runs the whole lifecycle of a kiosk: wait for a tag,
+//! identify it, hand it to the application, wait for its removal, again.
+
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use nfc1::Result;
+use {Srix4k, Srix4kCached};
+
+/// Change in the connection to the tag of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionEvent {
+    /// `Connected(uid)`
+    /// The session is connected to the tag, with its UID if known. Sent
+    /// first to each subscriber.
+    Connected(Option<u64>),
+    /// `Removed(uid)`
+    /// The tag left the field: `keep_alive` could not select it again,
+    /// another tag took its place or `wait_for_removal` saw it go.
+    Removed(Option<u64>),
+    /// `Reconnected(uid)`
+    /// The tag dropped out and `keep_alive` selected it again.
+    Reconnected(Option<u64>),
+    /// `SyncCompleted { written, remaining }`
+    /// A sync wrote `written` blocks, `remaining` failed and stay
+    /// modified.
+    SyncCompleted { written: usize, remaining: usize },
+}
+
+/// Subscribers to the events of a session.
+#[derive(Debug, Default)]
+pub(crate) struct Subscribers(Vec<Sender<SessionEvent>>);
+
+impl Subscribers {
+    /// Add a subscriber, sending it `first`.
+    pub(crate) fn subscribe(
+        &mut self,
+        first: SessionEvent,
+    ) -> Receiver<SessionEvent> {
+        let (sender, receiver) = mpsc::channel();
+        // The receiver is alive, sending cannot fail.
+        let _ = sender.send(first);
+        self.0.push(sender);
+        receiver
+    }
+    /// Send `event` to every subscriber, forgetting those gone.
+    pub(crate) fn emit(&mut self, event: SessionEvent) {
+        trace!("Session event {:?}", event);
+        self.0.retain(|sender| sender.send(event).is_ok());
+    }
+}
+
+/// Reader serving the tags presented to it one after another.
+pub struct Srix4kSessionLoop<'a> {
+    /// Reader, without a tag selected between sessions.
+    device: nfc1::Device<'a>,
+}
+
+impl<'a> Srix4kSessionLoop<'a> {
+    /// Serve the tags presented to the device.
+    pub fn new(device: nfc1::Device<'a>) -> Srix4kSessionLoop<'a> {
+        Srix4kSessionLoop { device }
+    }
+    /// For each tag entering the field: connect to it, detecting its chip
+    /// like `Srix4kCached::connect_from`, call `session` with the cache,
+    /// then wait until the tag leaves. Stops after the session returning
+    /// `Break`, once its tag left, and returns the device.
+    ///
+    /// Blocks not synced by `session` are dropped. Fails on the errors of
+    /// the reader while waiting, closing the device.
+    pub fn run<F>(self, mut session: F) -> Result<nfc1::Device<'a>>
+    where
+        F: FnMut(&mut Srix4kCached<'a>) -> ControlFlow<()>,
+    {
+        let mut device = self.device;
+        loop {
+            let mut tag =
+                Srix4kCached::detect(Srix4k::wait_for_tag(device, None)?);
+            match tag.uid {
+                Some(uid) => info!("Session with tag {:016X}", uid),
+                None => info!("Session with a tag without UID"),
+            }
+            let flow = session(&mut tag);
+            tag.wait_for_removal(None)?;
+            device = tag.into_tag().into_inner();
+            if flow.is_break() {
+                debug!("Session loop stopped");
+                return Ok(device);
+            }
+        }
+    }
+}
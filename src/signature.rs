@@ -0,0 +1,202 @@
+//! Signed dump files, so that tag images exchanged between organizations
+//! can be checked for tampering or accidental modification.
+//!
+//! A signed dump is a dump file followed by a trailer with the Ed25519
+//! public key of the signer and the signature of the dump bytes:
+//!
+//! | Offset from the trailer | Size | Content |
+//! | --- | --- | --- |
+//! | 0 | 8 | `SRIXSIG1` |
+//! | 8 | 32 | public key |
+//! | 40 | 64 | signature |
+//!
+//! The crate does not implement Ed25519 itself: `sign` and `verify` take
+//! the `Signer` and `Verifier` of the cryptography library of the
+//! application. With the `ed25519` feature, `Ed25519Signer` and
+//! `Ed25519Verifier` implement them with `ed25519-dalek`.
+
+use std::convert::TryInto;
+use std::fmt;
+
+use dump::{self, DumpError, Srix4kDump};
+
+/// First bytes of the trailer of a signed dump.
+pub const MAGIC: [u8; 8] = *b"SRIXSIG1";
+/// Size of an Ed25519 public key.
+pub const PUBLIC_KEY_SIZE: usize = 32;
+/// Size of an Ed25519 signature.
+pub const SIGNATURE_SIZE: usize = 64;
+/// Size of the trailer of a signed dump.
+pub const TRAILER_SIZE: usize = MAGIC.len() + PUBLIC_KEY_SIZE + SIGNATURE_SIZE;
+
+/// Ed25519 public key.
+pub type PublicKey = [u8; PUBLIC_KEY_SIZE];
+/// Ed25519 signature.
+pub type Signature = [u8; SIGNATURE_SIZE];
+
+/// Private key signing dumps.
+pub trait Signer {
+    /// Public key of the signer.
+    fn public_key(&self) -> PublicKey;
+    /// Signature of `message`.
+    fn sign(&self, message: &[u8]) -> Signature;
+}
+
+/// Implementation of the Ed25519 verification.
+pub trait Verifier {
+    /// Whether `signature` is the signature of `message` by `public_key`.
+    fn verify(
+        &self,
+        public_key: &PublicKey,
+        message: &[u8],
+        signature: &Signature,
+    ) -> bool;
+}
+
+/// `Signer` holding an Ed25519 secret key, with the `ed25519` feature.
+#[cfg(feature = "ed25519")]
+pub struct Ed25519Signer(ed25519_dalek::SigningKey);
+
+#[cfg(feature = "ed25519")]
+impl Ed25519Signer {
+    /// Signer with the 32 bytes of an Ed25519 secret key.
+    pub fn from_secret_key(secret_key: &[u8; 32]) -> Ed25519Signer {
+        Ed25519Signer(ed25519_dalek::SigningKey::from_bytes(secret_key))
+    }
+}
+
+#[cfg(feature = "ed25519")]
+impl Signer for Ed25519Signer {
+    fn public_key(&self) -> PublicKey {
+        self.0.verifying_key().to_bytes()
+    }
+    fn sign(&self, message: &[u8]) -> Signature {
+        ed25519_dalek::Signer::sign(&self.0, message).to_bytes()
+    }
+}
+
+/// `Verifier` of `ed25519-dalek`, with the `ed25519` feature. Rejects the
+/// weak keys and malleable signatures, see `VerifyingKey::verify_strict`.
+#[cfg(feature = "ed25519")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Ed25519Verifier;
+
+#[cfg(feature = "ed25519")]
+impl Verifier for Ed25519Verifier {
+    fn verify(
+        &self,
+        public_key: &PublicKey,
+        message: &[u8],
+        signature: &Signature,
+    ) -> bool {
+        let signature = ed25519_dalek::Signature::from_bytes(signature);
+        ed25519_dalek::VerifyingKey::from_bytes(public_key)
+            .and_then(|key| key.verify_strict(message, &signature))
+            .is_ok()
+    }
+}
+
+/// Errors that can occur while verifying a signed dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The file does not end with a signature trailer.
+    Unsigned,
+    /// `UntrustedKey(public_key)`
+    /// The dump is signed by a key that is not trusted.
+    UntrustedKey(PublicKey),
+    /// The signature does not match the dump, which was modified.
+    Invalid,
+    /// `Dump(error)`
+    /// The signed bytes are not a dump.
+    Dump(DumpError),
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::Unsigned => write!(f, "dump is not signed"),
+            SignatureError::UntrustedKey(public_key) => {
+                write!(f, "dump signed by untrusted key ")?;
+                for byte in public_key.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            SignatureError::Invalid => {
+                write!(f, "invalid signature, the dump was modified")
+            }
+            SignatureError::Dump(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+impl From<DumpError> for SignatureError {
+    fn from(err: DumpError) -> Self {
+        SignatureError::Dump(err)
+    }
+}
+
+/// Dump file of `dump`, see `Srix4kDump::serialize`, signed by `signer`.
+pub fn sign(dump: &Srix4kDump, signer: &dyn Signer) -> Vec<u8> {
+    let mut bytes = dump.serialize();
+    let signature = signer.sign(&bytes);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&signer.public_key());
+    bytes.extend_from_slice(&signature);
+    bytes
+}
+
+/// Public key of its 64 hexadecimal digits, as displayed by
+/// `SignatureError::UntrustedKey`.
+pub fn parse_public_key(hex: &str) -> Option<PublicKey> {
+    let hex = hex.trim();
+    let digits = hex.bytes().all(|byte| byte.is_ascii_hexdigit());
+    if hex.len() != 2 * PUBLIC_KEY_SIZE || !digits {
+        return None;
+    }
+    let mut public_key = [0; PUBLIC_KEY_SIZE];
+    for (byte, digits) in public_key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let digits = std::str::from_utf8(digits).ok()?;
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+    Some(public_key)
+}
+
+/// Split a signed dump file into the signed bytes, the public key and the
+/// signature, `None` if it has no trailer.
+pub fn split(bytes: &[u8]) -> Option<(&[u8], PublicKey, Signature)> {
+    let signed = bytes.len().checked_sub(TRAILER_SIZE)?;
+    let (message, trailer) = bytes.split_at(signed);
+    let (magic, trailer) = trailer.split_at(MAGIC.len());
+    if magic != MAGIC {
+        return None;
+    }
+    let (public_key, signature) = trailer.split_at(PUBLIC_KEY_SIZE);
+    Some((
+        message,
+        public_key.try_into().ok()?,
+        signature.try_into().ok()?,
+    ))
+}
+
+/// Parse a signed dump file, or a zstd archive of one, checking that it is
+/// signed by one of `trusted` keys and was not modified since. Returns the
+/// dump and the key that signed it.
+pub fn verify(
+    bytes: &[u8],
+    verifier: &dyn Verifier,
+    trusted: &[PublicKey],
+) -> Result<(Srix4kDump, PublicKey), SignatureError> {
+    let bytes = &*dump::decompress(bytes)?;
+    let (message, public_key, signature) =
+        split(bytes).ok_or(SignatureError::Unsigned)?;
+    if !trusted.contains(&public_key) {
+        return Err(SignatureError::UntrustedKey(public_key));
+    }
+    if !verifier.verify(&public_key, message, &signature) {
+        return Err(SignatureError::Invalid);
+    }
+    Ok((Srix4kDump::parse(message)?, public_key))
+}
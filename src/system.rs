@@ -0,0 +1,192 @@
+//! Typed view of the *System OTP bits* block, 255.
+//!
+//! | Bits | Content |
+//! | --- | --- |
+//! | 31 to 24 | `OTP_Lock_Reg`, lock bits of the *Lockable EEPROM* |
+//! | 23 to 8 | Reserved by ST |
+//! | 7 to 0 | Fixed chip ID |
+//!
+//! Bit 24 locks blocks 7 and 8, bits 25 to 31 lock blocks 9 to 15. A
+//! block is locked when its bit is cleared, which cannot be undone, and
+//! the other bits cannot be written.
+
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+
+/// Blocks of the *Lockable EEPROM* locked by the system block. Unlike
+/// the raw bits, a flag is set when its blocks are locked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LockBits(u8);
+
+impl LockBits {
+    /// No block locked.
+    pub const NONE: LockBits = LockBits(0);
+    /// Blocks 7 and 8, sharing one bit.
+    pub const BLOCKS_7_8: LockBits = LockBits(1 << 0);
+    /// Block 9.
+    pub const BLOCK_9: LockBits = LockBits(1 << 1);
+    /// Block 10.
+    pub const BLOCK_10: LockBits = LockBits(1 << 2);
+    /// Block 11.
+    pub const BLOCK_11: LockBits = LockBits(1 << 3);
+    /// Block 12.
+    pub const BLOCK_12: LockBits = LockBits(1 << 4);
+    /// Block 13.
+    pub const BLOCK_13: LockBits = LockBits(1 << 5);
+    /// Block 14.
+    pub const BLOCK_14: LockBits = LockBits(1 << 6);
+    /// Block 15.
+    pub const BLOCK_15: LockBits = LockBits(1 << 7);
+    /// Every lockable block.
+    pub const ALL: LockBits = LockBits(0xFF);
+
+    /// Flags from their bits, bit 0 for blocks 7 and 8.
+    pub fn from_bits(bits: u8) -> LockBits {
+        LockBits(bits)
+    }
+    /// Bits of the flags, bit 0 for blocks 7 and 8.
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+    /// Flag locking the block, `None` if it is not lockable.
+    pub fn for_block(block_address: u8) -> Option<LockBits> {
+        match block_address {
+            7 | 8 => Some(LockBits::BLOCKS_7_8),
+            9..=15 => Some(LockBits(1 << (block_address - 8))),
+            _ => None,
+        }
+    }
+    /// Whether every flag of `other` is set.
+    pub fn contains(&self, other: LockBits) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// Whether no flag is set.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for LockBits {
+    type Output = LockBits;
+
+    fn bitor(self, rhs: LockBits) -> LockBits {
+        LockBits(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for LockBits {
+    fn bitor_assign(&mut self, rhs: LockBits) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for LockBits {
+    type Output = LockBits;
+
+    fn bitand(self, rhs: LockBits) -> LockBits {
+        LockBits(self.0 & rhs.0)
+    }
+}
+
+/// Acknowledgment that a write may damage the tag for good, taken by the
+/// operations writing the system block, whose lock bits cannot be set
+/// again once cleared.
+///
+/// Only `acknowledge_permanent_damage` makes one, and each operation
+/// consumes its own, so such a write cannot happen by accident.
+#[derive(Debug, PartialEq, Eq)]
+pub struct IrreversibleWrite(());
+
+/// Acknowledge that the next irreversible write, e.g. locking blocks of
+/// the *Lockable EEPROM*, cannot be undone.
+pub fn acknowledge_permanent_damage() -> IrreversibleWrite {
+    IrreversibleWrite(())
+}
+
+/// Errors of the modifications of the system block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemBlockError {
+    /// `ReadOnly(mask)`
+    /// The modification changes the reserved bits or the chip ID.
+    ReadOnly(u32),
+    /// `Unlock(locks)`
+    /// The modification unlocks blocks, which OTP bits cannot do.
+    Unlock(LockBits),
+}
+
+impl fmt::Display for SystemBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SystemBlockError::ReadOnly(mask) => {
+                write!(f, "read only system bits {:#010X} changed", mask)
+            }
+            SystemBlockError::Unlock(locks) => {
+                write!(f, "cannot unlock blocks {:#04X}", locks.bits())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SystemBlockError {}
+
+/// Data of the system block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SystemBlock(u32);
+
+impl SystemBlock {
+    /// Bits of `OTP_Lock_Reg`.
+    pub const LOCK_MASK: u32 = 0xFF00_0000;
+    /// Bits reserved by ST.
+    pub const RESERVED_MASK: u32 = 0x00FF_FF00;
+    /// Bits of the fixed chip ID.
+    pub const CHIP_ID_MASK: u32 = 0x0000_00FF;
+
+    /// Raw block data.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+    /// Fixed chip ID.
+    pub fn chip_id(&self) -> u8 {
+        (self.0 & SystemBlock::CHIP_ID_MASK) as u8
+    }
+    /// Locked blocks.
+    pub fn locks(&self) -> LockBits {
+        LockBits(!(self.0 >> 24) as u8)
+    }
+    /// Whether the block is locked, `None` if it is not lockable.
+    pub fn is_locked(&self, block_address: u8) -> Option<bool> {
+        LockBits::for_block(block_address)
+            .map(|lock| self.locks().contains(lock))
+    }
+    /// Lock the blocks of `locks` too. Blocks already locked stay locked.
+    pub fn lock(&mut self, locks: LockBits) {
+        self.0 &= !((locks.0 as u32) << 24);
+    }
+    /// Replace the raw block data, refusing changes of the read only bits
+    /// and unlocks.
+    pub fn set(&mut self, bits: u32) -> Result<(), SystemBlockError> {
+        let read_only = SystemBlock::RESERVED_MASK | SystemBlock::CHIP_ID_MASK;
+        let changed = (self.0 ^ bits) & read_only;
+        if changed != 0 {
+            return Err(SystemBlockError::ReadOnly(changed));
+        }
+        let unlocked = self.locks().0 & !SystemBlock(bits).locks().0;
+        if unlocked != 0 {
+            return Err(SystemBlockError::Unlock(LockBits(unlocked)));
+        }
+        self.0 = bits;
+        Ok(())
+    }
+}
+
+impl From<u32> for SystemBlock {
+    fn from(value: u32) -> Self {
+        SystemBlock(value)
+    }
+}
+
+impl From<SystemBlock> for u32 {
+    fn from(value: SystemBlock) -> Self {
+        value.0
+    }
+}
@@ -0,0 +1,90 @@
+//! Controlled tear-off of writes, enabled by the `dangerous` feature, to
+//! study how genuine and clone chips behave when the field drops while a
+//! block is being programmed.
+//!
+//! Tearing writes can leave blocks, including OTP and counter blocks, in
+//! states no regular write can undo. Only use expendable tags.
+
+use std::thread;
+use std::time::Duration;
+
+use mem;
+use nfc1::{Property, Result, Timeout};
+use Command;
+use Srix4k;
+
+/// Time the field stays off after the tear.
+const FIELD_OFF: Duration = Duration::from_millis(50);
+
+/// Outcome of a torn write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tear {
+    /// Time between the write command and the field drop.
+    pub delay: Duration,
+    /// Block data before the write.
+    pub before: u32,
+    /// Block data the write would have programmed.
+    pub written: u32,
+    /// Block data read back after the tear.
+    pub after: u32,
+}
+
+impl Tear {
+    /// Bits that changed, either way.
+    pub fn changed_bits(&self) -> u32 {
+        self.before ^ self.after
+    }
+    /// Whether the write completed despite the tear.
+    pub fn completed(&self) -> bool {
+        self.after == self.written
+    }
+}
+
+impl Srix4k<'_> {
+    /// Send `WriteBlock` and drop the field `delay` after it, then select
+    /// the tag again and read the block back.
+    ///
+    /// The SRIX4K needs about 5 ms to program a block, shorter delays tear
+    /// the write. The actual delay also includes the latency of the reader.
+    ///
+    /// Fails with `InvalidArgument` for the system block, like
+    /// `send_write_block`.
+    pub fn tear_write(
+        &mut self,
+        block_address: u8,
+        block_data: u32,
+        delay: Duration,
+    ) -> Result<Tear> {
+        if block_address as usize == mem::SYSTEM_ADDR {
+            return Err(nfc1::Error::InvalidArgument);
+        }
+        let before = self.send_read_block(block_address)?;
+        warn!(
+            "Tearing write of {:#010X} to block {:#04X} after {:?}",
+            block_data, block_address, delay
+        );
+
+        let frame: Vec<u8> =
+            Command::WriteBlock(block_address, block_data).into();
+        // The write has no answer: waiting for one for `delay` is the delay.
+        let _ = self.device.initiator_transceive_bytes(
+            &frame,
+            0,
+            Timeout::Duration(delay),
+        );
+        self.device
+            .set_property_bool(Property::ActivateField, false)?;
+        thread::sleep(FIELD_OFF);
+        self.device
+            .set_property_bool(Property::ActivateField, true)?;
+
+        self.select()?;
+        let after = self.send_read_block(block_address)?;
+        Ok(Tear {
+            delay,
+            before,
+            written: block_data,
+            after,
+        })
+    }
+}
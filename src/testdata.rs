@@ -0,0 +1,224 @@
+//! Fixtures shared by the tests of the crate and of its dependents: UIDs,
+//! dumps, and the frames of the commands with the answers of a tag.
+//!
+//! The values do not change between releases, so tests can compare them
+//! with recorded sessions and dump files.
+
+use dump::Srix4kDump;
+use iso14443b2;
+use mem;
+use Command;
+
+/// UID of a genuine SRIX4K, product code `0x03`.
+pub const SRIX4K_UID: u64 = 0xD002_0C00_1234_5678;
+/// UID of a genuine SRIX512, product code `0x04`.
+pub const SRIX512_UID: u64 = 0xD002_1000_1234_5678;
+/// UID of a genuine ST25TB02K, product code `0x3F`.
+pub const ST25TB02K_UID: u64 = 0xD002_FC00_1234_5678;
+/// UID of a clone, without the `D0 02` prefix of ST chips.
+pub const CLONE_UID: u64 = 0x1234_5678_9ABC_DEF0;
+
+/// Data of erased blocks.
+pub const ERASED: u32 = 0xFFFF_FFFF;
+
+/// Full dump of a blank SRIX4K with `SRIX4K_UID`: every block erased.
+pub const BLANK_DUMP: Srix4kDump = Srix4kDump {
+    uid: Some(SRIX4K_UID),
+    eeprom: [ERASED; mem::BLOCK_COUNT],
+    system: Some(ERASED),
+};
+
+/// Full dump of a used SRIX4K with `SRIX4K_UID`, see `sample_block`.
+pub const SAMPLE_DUMP: Srix4kDump = Srix4kDump {
+    uid: Some(SRIX4K_UID),
+    eeprom: sample_eeprom(),
+    system: Some(0xFFFF_FFFE),
+};
+
+/// EEPROM dump of `SAMPLE_DUMP`, without system block and UID, like the
+/// dump files of 512 bytes.
+pub const SAMPLE_EEPROM_DUMP: Srix4kDump = Srix4kDump {
+    uid: None,
+    eeprom: sample_eeprom(),
+    system: None,
+};
+
+/// Data of block `i` of `SAMPLE_DUMP`: the address repeated in each byte,
+/// but for the first counter, decremented once.
+pub const fn sample_block(i: usize) -> u32 {
+    if i == mem::COUNTDOWN.start {
+        ERASED - 1
+    } else {
+        (i as u32) * 0x0101_0101
+    }
+}
+
+/// EEPROM of `SAMPLE_DUMP`.
+const fn sample_eeprom() -> [u32; mem::BLOCK_COUNT] {
+    let mut eeprom = [0; mem::BLOCK_COUNT];
+    let mut i = 0;
+    while i < mem::BLOCK_COUNT {
+        eeprom[i] = sample_block(i);
+        i += 1;
+    }
+    eeprom
+}
+
+/// Exchange of a command with a tag holding `SAMPLE_DUMP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Exchange {
+    /// Command sent.
+    pub command: Command,
+    /// Frame of the command, without CRC.
+    pub frame: &'static [u8],
+    /// Frame answered by the tag, without CRC, empty if none.
+    pub response: &'static [u8],
+}
+
+/// Exchanges of each command with a tag holding `SAMPLE_DUMP`.
+pub const EXCHANGES: [Exchange; 5] = [
+    Exchange {
+        command: Command::GetUid,
+        frame: &[iso14443b2::GET_UID],
+        response: &[0x78, 0x56, 0x34, 0x12, 0x00, 0x0C, 0x02, 0xD0],
+    },
+    Exchange {
+        command: Command::ReadBlock(0x07),
+        frame: &[iso14443b2::READ_BLOCK, 0x07],
+        response: &[0x07, 0x07, 0x07, 0x07],
+    },
+    Exchange {
+        command: Command::ReadBlock(0x05),
+        frame: &[iso14443b2::READ_BLOCK, 0x05],
+        response: &[0xFE, 0xFF, 0xFF, 0xFF],
+    },
+    Exchange {
+        command: Command::ReadBlock(0xFF),
+        frame: &[iso14443b2::READ_BLOCK, 0xFF],
+        response: &[0xFE, 0xFF, 0xFF, 0xFF],
+    },
+    Exchange {
+        command: Command::WriteBlock(0x07, 0x1234_5678),
+        frame: &[iso14443b2::WRITE_BLOCK, 0x07, 0x78, 0x56, 0x34, 0x12],
+        response: &[],
+    },
+];
+
+/// Frames and their CRC_B, see `iso14443b2::crc_b`.
+pub const CRC_B: [(&[u8], u16); 4] = [
+    (b"123456789", 0x906E),
+    (&[iso14443b2::GET_UID], 0x4EAB),
+    (&[iso14443b2::READ_BLOCK, 0x07], 0xB538),
+    (&[iso14443b2::READ_BLOCK, 0xFF], 0xCEFF),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use codec::Response;
+    #[cfg(feature = "zstd")]
+    use dump;
+    use info::TagInfo;
+    use Uid;
+
+    #[test]
+    fn uids_tell_their_chip() {
+        let chip = |uid| mem::layout_of(uid).map(|layout| layout.name());
+        assert_eq!(chip(SRIX4K_UID), Some("SRIX4K"));
+        assert_eq!(chip(SRIX512_UID), Some("SRI512"));
+        assert_eq!(chip(ST25TB02K_UID), Some("ST25TB02K"));
+        assert!(Uid(SRIX4K_UID).is_st_manufactured());
+        assert!(!Uid(CLONE_UID).is_st_manufactured());
+    }
+
+    #[test]
+    fn exchanges_encode_and_decode() {
+        for exchange in &EXCHANGES {
+            let command = exchange.command;
+            assert_eq!(*command.frame(), *exchange.frame, "{:?}", command);
+            assert_eq!(Command::try_from(exchange.frame), Ok(command));
+            let response = Response::decode(&command, exchange.response)
+                .expect("fixture response decodes");
+            let expected = match command {
+                Command::ReadBlock(address) => {
+                    SAMPLE_DUMP.block(address).map(Response::Block)
+                }
+                Command::WriteBlock(_, _) => None,
+                Command::GetUid => SAMPLE_DUMP.uid.map(Response::Uid),
+            };
+            assert_eq!(response, expected, "{:?}", command);
+        }
+    }
+
+    #[test]
+    fn crc_b_of_frames() {
+        for &(frame, crc) in &CRC_B {
+            assert_eq!(iso14443b2::crc_b(frame), crc, "{:02X?}", frame);
+        }
+    }
+
+    #[test]
+    fn dumps_round_trip() {
+        for dump in &[BLANK_DUMP, SAMPLE_DUMP, SAMPLE_EEPROM_DUMP] {
+            assert_eq!(Srix4kDump::parse(&dump.serialize()).as_ref(), Ok(dump));
+        }
+        assert_eq!(
+            Srix4kDump::from(SAMPLE_DUMP.to_bytes()),
+            SAMPLE_EEPROM_DUMP
+        );
+    }
+
+    #[test]
+    fn dumps_starting_as_archives() {
+        let mut dump = SAMPLE_DUMP;
+        dump.eeprom[0] = 0xFD2F_B528;
+        assert_eq!(Srix4kDump::parse(&dump.serialize()), Ok(dump));
+        let archive = [0x28, 0xB5, 0x2F, 0xFD, 0x00];
+        assert!(Srix4kDump::parse(&archive).is_err());
+        let mut bytes = SAMPLE_DUMP.to_bytes();
+        bytes[..archive.len()].copy_from_slice(&archive);
+        assert_eq!(Srix4kDump::from(bytes).to_bytes(), bytes);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn compressed_dumps_round_trip() {
+        for dump in &[BLANK_DUMP, SAMPLE_DUMP, SAMPLE_EEPROM_DUMP] {
+            let archive = dump.serialize_compressed(0).unwrap();
+            assert_eq!(Srix4kDump::parse(&archive).as_ref(), Ok(dump));
+        }
+        // Blocks holding an archive are not decompressed by `From`.
+        let archive = BLANK_DUMP.serialize_compressed(0).unwrap();
+        let mut bytes = [0; dump::EEPROM_DUMP_SIZE];
+        bytes[..archive.len()].copy_from_slice(&archive);
+        assert_eq!(Srix4kDump::from(bytes).to_bytes(), bytes);
+        let oversized = dump::compress(&[0; 4096], 0).unwrap();
+        assert_eq!(
+            Srix4kDump::parse(&oversized),
+            Err(dump::DumpError::InvalidArchive)
+        );
+    }
+
+    #[test]
+    fn sample_dump_differs_from_blank() {
+        let diff = BLANK_DUMP.diff(&SAMPLE_DUMP);
+        assert_eq!(diff.uid, None);
+        // Every EEPROM block and the system block.
+        assert_eq!(diff.blocks.len(), mem::BLOCK_COUNT + 1);
+        assert!(diff.blocks.iter().all(|block| block.old == ERASED));
+        assert!(SAMPLE_DUMP.diff(&SAMPLE_EEPROM_DUMP).is_empty());
+    }
+
+    #[test]
+    fn sample_dump_info() {
+        let info = TagInfo::from_dump(&SAMPLE_DUMP).unwrap();
+        assert_eq!(info.chip, Some("SRIX4K"));
+        assert_eq!(info.chip_id, Some(0xFE));
+        assert_eq!(info.locked_blocks, Some(vec![]));
+        assert_eq!(info.counters[0].bits, ERASED - 1);
+        assert_eq!(info.otp_used, info.otp_blocks);
+        assert_eq!(TagInfo::from_dump(&SAMPLE_EEPROM_DUMP), None);
+    }
+}
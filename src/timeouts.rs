@@ -0,0 +1,77 @@
+//! Time the reader waits for the tag, per command.
+//!
+//! The worst cases differ: `ReadBlock` and `GetUID` are answered right
+//! away, while `WriteBlock` also includes the programming time of the
+//! block. `Srix4k` uses `DEFAULT` until `Srix4k::set_timeouts` overrides
+//! it.
+//!
+//! Marginal antennas, e.g. homemade ones, answer later than the datasheet
+//! allows: `extended` lengthens every timeout by a frame waiting time, and
+//! `set` overrides the one of a command. Selection is bounded by
+//! `DeviceConfig::atr_timeout` instead.
+
+use std::time::Duration;
+
+use iso14443b2;
+use Command;
+
+/// Timeout of each command, `Duration::ZERO` to wait forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CommandTimeouts {
+    /// `ReadBlock`, until the block data is received.
+    pub read_block: Duration,
+    /// `WriteBlock`, until the frame is sent, the programming time of the
+    /// block included.
+    pub write_block: Duration,
+    /// `GetUID`, until the UID is received.
+    pub get_uid: Duration,
+}
+
+/// Margin for slow readers over the answer times of the datasheet.
+const MARGIN: Duration = Duration::from_millis(20);
+
+/// Timeouts used when none are set.
+pub static DEFAULT: CommandTimeouts = CommandTimeouts {
+    read_block: MARGIN,
+    write_block: iso14443b2::WRITE_TIME.saturating_add(MARGIN),
+    get_uid: MARGIN,
+};
+
+impl CommandTimeouts {
+    /// Timeout of `command`.
+    pub fn get(&self, command: &Command) -> Duration {
+        match command {
+            Command::ReadBlock(_) => self.read_block,
+            Command::WriteBlock(_, _) => self.write_block,
+            Command::GetUid => self.get_uid,
+        }
+    }
+    /// Set the timeout of `command`, and of the other commands of the same
+    /// kind.
+    pub fn set(&mut self, command: &Command, timeout: Duration) {
+        match command {
+            Command::ReadBlock(_) => self.read_block = timeout,
+            Command::WriteBlock(_, _) => self.write_block = timeout,
+            Command::GetUid => self.get_uid = timeout,
+        }
+    }
+    /// Timeouts longer by `extra`, e.g.
+    /// `iso14443b2::etus_to_duration(fwt)`. Infinite ones stay infinite.
+    pub fn extended(&self, extra: Duration) -> CommandTimeouts {
+        let extend = |timeout: Duration| match timeout {
+            Duration::ZERO => Duration::ZERO,
+            timeout => timeout.saturating_add(extra),
+        };
+        CommandTimeouts {
+            read_block: extend(self.read_block),
+            write_block: extend(self.write_block),
+            get_uid: extend(self.get_uid),
+        }
+    }
+}
+
+impl Default for CommandTimeouts {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
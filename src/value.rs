@@ -0,0 +1,167 @@
+//! Tear-detecting storage of a value in a pair of blocks: the value block
+//! and a backup block holding a copy or the complement of the value.
+//!
+//! `ValueField::write` writes the backup block first, then the value
+//! block, and reads both back. A write interrupted by the tag leaving the
+//! field leaves at most one block torn:
+//!
+//! | Interrupted while writing | Value block | Backup block |
+//! | --- | --- | --- |
+//! | the backup | old value | torn |
+//! | the value | torn | new value |
+//!
+//! Without a checksum, a torn block cannot be told from an intact one: when
+//! the two disagree, either may hold the good value. `ValueField::read`
+//! then returns `None` and repairs nothing, the application writing the
+//! value again. Data that must survive torn writes belongs in a
+//! `mirror::MirroredRecord`, whose CRC identifies the intact copy.
+
+#[cfg(feature = "nfc")]
+use error::{CachedResult, Context};
+use mem::BlockAddr;
+#[cfg(feature = "nfc")]
+use range::BlockSet;
+#[cfg(feature = "nfc")]
+use {Command, Srix4kCached};
+
+/// What the backup block of a `ValueField` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backup {
+    /// The value itself.
+    Copy,
+    /// The bitwise complement of the value, so that two blocks with the
+    /// same data, e.g. erased ones, do not read as a consistent pair.
+    Complement,
+}
+
+/// Value stored in a value block and a backup block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValueField {
+    /// Block holding the value.
+    pub value: BlockAddr,
+    /// Block holding the backup of the value.
+    pub backup: BlockAddr,
+    /// What the backup block holds.
+    pub kind: Backup,
+}
+
+impl ValueField {
+    /// Value in `value`, backed up in `backup`.
+    pub fn new(value: BlockAddr, backup: BlockAddr, kind: Backup) -> Self {
+        ValueField {
+            value,
+            backup,
+            kind,
+        }
+    }
+    /// Backup block data of `value`.
+    pub fn backup_data(&self, value: u32) -> u32 {
+        match self.kind {
+            Backup::Copy => value,
+            Backup::Complement => !value,
+        }
+    }
+    /// Value of the blocks with `value_data` and `backup_data`, `None` if
+    /// they disagree because a write was torn, see the `value` module.
+    pub fn resolve(&self, value_data: u32, backup_data: u32) -> Option<u32> {
+        // Complementing is its own inverse.
+        if self.backup_data(backup_data) == value_data {
+            Some(value_data)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "nfc")]
+impl ValueField {
+    /// Read the value from the cached blocks, reading those not cached.
+    ///
+    /// Returns `None` if the blocks disagree because a write was torn,
+    /// leaving both blocks as they are, see `resolve`.
+    pub fn read(&self, tag: &mut Srix4kCached) -> CachedResult<Option<u32>> {
+        let value_data = tag.eeprom_get(self.value.into())?;
+        let backup_data = tag.eeprom_get(self.backup.into())?;
+        let value = self.resolve(value_data, backup_data);
+        if value.is_none() {
+            warn!(
+                "Blocks {:#04X} and {:#04X} disagree, a write was torn",
+                self.value.get(),
+                self.backup.get()
+            );
+        }
+        Ok(value)
+    }
+    /// Write `value`: the backup block, then the value block, each synced
+    /// on its own, then read both back from the tag.
+    ///
+    /// Other modified blocks stay in the cache. Fails on the first block
+    /// that cannot be written, with `InvalidArgument` if a group of
+    /// `add_sync_group` holds both blocks, and with `RfTransmissionError`
+    /// if a block does not read back as written.
+    pub fn write(
+        &self,
+        tag: &mut Srix4kCached,
+        value: u32,
+    ) -> CachedResult<()> {
+        let blocks =
+            [(self.backup, self.backup_data(value)), (self.value, value)];
+        for &(block_address, block_data) in &blocks {
+            tag.eeprom_set(block_address.into(), block_data)?;
+            let mut block = BlockSet::EMPTY;
+            block.insert(block_address);
+            tag.sync_blocks(&block)?;
+        }
+        for &(block_address, block_data) in &blocks {
+            let address = block_address.get();
+            if tag.read_block(address)? != block_data {
+                warn!("Block {:#04X}: write not verified", address);
+                return Err(nfc1::Error::RfTransmissionError)
+                    .context(Command::WriteBlock(address, block_data));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(kind: Backup) -> ValueField {
+        let value = BlockAddr::new(0x10).unwrap();
+        let backup = BlockAddr::new(0x11).unwrap();
+        ValueField::new(value, backup, kind)
+    }
+
+    #[test]
+    fn consistent_blocks_resolve() {
+        let copy = field(Backup::Copy);
+        assert_eq!(copy.resolve(1234, 1234), Some(1234));
+        let complement = field(Backup::Complement);
+        assert_eq!(complement.resolve(1234, !1234), Some(1234));
+        assert_eq!(complement.backup_data(1234), !1234);
+    }
+
+    #[test]
+    fn erased_blocks_do_not_resolve_with_complement() {
+        let complement = field(Backup::Complement);
+        assert_eq!(complement.resolve(u32::MAX, u32::MAX), None);
+        assert_eq!(field(Backup::Copy).resolve(0, 0), Some(0));
+    }
+
+    #[test]
+    fn torn_writes_do_not_resolve() {
+        for &kind in &[Backup::Copy, Backup::Complement] {
+            let field = field(kind);
+            let (old, new) = (0x0001_0000, 0x0000_FFFF);
+            // Backup torn, value intact.
+            let torn = (field.backup_data(old) & 0xFFFF_0000)
+                | (field.backup_data(new) & 0x0000_FFFF);
+            assert_eq!(field.resolve(old, torn), None);
+            // Backup written, value torn.
+            let torn = (old & 0xFFFF_0000) | (new & 0x0000_FFFF);
+            assert_eq!(field.resolve(torn, field.backup_data(new)), None);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Polling of counter blocks, reporting their changes over a channel.
+//!
+//! A `CounterWatcher` follows the tags entering the field of a reader, like
+//! `Monitor`, and reads the watched blocks of the tag in the field at each
+//! poll. Every block whose data differs from the last time the tag was
+//! seen is sent as a `CounterChange`, so applications can react to the use
+//! of a tag without polling it themselves. The first time a tag is seen
+//! its blocks are only recorded.
+//!
+//! `nfc1::Device` cannot leave the thread of its context: `spawn` opens the
+//! device and runs the watcher on a thread of its own.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use counter::Counter;
+use monitor::Monitor;
+
+/// Change of a watched block of a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CounterChange {
+    /// UID of the tag.
+    pub uid: u64,
+    /// Address of the block.
+    pub block: u8,
+    /// Block data the last time the tag was seen.
+    pub old: Counter,
+    /// Block data read.
+    pub new: Counter,
+    /// When the block was read.
+    pub timestamp: SystemTime,
+}
+
+/// Reader polling the watched blocks of the tags in its field.
+pub struct CounterWatcher<'a> {
+    /// Tag in the field.
+    monitor: Monitor<'a>,
+    /// Blocks read at each poll.
+    blocks: Vec<u8>,
+    /// Time between two polls of `run`.
+    interval: Duration,
+    /// Last data of the blocks of each tag seen.
+    last: HashMap<(u64, u8), Counter>,
+    /// Channel of the changes.
+    sender: Sender<CounterChange>,
+}
+
+impl CounterWatcher<'_> {
+    /// Watch `blocks`, e.g. the *Count down Counter* blocks 5 and 6, of
+    /// the tags presented to the device, polling them every `interval`.
+    /// Returns the watcher and the receiving end of its changes.
+    pub fn new(
+        device: nfc1::Device<'_>,
+        blocks: Vec<u8>,
+        interval: Duration,
+    ) -> nfc1::Result<(CounterWatcher<'_>, Receiver<CounterChange>)> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher =
+            CounterWatcher::with_sender(device, blocks, interval, sender)?;
+        Ok((watcher, receiver))
+    }
+    /// Watch `blocks`, sending the changes to `sender`.
+    fn with_sender(
+        device: nfc1::Device<'_>,
+        blocks: Vec<u8>,
+        interval: Duration,
+        sender: Sender<CounterChange>,
+    ) -> nfc1::Result<CounterWatcher<'_>> {
+        Ok(CounterWatcher {
+            monitor: Monitor::new(device)?,
+            blocks,
+            interval,
+            last: HashMap::new(),
+            sender,
+        })
+    }
+    /// Read the watched blocks of the tag in the field, if any, and send
+    /// their changes. Returns how many were sent, `None` once the receiver
+    /// is dropped.
+    pub fn poll(&mut self) -> Option<usize> {
+        self.monitor.poll();
+        let uid = match self.monitor.uid() {
+            Some(uid) => uid,
+            None => return Some(0),
+        };
+        let tag = self.monitor.tag()?;
+        let mut sent = 0;
+        for &block in &self.blocks {
+            let new = match tag.send_read_block(block) {
+                Ok(block_data) => Counter::from(block_data),
+                Err(err) => {
+                    debug!("Cannot read block {:#04X}: {}", block, err);
+                    continue;
+                }
+            };
+            match self.last.insert((uid, block), new) {
+                Some(old) if old != new => {
+                    let change = CounterChange {
+                        uid,
+                        block,
+                        old,
+                        new,
+                        timestamp: SystemTime::now(),
+                    };
+                    self.sender.send(change).ok()?;
+                    sent += 1;
+                }
+                _ => {}
+            }
+        }
+        Some(sent)
+    }
+    /// Poll every interval until the receiver is dropped, which is noticed
+    /// when the next change is sent.
+    pub fn run(&mut self) {
+        while self.poll().is_some() {
+            thread::sleep(self.interval);
+        }
+        debug!("Receiver dropped, watcher stopped");
+    }
+}
+
+/// Watch `blocks` of the tags presented to the device of `connstring`, the
+/// default one if `None`, from a new thread, see `CounterWatcher::new`.
+///
+/// The thread returns once the receiver is dropped, see
+/// `CounterWatcher::run`, or with the error opening the device.
+pub fn spawn(
+    connstring: Option<String>,
+    blocks: Vec<u8>,
+    interval: Duration,
+) -> (Receiver<CounterChange>, JoinHandle<nfc1::Result<()>>) {
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let mut context = nfc1::Context::new()?;
+        let device = match connstring {
+            Some(ref connstring) => context.open_with_connstring(connstring),
+            None => context.open(),
+        }?;
+        CounterWatcher::with_sender(device, blocks, interval, sender)?.run();
+        Ok(())
+    });
+    (receiver, handle)
+}
@@ -0,0 +1,121 @@
+//! HTTP webhooks notified of tag events.
+//!
+//! Every time a tag enters or leaves the field the JSON description of the
+//! event, see `Monitor::event_json`, is `POST`ed to the webhook URL.
+//! Only plain `http://` URLs are supported.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Maximum time to wait for the endpoint to accept and answer a request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Endpoint notified of tag events.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    /// `host:port` of the endpoint, with an IPv6 host in brackets.
+    authority: String,
+    /// Path and query of the endpoint.
+    path: String,
+    /// Blocks whose data is included in the arrival notifications.
+    blocks: Vec<u8>,
+}
+
+impl Webhook {
+    /// Parse a `http://host[:port][/path]` URL. An IPv6 host is in
+    /// brackets, e.g. `http://[::1]:8080/events`.
+    pub fn new(url: &str) -> io::Result<Webhook> {
+        let invalid =
+            || io::Error::new(io::ErrorKind::InvalidInput, "invalid URL");
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(ipv6) => {
+                let (host, port) = ipv6.split_once(']').ok_or_else(invalid)?;
+                let port = match port {
+                    "" => None,
+                    port => Some(port.strip_prefix(':').ok_or_else(invalid)?),
+                };
+                if host.is_empty() {
+                    return Err(invalid());
+                }
+                (&authority[..host.len() + 2], port)
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse::<u16>().map_err(|_| invalid())?,
+            None => 80,
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let authority = format!("{}:{}", host, port);
+
+        Ok(Webhook {
+            authority,
+            path: path.to_string(),
+            blocks: Vec::new(),
+        })
+    }
+    /// Set the blocks whose data is included in arrival notifications.
+    /// From 0 to 127, or 255 for system.
+    pub fn set_blocks(&mut self, blocks: Vec<u8>) {
+        self.blocks = blocks;
+    }
+    /// Blocks whose data is included in arrival notifications.
+    pub fn blocks(&self) -> &[u8] {
+        &self.blocks
+    }
+    /// `POST` the JSON payload, failing unless the endpoint answers 2xx.
+    pub fn notify(&self, payload: &str) -> io::Result<()> {
+        debug!("Notifying http://{}{}", self.authority, self.path);
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            self.path,
+            self.authority,
+            payload.len(),
+            payload
+        )?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line)?;
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "unexpected answer `{}`",
+                status_line.trim_end()
+            ))),
+        }
+    }
+    /// Connect to the first address of the endpoint accepting within
+    /// `TIMEOUT`.
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no address for {}", self.authority),
+        );
+        for addr in self.authority.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+}